@@ -0,0 +1,516 @@
+use crate::schemas::listing::Listing;
+use crate::schemas::property_match::{
+    CandidatePair, Property, PropertyIdAssignment, PropertyListing,
+};
+use crate::utils::env_utils::env_number;
+use crate::utils::file_utils::{
+    get_content_lines, get_file_read, get_file_write_truncate, write_to_file,
+};
+use crate::utils::listing_utils::fold_text;
+use crate::utils::minhash_utils::{
+    band_keys, estimated_similarity, minhash_signature, shingles, stable_hash,
+};
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::error::Error;
+use tokio::fs::File;
+
+// Buckets this big come from boilerplate text shared by every listing of an agency
+const MAX_BUCKET_SIZE: usize = 50;
+
+pub struct MatchingConfig {
+    pub price_tolerance: f64,
+    pub area_tolerance: f64,
+    pub text_threshold: f64,
+    pub text_only_threshold: f64,
+}
+
+impl MatchingConfig {
+    pub fn from_env() -> Result<MatchingConfig, Box<dyn Error + Send + Sync>> {
+        Ok(MatchingConfig {
+            price_tolerance: env_number("MATCH_PRICE_TOLERANCE", 0.05)?,
+            area_tolerance: env_number("MATCH_AREA_TOLERANCE", 0.1)?,
+            text_threshold: env_number("MATCH_TEXT_THRESHOLD", 0.3)?,
+            text_only_threshold: env_number("MATCH_TEXT_ONLY_THRESHOLD", 0.8)?,
+        })
+    }
+}
+
+fn relative_difference(value: Option<f64>, other_value: Option<f64>) -> Option<f64> {
+    match (value, other_value) {
+        (Some(value), Some(other_value)) if value > 0.0 && other_value > 0.0 => {
+            Some((value - other_value).abs() / value.max(other_value))
+        }
+        _ => None,
+    }
+}
+
+fn same<T: PartialEq>(value: &Option<T>, other_value: &Option<T>) -> Option<bool> {
+    match (value, other_value) {
+        (Some(value), Some(other_value)) => Some(value == other_value),
+        _ => None,
+    }
+}
+
+/// Compares the freguesia when both portals give it. A district only tells different places
+/// apart, the same district is unknown: Lisboa alone has thousands of similar T2.
+fn same_location(listing: &Listing, other_listing: &Listing) -> Option<bool> {
+    let fold = |value: &Option<String>| value.as_deref().map(fold_text);
+
+    same(&fold(&listing.freguesia), &fold(&other_listing.freguesia)).or_else(|| {
+        same(&listing.district, &other_listing.district).filter(|same_district| !same_district)
+    })
+}
+
+fn compare(
+    config: &MatchingConfig,
+    listing: &Listing,
+    other_listing: &Listing,
+    signature: &[u64],
+    other_signature: &[u64],
+) -> CandidatePair {
+    let price_difference: Option<f64> = relative_difference(
+        listing.price_cents.map(|price| price as f64),
+        other_listing.price_cents.map(|price| price as f64),
+    );
+    let area_difference: Option<f64> = relative_difference(
+        listing.area_sqr_meters.map(|area| area as f64),
+        other_listing.area_sqr_meters.map(|area| area as f64),
+    );
+    let same_typology: Option<bool> = same(&listing.typology, &other_listing.typology);
+    let same_location: Option<bool> = same_location(listing, other_listing);
+    let text_similarity: f64 = estimated_similarity(signature, other_signature);
+
+    let compatible = |same: Option<bool>| same != Some(false);
+    let price_close: bool = price_difference.is_some_and(|diff| diff <= config.price_tolerance);
+    let area_close: bool = area_difference.is_some_and(|diff| diff <= config.area_tolerance);
+
+    // Different agencies rarely share descriptions, so matching numbers and location are enough
+    let strict_match: bool =
+        price_close && area_close && same_typology == Some(true) && same_location == Some(true);
+    let structural_match: bool = price_close
+        && (area_close || area_difference.is_none())
+        && compatible(same_typology)
+        && compatible(same_location)
+        && text_similarity >= config.text_threshold;
+    let text_match: bool = text_similarity >= config.text_only_threshold
+        && compatible(same_typology)
+        && compatible(same_location);
+
+    CandidatePair {
+        left_portal: listing.portal.clone(),
+        left_url_id: listing.url_id.clone(),
+        right_portal: other_listing.portal.clone(),
+        right_url_id: other_listing.url_id.clone(),
+        price_difference,
+        area_difference,
+        same_typology,
+        same_location,
+        text_similarity,
+        is_match: listing.portal != other_listing.portal
+            && (strict_match || structural_match || text_match),
+    }
+}
+
+/// Pairs worth comparing: close prices within the same district and typology, or similar text
+fn candidate_pairs(
+    config: &MatchingConfig,
+    listings: &[Listing],
+    signatures: &[Vec<u64>],
+) -> Vec<(usize, usize)> {
+    let mut pairs: HashSet<(usize, usize)> = HashSet::new();
+    let mut add_pair = |index: usize, other_index: usize| {
+        if listings[index].portal != listings[other_index].portal {
+            pairs.insert((index.min(other_index), index.max(other_index)));
+        }
+    };
+
+    let mut blocks: HashMap<(String, String), Vec<usize>> = HashMap::new();
+    for (index, listing) in listings.iter().enumerate() {
+        if let (Some(district), Some(typology), Some(_)) =
+            (&listing.district, &listing.typology, listing.price_cents)
+        {
+            blocks
+                .entry((district.clone(), format!("{:?}", typology)))
+                .or_default()
+                .push(index);
+        }
+    }
+
+    for block in blocks.values_mut() {
+        block.sort_by_key(|index| listings[*index].price_cents);
+
+        for (position, index) in block.iter().enumerate() {
+            let max_price: f64 = listings[*index].price_cents.unwrap_or_default() as f64
+                / (1.0 - config.price_tolerance);
+
+            for other_index in block[position + 1..].iter().take_while(|other_index| {
+                (listings[**other_index].price_cents.unwrap_or_default() as f64) <= max_price
+            }) {
+                add_pair(*index, *other_index);
+            }
+        }
+    }
+
+    let mut buckets: HashMap<u64, Vec<usize>> = HashMap::new();
+    for (index, signature) in signatures.iter().enumerate() {
+        for key in band_keys(signature) {
+            buckets.entry(key).or_default().push(index);
+        }
+    }
+
+    for bucket in buckets.values() {
+        if bucket.len() > MAX_BUCKET_SIZE {
+            continue;
+        }
+        for (position, index) in bucket.iter().enumerate() {
+            for other_index in &bucket[position + 1..] {
+                add_pair(*index, *other_index);
+            }
+        }
+    }
+
+    let mut pairs: Vec<(usize, usize)> = pairs.into_iter().collect();
+    pairs.sort();
+    pairs
+}
+
+fn find_root(parents: &mut [usize], index: usize) -> usize {
+    let mut root: usize = index;
+    while parents[root] != root {
+        root = parents[root];
+    }
+    // Path compression
+    let mut current: usize = index;
+    while parents[current] != root {
+        let next: usize = parents[current];
+        parents[current] = root;
+        current = next;
+    }
+    root
+}
+
+fn listing_key(portal: &str, url_id: &str) -> String {
+    format!("{} {}", portal, url_id)
+}
+
+/// Keeps the id a property had in previous runs, new properties get one derived from a member
+fn assign_property_ids(
+    clusters: &[Vec<usize>],
+    listings: &[Listing],
+    previous_ids: &HashMap<String, String>,
+) -> Vec<String> {
+    let mut used_ids: HashSet<String> = HashSet::new();
+
+    clusters
+        .iter()
+        .map(|members| {
+            let keys: Vec<String> = members
+                .iter()
+                .map(|index| listing_key(&listings[*index].portal, &listings[*index].url_id))
+                .collect();
+
+            let previous_id: Option<String> = keys
+                .iter()
+                .filter_map(|key| previous_ids.get(key))
+                .filter(|id| !used_ids.contains(*id))
+                .min()
+                .cloned();
+
+            let property_id: String = previous_id.unwrap_or_else(|| {
+                let min_key: &String = keys.iter().min().unwrap();
+                format!("P{:016x}", stable_hash(min_key.as_bytes()))
+            });
+
+            used_ids.insert(property_id.clone());
+            property_id
+        })
+        .collect()
+}
+
+async fn read_previous_ids(
+    path: &str,
+) -> Result<HashMap<String, String>, Box<dyn Error + Send + Sync>> {
+    let mut previous_ids: HashMap<String, String> = HashMap::new();
+
+    if let Ok(file) = get_file_read(path).await {
+        for line in get_content_lines(file).await? {
+            let assignment: PropertyIdAssignment = serde_json::from_str(&line)?;
+            previous_ids.insert(
+                listing_key(&assignment.portal, &assignment.url_id),
+                assignment.property_id,
+            );
+        }
+    }
+
+    Ok(previous_ids)
+}
+
+async fn duplicates_mechanism(
+    input_path: &str,
+    output_path: &str,
+    matches_path: &str,
+    property_ids_path: &str,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let config: MatchingConfig = MatchingConfig::from_env()?;
+
+    let mut listings: Vec<Listing> = Vec::new();
+    for line in get_content_lines(get_file_read(input_path).await?).await? {
+        listings.push(serde_json::from_str::<Listing>(&line)?);
+    }
+
+    let signatures: Vec<Vec<u64>> = listings
+        .iter()
+        .map(|listing| {
+            minhash_signature(&shingles(
+                listing.description.as_deref().unwrap_or_default(),
+                3,
+            ))
+        })
+        .collect();
+
+    let pairs: Vec<(usize, usize)> = candidate_pairs(&config, &listings, &signatures);
+    println!(
+        "Comparing {} candidate pairs of {} listings",
+        pairs.len(),
+        listings.len()
+    );
+
+    let mut write_matches: File = get_file_write_truncate(matches_path).await?;
+    let mut parents: Vec<usize> = (0..listings.len()).collect();
+    let mut no_matches: usize = 0;
+
+    for (index, other_index) in pairs {
+        let candidate_pair: CandidatePair = compare(
+            &config,
+            &listings[index],
+            &listings[other_index],
+            &signatures[index],
+            &signatures[other_index],
+        );
+
+        if candidate_pair.is_match {
+            let root: usize = find_root(&mut parents, index);
+            let other_root: usize = find_root(&mut parents, other_index);
+            parents[root.max(other_root)] = root.min(other_root);
+            no_matches += 1;
+        }
+
+        write_to_file(&mut write_matches, format!("{}\n", json!(candidate_pair))).await?;
+    }
+
+    let mut clusters: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for index in 0..listings.len() {
+        let root: usize = find_root(&mut parents, index);
+        clusters.entry(root).or_default().push(index);
+    }
+    let clusters: Vec<Vec<usize>> = clusters.into_values().collect();
+
+    let previous_ids: HashMap<String, String> = read_previous_ids(property_ids_path).await?;
+    let property_ids: Vec<String> = assign_property_ids(&clusters, &listings, &previous_ids);
+
+    let mut write_output: File = get_file_write_truncate(output_path).await?;
+    let mut write_property_ids: File = get_file_write_truncate(property_ids_path).await?;
+    let mut no_duplicated: usize = 0;
+
+    for (members, property_id) in clusters.iter().zip(property_ids) {
+        let property: Property = Property {
+            property_id,
+            listings: members
+                .iter()
+                .map(|index| PropertyListing {
+                    portal: listings[*index].portal.clone(),
+                    url_id: listings[*index].url_id.clone(),
+                    url: listings[*index].url.clone(),
+                    price_cents: listings[*index].price_cents,
+                })
+                .collect(),
+        };
+
+        if property.listings.len() > 1 {
+            no_duplicated += 1;
+        }
+
+        for listing in &property.listings {
+            let assignment: PropertyIdAssignment = PropertyIdAssignment {
+                portal: listing.portal.clone(),
+                url_id: listing.url_id.clone(),
+                property_id: property.property_id.clone(),
+            };
+            write_to_file(&mut write_property_ids, format!("{}\n", json!(assignment))).await?;
+        }
+        write_to_file(&mut write_output, format!("{}\n", json!(property))).await?;
+    }
+
+    println!(
+        "{} matches, {} listings are {} properties, {} of them listed on more than one portal",
+        no_matches,
+        listings.len(),
+        clusters.len(),
+        no_duplicated
+    );
+
+    Ok(())
+}
+
+pub async fn run(input_path: &str, output_path: &str, matches_path: &str, property_ids_path: &str) {
+    match duplicates_mechanism(input_path, output_path, matches_path, property_ids_path).await {
+        Ok(_) => println!("Duplicates mechanism finished"),
+        Err(e) => eprintln!("Error: {:?}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schemas::listing::Typology;
+
+    const DESCRIPTION: &str = "Apartamento T2 totalmente remodelado junto ao metro de Arroios com \
+        cozinha equipada e varanda com vista desafogada";
+
+    fn config() -> MatchingConfig {
+        MatchingConfig {
+            price_tolerance: 0.05,
+            area_tolerance: 0.1,
+            text_threshold: 0.3,
+            text_only_threshold: 0.8,
+        }
+    }
+
+    fn listing(portal: &str, url_id: &str, price: u64, freguesia: Option<&str>) -> Listing {
+        Listing {
+            portal: portal.to_string(),
+            url_id: url_id.to_string(),
+            price_cents: Some(price * 100),
+            area_sqr_meters: Some(82.0),
+            typology: Some(Typology::T2),
+            district: Some("lisboa".to_string()),
+            freguesia: freguesia.map(|freguesia| freguesia.to_string()),
+            ..Listing::default()
+        }
+    }
+
+    fn signature(text: &str) -> Vec<u64> {
+        minhash_signature(&shingles(text, 3))
+    }
+
+    #[test]
+    fn matches_close_listings_in_the_same_freguesia() {
+        let remax: Listing = listing("remax", "1", 385_000, Some("Arroios"));
+        let idealista: Listing = listing("idealista", "2", 380_000, Some("arroios"));
+        let elsewhere: Listing = listing("idealista", "3", 380_000, Some("Belém"));
+
+        assert!(
+            compare(
+                &config(),
+                &remax,
+                &idealista,
+                &signature("a"),
+                &signature("b")
+            )
+            .is_match
+        );
+        assert!(
+            !compare(
+                &config(),
+                &remax,
+                &elsewhere,
+                &signature("a"),
+                &signature("b")
+            )
+            .is_match
+        );
+    }
+
+    #[test]
+    fn needs_similar_text_when_only_the_district_matches() {
+        let remax: Listing = listing("remax", "1", 385_000, None);
+        let idealista: Listing = listing("idealista", "2", 380_000, None);
+        let pair = |text: &str, other_text: &str| {
+            compare(
+                &config(),
+                &remax,
+                &idealista,
+                &signature(text),
+                &signature(other_text),
+            )
+        };
+
+        let unrelated: CandidatePair = pair(DESCRIPTION, "Moradia isolada com piscina e jardim");
+        assert_eq!(unrelated.same_location, None);
+        assert!(!unrelated.is_match);
+        assert!(pair(DESCRIPTION, DESCRIPTION).is_match);
+    }
+
+    #[test]
+    fn never_matches_the_same_portal() {
+        let listing_1: Listing = listing("remax", "1", 385_000, Some("Arroios"));
+        let listing_2: Listing = listing("remax", "2", 385_000, Some("Arroios"));
+
+        assert!(
+            !compare(
+                &config(),
+                &listing_1,
+                &listing_2,
+                &signature(DESCRIPTION),
+                &signature(DESCRIPTION)
+            )
+            .is_match
+        );
+    }
+
+    #[test]
+    fn pairs_close_prices_and_similar_texts_across_portals() {
+        let listings: Vec<Listing> = vec![
+            listing("remax", "1", 385_000, None),
+            listing("idealista", "2", 380_000, None),
+            listing("remax", "3", 380_000, None),
+            listing("era", "4", 900_000, None),
+            Listing {
+                district: Some("porto".to_string()),
+                ..listing("era", "5", 120_000, None)
+            },
+        ];
+        let signatures: Vec<Vec<u64>> = vec![
+            signature("Moradia isolada com piscina e jardim"),
+            signature("Loja no centro comercial"),
+            signature("Terreno para construção"),
+            signature("Armazém com escritório"),
+            signature("Moradia isolada com piscina e jardim"),
+        ];
+
+        assert_eq!(
+            candidate_pairs(&config(), &listings, &signatures),
+            // Close prices in Lisboa, then the same text in another district
+            vec![(0, 1), (0, 4), (1, 2)]
+        );
+    }
+
+    #[test]
+    fn keeps_property_ids_across_runs() {
+        let listings: Vec<Listing> = vec![
+            listing("remax", "1", 385_000, None),
+            listing("idealista", "2", 380_000, None),
+            listing("era", "3", 900_000, None),
+        ];
+        let clusters: Vec<Vec<usize>> = vec![vec![0, 1], vec![2]];
+
+        let first_ids: Vec<String> = assign_property_ids(&clusters, &listings, &HashMap::new());
+        let previous_ids: HashMap<String, String> = HashMap::from([
+            (listing_key("idealista", "2"), first_ids[0].clone()),
+            (listing_key("era", "3"), "P-kept".to_string()),
+        ]);
+        // The remax listing moved to the cluster of the era one
+        let next_clusters: Vec<Vec<usize>> = vec![vec![1], vec![0, 2]];
+
+        assert_ne!(first_ids[0], first_ids[1]);
+        assert_eq!(
+            assign_property_ids(&clusters, &listings, &HashMap::new()),
+            first_ids
+        );
+        assert_eq!(
+            assign_property_ids(&next_clusters, &listings, &previous_ids),
+            vec![first_ids[0].clone(), "P-kept".to_string()]
+        );
+    }
+}
@@ -0,0 +1,45 @@
+use crate::scrappers::scrapper::PortalScrapper;
+use crate::storage::listing_storage::{open_storage, ListingStorage};
+use crate::utils::file_utils::{get_file_write_truncate, write_to_file};
+use serde_json::json;
+use std::error::Error;
+use tokio::fs::File;
+
+async fn normalize_mechanism(
+    scrappers: &[Box<dyn PortalScrapper>],
+    output_path: &str,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let storage: Box<dyn ListingStorage> = open_storage()?;
+    let mut write_output: File = get_file_write_truncate(output_path).await?;
+
+    for scrapper in scrappers {
+        let raw_listings: Vec<String> = storage.raw_listings(scrapper.portal()).await?;
+        if raw_listings.is_empty() {
+            println!("No {} listings to normalize", scrapper.portal());
+            continue;
+        }
+
+        let mut normalized: usize = 0;
+
+        for raw_listing in raw_listings {
+            match scrapper.normalize_line(&raw_listing) {
+                Ok(listing) => {
+                    write_to_file(&mut write_output, format!("{}\n", json!(listing))).await?;
+                    normalized += 1;
+                }
+                Err(e) => println!("Failed to normalize {} line: {}", scrapper.portal(), e),
+            }
+        }
+
+        println!("Normalized {} {} listings", normalized, scrapper.portal());
+    }
+
+    Ok(())
+}
+
+pub async fn run(scrappers: &[Box<dyn PortalScrapper>], output_path: &str) {
+    match normalize_mechanism(scrappers, output_path).await {
+        Ok(_) => println!("Normalize mechanism finished"),
+        Err(e) => eprintln!("Error: {:?}", e),
+    }
+}
@@ -0,0 +1,50 @@
+use crate::schemas::listing_history::PriceDrop;
+use crate::storage::listing_storage::{open_storage, ListingStorage};
+use crate::utils::file_utils::{get_file_write_truncate, write_to_file};
+use crate::utils::time_utils::{unix_timestamp, SECONDS_PER_DAY};
+use serde_json::json;
+use std::error::Error;
+use tokio::fs::File;
+
+async fn price_drops_mechanism(
+    min_drop_percentage: f64,
+    days: i64,
+    output_path: &str,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let storage: Box<dyn ListingStorage> = open_storage()?;
+    let mut write_output: File = get_file_write_truncate(output_path).await?;
+
+    let price_drops: Vec<PriceDrop> = storage
+        .price_drops(
+            min_drop_percentage,
+            unix_timestamp() - days * SECONDS_PER_DAY,
+        )
+        .await?;
+
+    for price_drop in &price_drops {
+        println!(
+            "{:.1}% {} -> {} {}",
+            price_drop.drop_percentage,
+            price_drop.old_price_cents / 100,
+            price_drop.new_price_cents / 100,
+            price_drop.url
+        );
+        write_to_file(&mut write_output, format!("{}\n", json!(price_drop))).await?;
+    }
+
+    println!(
+        "{} listings dropped their price more than {}% in the last {} days",
+        price_drops.len(),
+        min_drop_percentage,
+        days
+    );
+
+    Ok(())
+}
+
+pub async fn run(min_drop_percentage: f64, days: i64, output_path: &str) {
+    match price_drops_mechanism(min_drop_percentage, days, output_path).await {
+        Ok(_) => println!("Price drops mechanism finished"),
+        Err(e) => eprintln!("Error: {:?}", e),
+    }
+}
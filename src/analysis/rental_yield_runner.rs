@@ -0,0 +1,336 @@
+use crate::schemas::listing::{Listing, PropertyKind, Transaction, Typology};
+use crate::schemas::rental_yield::RentalYield;
+use crate::utils::env_utils::env_number;
+use crate::utils::file_utils::{get_content_lines, get_file_read, write_atomically};
+use crate::utils::listing_utils::fold_text;
+use serde_json::json;
+use std::collections::HashMap;
+use std::error::Error;
+
+/// Where comparable rentals are: same concelho, freguesia, typology and kind of property
+type ComparablesKey = (String, String, Typology, PropertyKind);
+
+pub struct YieldConfig {
+    pub min_comparables: usize,
+    /// Share of the rent spent on condominium, taxes, insurance and maintenance
+    pub expenses_ratio: f64,
+    /// Months a year the property is expected to be empty
+    pub vacancy_months: f64,
+    /// Taxes and fees paid on top of the price when buying, as a share of it
+    pub purchase_costs_ratio: f64,
+}
+
+impl YieldConfig {
+    pub fn from_env() -> Result<YieldConfig, Box<dyn Error + Send + Sync>> {
+        Ok(YieldConfig {
+            min_comparables: env_number("RENTAL_MIN_COMPARABLES", 3)?,
+            expenses_ratio: env_number("YIELD_EXPENSES_RATIO", 0.25)?,
+            vacancy_months: env_number("YIELD_VACANCY_MONTHS", 1.0)?,
+            purchase_costs_ratio: env_number("YIELD_PURCHASE_COSTS_RATIO", 0.08)?,
+        })
+    }
+}
+
+/// None when the listing is missing any of them, a whole concelho is too wide to compare and
+/// land, shops or garages without a typology are not comparable with each other
+fn comparables_key(listing: &Listing) -> Option<ComparablesKey> {
+    let fold = |value: &Option<String>| value.as_deref().map(fold_text);
+
+    Some((
+        fold(&listing.concelho)?,
+        fold(&listing.freguesia)?,
+        listing.typology?,
+        listing.property_kind?,
+    ))
+}
+
+fn median(mut values: Vec<f64>) -> Option<f64> {
+    values.sort_by(|value, other_value| value.total_cmp(other_value));
+    let middle: usize = values.len() / 2;
+
+    match values.len() {
+        0 => None,
+        len if len % 2 == 0 => Some((values[middle - 1] + values[middle]) / 2.0),
+        _ => Some(values[middle]),
+    }
+}
+
+/// The monthly rent of a listing for sale from its comparable rentals: the median rent per m²
+/// times its area when both have one, the median rent otherwise
+fn estimate_rent(listing: &Listing, rentals: &[&Listing]) -> Option<u64> {
+    let rents_per_sqr_meter: Vec<f64> = rentals
+        .iter()
+        .filter_map(
+            |rental| match (rental.price_cents, rental.area_sqr_meters) {
+                (Some(rent), Some(area)) if area > 0.0 => Some(rent as f64 / area as f64),
+                _ => None,
+            },
+        )
+        .collect();
+
+    let rent: f64 = match (listing.area_sqr_meters, median(rents_per_sqr_meter)) {
+        (Some(area), Some(rent_per_sqr_meter)) if area > 0.0 => rent_per_sqr_meter * area as f64,
+        _ => median(
+            rentals
+                .iter()
+                .filter_map(|rental| rental.price_cents.map(|rent| rent as f64))
+                .collect(),
+        )?,
+    };
+    Some(rent.round() as u64)
+}
+
+fn rental_yield(
+    config: &YieldConfig,
+    listing: &Listing,
+    rentals: &[&Listing],
+) -> Option<RentalYield> {
+    let price_cents: u64 = listing.price_cents.filter(|price| *price > 0)?;
+    let rent_cents: u64 = estimate_rent(listing, rentals)?;
+
+    let price: f64 = price_cents as f64;
+    let rent: f64 = rent_cents as f64;
+
+    Some(RentalYield {
+        portal: listing.portal.clone(),
+        url_id: listing.url_id.clone(),
+        url: listing.url.clone(),
+        price_cents,
+        concelho: listing.concelho.clone(),
+        freguesia: listing.freguesia.clone(),
+        typology: listing.typology,
+        estimated_monthly_rent_cents: rent_cents,
+        comparables: rentals.len(),
+        gross_yield: rent * 12.0 / price,
+        net_yield: rent * (12.0 - config.vacancy_months) * (1.0 - config.expenses_ratio)
+            / (price * (1.0 + config.purchase_costs_ratio)),
+    })
+}
+
+/// Yields of the listings for sale with enough comparable rentals, the highest net yield first.
+/// Also returns how many listings for sale had too few comparables or nothing to compare by.
+fn rental_yields(config: &YieldConfig, listings: &[Listing]) -> (Vec<RentalYield>, usize) {
+    let mut rentals: HashMap<ComparablesKey, Vec<&Listing>> = HashMap::new();
+    for listing in listings
+        .iter()
+        .filter(|listing| listing.transaction == Transaction::Rent)
+    {
+        if let (Some(key), Some(_)) = (comparables_key(listing), listing.price_cents) {
+            rentals.entry(key).or_default().push(listing);
+        }
+    }
+
+    let mut yields: Vec<RentalYield> = Vec::new();
+    let mut without_comparables: usize = 0;
+
+    for listing in listings
+        .iter()
+        .filter(|listing| listing.transaction == Transaction::Buy)
+    {
+        let comparables: &[&Listing] = comparables_key(listing)
+            .and_then(|key| rentals.get(&key))
+            .map(|rentals| rentals.as_slice())
+            .unwrap_or_default();
+
+        if comparables.len() < config.min_comparables {
+            without_comparables += 1;
+            continue;
+        }
+        yields.extend(rental_yield(config, listing, comparables));
+    }
+
+    yields.sort_by(|rental_yield, other_rental_yield| {
+        other_rental_yield
+            .net_yield
+            .total_cmp(&rental_yield.net_yield)
+    });
+    (yields, without_comparables)
+}
+
+async fn rental_yield_mechanism(
+    input_path: &str,
+    output_path: &str,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let config: YieldConfig = YieldConfig::from_env()?;
+
+    let mut listings: Vec<Listing> = Vec::new();
+    for line in get_content_lines(get_file_read(input_path).await?).await? {
+        listings.push(serde_json::from_str::<Listing>(&line)?);
+    }
+
+    let no_rentals: usize = listings
+        .iter()
+        .filter(|listing| listing.transaction == Transaction::Rent)
+        .count();
+    let (yields, without_comparables): (Vec<RentalYield>, usize) =
+        rental_yields(&config, &listings);
+
+    let content: String = yields
+        .iter()
+        .map(|rental_yield| format!("{}\n", json!(rental_yield)))
+        .collect();
+    write_atomically(output_path, content).await?;
+
+    println!(
+        "Estimated the yield of {} listings for sale from {} rentals, {} had nothing to compare by or less than {} comparable rentals",
+        yields.len(),
+        no_rentals,
+        without_comparables,
+        config.min_comparables
+    );
+
+    Ok(())
+}
+
+pub async fn run(input_path: &str, output_path: &str) {
+    match rental_yield_mechanism(input_path, output_path).await {
+        Ok(_) => println!("Rental yield mechanism finished"),
+        Err(e) => eprintln!("Error: {:?}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: YieldConfig = YieldConfig {
+        min_comparables: 2,
+        expenses_ratio: 0.25,
+        vacancy_months: 1.0,
+        purchase_costs_ratio: 0.08,
+    };
+
+    fn listing(
+        url_id: &str,
+        transaction: Transaction,
+        freguesia: Option<&str>,
+        price_cents: u64,
+        area_sqr_meters: Option<f32>,
+    ) -> Listing {
+        Listing {
+            url_id: url_id.to_string(),
+            transaction,
+            concelho: Some("Lisboa".to_string()),
+            freguesia: freguesia.map(str::to_string),
+            typology: Some(Typology::T2),
+            property_kind: Some(PropertyKind::Apartment),
+            price_cents: Some(price_cents),
+            area_sqr_meters,
+            ..Listing::default()
+        }
+    }
+
+    #[test]
+    fn takes_the_median() {
+        assert_eq!(median(vec![]), None);
+        assert_eq!(median(vec![3.0, 1.0, 2.0]), Some(2.0));
+        assert_eq!(median(vec![4.0, 1.0, 3.0, 2.0]), Some(2.5));
+    }
+
+    #[test]
+    fn estimates_the_rent_from_the_comparables() {
+        let small: Listing = listing("1", Transaction::Rent, Some("Arroios"), 100_000, Some(50.0));
+        let big: Listing = listing(
+            "2",
+            Transaction::Rent,
+            Some("Arroios"),
+            180_000,
+            Some(100.0),
+        );
+        let no_area: Listing = listing("3", Transaction::Rent, Some("Arroios"), 150_000, None);
+        let for_sale: Listing = listing(
+            "4",
+            Transaction::Buy,
+            Some("Arroios"),
+            30_000_000,
+            Some(80.0),
+        );
+        let for_sale_without_area: Listing =
+            listing("5", Transaction::Buy, Some("Arroios"), 30_000_000, None);
+
+        // Median of 2000 and 1800 cents per m² times 80 m²
+        assert_eq!(
+            estimate_rent(&for_sale, &[&small, &big, &no_area]),
+            Some(152_000)
+        );
+        assert_eq!(estimate_rent(&for_sale, &[&no_area]), Some(150_000));
+        assert_eq!(
+            estimate_rent(&for_sale_without_area, &[&small, &big, &no_area]),
+            Some(150_000)
+        );
+        assert_eq!(estimate_rent(&for_sale, &[]), None);
+    }
+
+    #[test]
+    fn computes_the_gross_and_net_yields() {
+        let rental: Listing = listing("1", Transaction::Rent, Some("Arroios"), 100_000, None);
+        let for_sale: Listing = listing("2", Transaction::Buy, Some("Arroios"), 24_000_000, None);
+
+        let rental_yield: RentalYield = rental_yield(&CONFIG, &for_sale, &[&rental]).unwrap();
+
+        assert_eq!(rental_yield.estimated_monthly_rent_cents, 100_000);
+        assert_eq!(rental_yield.gross_yield, 0.05);
+        // 11 months of rent less 25% of expenses over the price plus 8% of purchase costs
+        assert!((rental_yield.net_yield - 825_000.0 / 25_920_000.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn leaves_out_listings_without_enough_comparables() {
+        let house = |listing: Listing| Listing {
+            property_kind: Some(PropertyKind::House),
+            ..listing
+        };
+        let land = |listing: Listing| Listing {
+            typology: None,
+            property_kind: Some(PropertyKind::Land),
+            ..listing
+        };
+        let listings: Vec<Listing> = vec![
+            listing("1", Transaction::Rent, Some("Arroios"), 100_000, None),
+            listing("2", Transaction::Rent, Some("Arroios"), 120_000, None),
+            listing("3", Transaction::Rent, Some("Areeiro"), 90_000, None),
+            listing("4", Transaction::Rent, None, 90_000, None),
+            listing("5", Transaction::Rent, None, 90_000, None),
+            listing("6", Transaction::Buy, Some("Arroios"), 30_000_000, None),
+            listing("7", Transaction::Buy, Some("Areeiro"), 30_000_000, None),
+            listing("8", Transaction::Buy, None, 30_000_000, None),
+            // Houses are not compared with apartments, nor land without a typology with itself
+            house(listing(
+                "9",
+                Transaction::Buy,
+                Some("Arroios"),
+                50_000_000,
+                None,
+            )),
+            land(listing(
+                "10",
+                Transaction::Rent,
+                Some("Arroios"),
+                20_000,
+                None,
+            )),
+            land(listing(
+                "11",
+                Transaction::Rent,
+                Some("Arroios"),
+                30_000,
+                None,
+            )),
+            land(listing(
+                "12",
+                Transaction::Buy,
+                Some("Arroios"),
+                5_000_000,
+                None,
+            )),
+        ];
+
+        let (yields, without_comparables): (Vec<RentalYield>, usize) =
+            rental_yields(&CONFIG, &listings);
+
+        assert_eq!(yields.len(), 1);
+        assert_eq!(yields[0].url_id, "6");
+        assert_eq!(yields[0].comparables, 2);
+        assert_eq!(without_comparables, 4);
+    }
+}
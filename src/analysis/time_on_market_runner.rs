@@ -0,0 +1,71 @@
+use crate::schemas::listing_history::{DistrictTimeOnMarket, TimeOnMarket};
+use crate::storage::listing_storage::{open_storage, ListingStorage};
+use crate::utils::file_utils::{get_file_write_truncate, write_to_file};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::error::Error;
+use tokio::fs::File;
+
+fn by_district(time_on_market: &[TimeOnMarket]) -> Vec<DistrictTimeOnMarket> {
+    let mut districts: BTreeMap<String, (u32, Vec<f64>)> = BTreeMap::new();
+
+    for listing in time_on_market {
+        let district: String = listing.district.clone().unwrap_or("unknown".to_string());
+        let (no_active, delisted_days) = districts.entry(district).or_default();
+
+        match listing.delisted_at {
+            Some(_) => delisted_days.push(listing.days_on_market),
+            None => *no_active += 1,
+        }
+    }
+
+    districts
+        .into_iter()
+        .map(
+            |(district, (no_active, delisted_days))| DistrictTimeOnMarket {
+                district,
+                no_active,
+                no_delisted: delisted_days.len() as u32,
+                average_days_on_market: if delisted_days.is_empty() {
+                    None
+                } else {
+                    Some(delisted_days.iter().sum::<f64>() / delisted_days.len() as f64)
+                },
+            },
+        )
+        .collect()
+}
+
+async fn time_on_market_mechanism(
+    output_path: &str,
+    summary_path: &str,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let storage: Box<dyn ListingStorage> = open_storage()?;
+    let time_on_market: Vec<TimeOnMarket> = storage.time_on_market().await?;
+
+    let mut write_output: File = get_file_write_truncate(output_path).await?;
+    for listing in &time_on_market {
+        write_to_file(&mut write_output, format!("{}\n", json!(listing))).await?;
+    }
+
+    let mut write_summary: File = get_file_write_truncate(summary_path).await?;
+    for district in by_district(&time_on_market) {
+        println!(
+            "{}: {} active, {} delisted, {:.1} average days on market",
+            district.district,
+            district.no_active,
+            district.no_delisted,
+            district.average_days_on_market.unwrap_or_default()
+        );
+        write_to_file(&mut write_summary, format!("{}\n", json!(district))).await?;
+    }
+
+    Ok(())
+}
+
+pub async fn run(output_path: &str, summary_path: &str) {
+    match time_on_market_mechanism(output_path, summary_path).await {
+        Ok(_) => println!("Time on market mechanism finished"),
+        Err(e) => eprintln!("Error: {:?}", e),
+    }
+}
@@ -0,0 +1,148 @@
+use crate::llms::llm_utils::{REPAIR_CONTENT, SYSTEM_CONTENT, USER_CONTENT};
+use crate::schemas::llm::LLMCacheEntry;
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+
+fn sha256_hex(content: &str) -> String {
+    Sha256::digest(content.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Changes whenever any of the prompts changes, invalidating the answers cached with the old ones
+pub fn prompt_version() -> String {
+    sha256_hex(&[SYSTEM_CONTENT, USER_CONTENT, REPAIR_CONTENT].join("\n"))[..12].to_string()
+}
+
+/// Sorts the keys and collapses the whitespace of the strings, so only real changes of the listing
+/// change its hash
+fn canonical_json(value: &Value) -> Value {
+    match value {
+        Value::Object(fields) => {
+            let mut names: Vec<&String> = fields.keys().collect();
+            names.sort();
+            Value::Object(
+                names
+                    .into_iter()
+                    .map(|name| (name.clone(), canonical_json(&fields[name])))
+                    .collect::<Map<String, Value>>(),
+            )
+        }
+        Value::Array(values) => Value::Array(values.iter().map(canonical_json).collect()),
+        Value::String(text) => {
+            Value::String(text.split_whitespace().collect::<Vec<&str>>().join(" "))
+        }
+        value => value.clone(),
+    }
+}
+
+/// What an LLM answer depends on, its hash is the key of the cache
+pub struct LlmCacheKey {
+    pub portal: String,
+    pub url_id: String,
+    pub content_hash: String,
+    pub prompt_version: String,
+    pub model: String,
+}
+
+impl LlmCacheKey {
+    pub fn new(portal: &str, content_line: &str, model: &str) -> serde_json::Result<LlmCacheKey> {
+        let content: Value = canonical_json(&serde_json::from_str(content_line)?);
+
+        Ok(LlmCacheKey {
+            portal: portal.to_string(),
+            url_id: content["url_id"].as_str().unwrap_or_default().to_string(),
+            content_hash: sha256_hex(&content.to_string()),
+            prompt_version: prompt_version(),
+            model: model.to_string(),
+        })
+    }
+
+    pub fn key(&self) -> String {
+        sha256_hex(
+            &[
+                self.portal.as_str(),
+                &self.url_id,
+                &self.content_hash,
+                &self.prompt_version,
+                &self.model,
+            ]
+            .join("\n"),
+        )
+    }
+
+    pub fn to_entry(&self, response: String) -> LLMCacheEntry {
+        LLMCacheEntry {
+            portal: self.portal.clone(),
+            url_id: self.url_id.clone(),
+            content_hash: self.content_hash.clone(),
+            prompt_version: self.prompt_version.clone(),
+            model: self.model.clone(),
+            response,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTENT: &str = r#"{"url_id": "/imovel/123", "price": "385 000 €", "features": ["Garagem", "Varanda"], "description": "T2 remodelado\n junto ao metro"}"#;
+
+    fn key(portal: &str, content_line: &str, model: &str) -> String {
+        LlmCacheKey::new(portal, content_line, model).unwrap().key()
+    }
+
+    #[test]
+    fn ignores_key_order_and_whitespace() {
+        let reformatted: &str = r#"{
+            "description": "  T2 remodelado junto   ao metro ",
+            "features": ["Garagem", "Varanda"],
+            "price": "385 000 €",
+            "url_id": "/imovel/123"
+        }"#;
+
+        assert_eq!(
+            key("remax", CONTENT, "gpt-4o-mini"),
+            key("remax", reformatted, "gpt-4o-mini")
+        );
+    }
+
+    #[test]
+    fn changes_with_everything_the_answer_depends_on() {
+        let base: String = key("remax", CONTENT, "gpt-4o-mini");
+        let mut new_prompt: LlmCacheKey =
+            LlmCacheKey::new("remax", CONTENT, "gpt-4o-mini").unwrap();
+        new_prompt.prompt_version = "000000000000".to_string();
+
+        assert_ne!(base, new_prompt.key());
+        assert_ne!(base, key("remax", CONTENT, "llama3"));
+        assert_ne!(base, key("idealista", CONTENT, "gpt-4o-mini"));
+        assert_ne!(
+            base,
+            key(
+                "remax",
+                &CONTENT.replace("385 000", "375 000"),
+                "gpt-4o-mini"
+            )
+        );
+        assert_ne!(
+            base,
+            key(
+                "remax",
+                &CONTENT.replace("\"Varanda\"", "\"Varanda\", \"Piscina\""),
+                "gpt-4o-mini"
+            )
+        );
+    }
+
+    #[test]
+    fn keeps_the_url_id_of_the_listing() {
+        let cache_key: LlmCacheKey = LlmCacheKey::new("remax", CONTENT, "gpt-4o-mini").unwrap();
+
+        assert_eq!(cache_key.url_id, "/imovel/123");
+        assert_eq!(cache_key.prompt_version, prompt_version());
+        assert!(LlmCacheKey::new("remax", "não é Json", "gpt-4o-mini").is_err());
+    }
+}
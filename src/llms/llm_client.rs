@@ -0,0 +1,105 @@
+use crate::llms::llm_error::LlmError;
+use crate::llms::llm_provider::{open_llm_provider, LlmProvider, LlmResult};
+use crate::llms::llm_utils::{add_repair_messages, real_estate_request_body, to_llm_response};
+use crate::llms::rate_limiter::{Permit, RateLimiter};
+use crate::schemas::llm::{LLMRequestBody, LLMResponse};
+use crate::utils::time_utils::unix_timestamp;
+
+// Consecutive 429 answers before giving up until the next run
+const MAX_RATE_LIMITED_RETRIES: u32 = 5;
+
+pub enum LlmAnswer {
+    Valid(LLMResponse),
+    /// Still invalid after every repair, with the last answer and what is wrong with it
+    Invalid(String, String, u32),
+    /// The request budget is over, the run should stop
+    Stopped,
+}
+
+/// The provider with its rate limiter, asking again when the answers are invalid
+pub struct LlmClient {
+    provider: Box<dyn LlmProvider>,
+    limiter: RateLimiter,
+    max_repairs: u32,
+}
+
+impl LlmClient {
+    pub async fn open(max_repairs: u32) -> LlmResult<LlmClient> {
+        let provider: Box<dyn LlmProvider> = open_llm_provider()?;
+        println!("Using {} with model {}", provider.name(), provider.model());
+        let limiter: RateLimiter = RateLimiter::load(
+            provider.rate_limits(),
+            &format!("{}_rate_limit.json", provider.name()),
+        )
+        .await?;
+
+        Ok(LlmClient {
+            provider,
+            limiter,
+            max_repairs,
+        })
+    }
+
+    pub fn model(&self) -> &str {
+        self.provider.model()
+    }
+
+    /// Sends the request respecting the rate limits, `None` when the budget is over
+    async fn send_rate_limited(&mut self, body: &LLMRequestBody) -> LlmResult<Option<String>> {
+        for _ in 0..MAX_RATE_LIMITED_RETRIES {
+            if let Permit::Exhausted(until) = self.limiter.acquire().await? {
+                let wait_minutes: i64 = (until - unix_timestamp()) / 60;
+                println!(
+                    "Request budget exhausted, run again in {}h{:02}m to continue",
+                    wait_minutes / 60,
+                    wait_minutes % 60
+                );
+                return Ok(None);
+            }
+
+            match self.provider.chat_completion(body).await {
+                Ok(llm_response_raw_text) => return Ok(Some(llm_response_raw_text)),
+                Err(LlmError::RateLimited(retry_after)) => {
+                    println!("{}", LlmError::RateLimited(retry_after));
+                    self.limiter.rate_limited(retry_after).await?;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        println!("Still rate limited, run again later to continue");
+        Ok(None)
+    }
+
+    /// Asks the model about the listing, feeding back what is wrong with invalid answers
+    pub async fn ask_real_estate_llm(&mut self, content_line: &str) -> LlmResult<LlmAnswer> {
+        let mut body: LLMRequestBody =
+            real_estate_request_body(content_line.to_string(), self.provider.as_ref());
+        let mut attempts: u32 = 0;
+
+        loop {
+            let llm_response_raw_text: String = match self.send_rate_limited(&body).await? {
+                Some(llm_response_raw_text) => llm_response_raw_text,
+                None => return Ok(LlmAnswer::Stopped),
+            };
+            attempts += 1;
+
+            match to_llm_response(&llm_response_raw_text, self.provider.as_ref()) {
+                Ok(llm_response) => return Ok(LlmAnswer::Valid(llm_response)),
+                Err(LlmError::InvalidOutput(output, error)) => {
+                    println!(
+                        "Invalid answer ({}/{}): {}",
+                        attempts,
+                        self.max_repairs + 1,
+                        error
+                    );
+                    if attempts > self.max_repairs {
+                        return Ok(LlmAnswer::Invalid(output, error, attempts));
+                    }
+                    add_repair_messages(&mut body, output, &error);
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use reqwest::StatusCode;
+use std::fmt;
+use std::time::Duration;
+
+#[derive(Debug)]
+pub enum LlmError {
+    /// The provider answered 429, `Retry-After` says how long to wait when present
+    RateLimited(Option<Duration>),
+    /// Any other non success answer with its body
+    Status(StatusCode, String),
+    Request(reqwest::Error),
+    /// The answer does not follow the expected schema, with the answer and what is wrong with it
+    InvalidOutput(String, String),
+}
+
+impl fmt::Display for LlmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LlmError::RateLimited(Some(retry_after)) => {
+                write!(f, "rate limited, retry after {:?}", retry_after)
+            }
+            LlmError::RateLimited(None) => write!(f, "rate limited"),
+            LlmError::Status(status, body) => write!(f, "{}: {}", status, body),
+            LlmError::Request(e) => write!(f, "{}", e),
+            LlmError::InvalidOutput(_, reason) => write!(f, "invalid answer: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for LlmError {}
+
+impl From<reqwest::Error> for LlmError {
+    fn from(e: reqwest::Error) -> Self {
+        LlmError::Request(e)
+    }
+}
@@ -0,0 +1,240 @@
+use crate::schemas::llm::LLMRealStateResponse;
+use crate::utils::parsing_utils::parse_number;
+use schemars::schema_for;
+use serde_json::{json, Map, Number, Value};
+
+/// Json schema of the answer we expect, derived from `LLMRealStateResponse`
+pub fn real_estate_response_schema() -> Value {
+    let mut schema: Value = schema_for!(LLMRealStateResponse).to_value();
+    if let Some(schema) = schema.as_object_mut() {
+        schema.remove("$schema");
+    }
+    schema
+}
+
+/// `response_format` of the OpenAI chat completions API, constraining the model to the schema
+pub fn real_estate_response_format() -> Value {
+    json!({
+        "type": "json_schema",
+        "json_schema": {
+            "name": "real_estate_response",
+            "schema": real_estate_response_schema(),
+        }
+    })
+}
+
+/// The content of the first code block when the model wrapped the Json in one
+fn strip_code_fences(content: &str) -> &str {
+    match content.split_once("```") {
+        Some((_, fenced)) => {
+            // Skips the language of the block, ```json
+            let fenced: &str = match fenced.split_once('\n') {
+                Some((language, rest)) if language.trim().chars().all(char::is_alphanumeric) => {
+                    rest
+                }
+                _ => fenced,
+            };
+            fenced.split_once("```").map_or(fenced, |(block, _)| block)
+        }
+        None => content,
+    }
+}
+
+/// The first Json object of the text, closing the braces the model forgot to close
+fn first_json_object(content: &str) -> Option<String> {
+    let start: usize = content.find('{')?;
+    let mut depth: usize = 0;
+    let mut in_string: bool = false;
+    let mut escaped: bool = false;
+
+    for (index, c) in content[start..].char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        match c {
+            '"' => in_string = true,
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(content[start..=start + index].to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let quote: &str = if in_string { "\"" } else { "" };
+    Some(format!(
+        "{}{}{}",
+        content[start..].trim_end().trim_end_matches(','),
+        quote,
+        "}".repeat(depth)
+    ))
+}
+
+fn schema_types(property_schema: &Value) -> Vec<&str> {
+    match &property_schema["type"] {
+        Value::String(schema_type) => vec![schema_type.as_str()],
+        Value::Array(schema_types) => schema_types.iter().filter_map(Value::as_str).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Converts the value to the type the schema expects when the model used another one, "3" to 3
+fn coerce(value: &Value, property_schema: &Value) -> Option<Value> {
+    let types: Vec<&str> = schema_types(property_schema);
+
+    let number: Option<f64> = match value {
+        Value::String(text) => parse_number(text),
+        Value::Number(number) => number.as_f64(),
+        _ => None,
+    };
+
+    if types.contains(&"integer") && !value.is_i64() && !value.is_u64() {
+        return number
+            .filter(|number| number.fract() == 0.0)
+            .map(|number| Value::from(number as i64));
+    }
+    if types.contains(&"number") && value.is_string() {
+        return number.and_then(Number::from_f64).map(Value::Number);
+    }
+    if types.contains(&"boolean") {
+        if let Value::String(text) = value {
+            return match text.trim().to_lowercase().as_str() {
+                "true" | "yes" | "sim" => Some(Value::Bool(true)),
+                "false" | "no" | "não" | "nao" => Some(Value::Bool(false)),
+                _ => None,
+            };
+        }
+    }
+    if types.contains(&"string") && (value.is_number() || value.is_boolean()) {
+        return Some(Value::String(value.to_string()));
+    }
+
+    None
+}
+
+/// Reads the answer of the model tolerating code fences, text around the Json, unclosed braces
+/// and values of the wrong type. The error describes what is still wrong to ask the model again.
+pub fn extract_real_estate_response(content: &str) -> Result<LLMRealStateResponse, String> {
+    let json_object: String = first_json_object(strip_code_fences(content))
+        .ok_or("the answer has no Json object".to_string())?;
+
+    let mut fields: Map<String, Value> = serde_json::from_str(&json_object)
+        .map_err(|e| format!("the answer is not valid Json: {}", e))?;
+
+    let schema: Value = real_estate_response_schema();
+    if let Some(properties) = schema["properties"].as_object() {
+        for (name, property_schema) in properties {
+            if let Some(value) = fields.get_mut(name) {
+                if let Some(coerced) = coerce(value, property_schema) {
+                    *value = coerced;
+                }
+            }
+        }
+    }
+
+    serde_json::from_value(Value::Object(fields))
+        .map_err(|e| format!("the Json does not follow the schema: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ANSWER: &str = r#"{"url_id": "/imovel/123", "no_bedrooms": 2, "no_bathrooms": 1,
+        "has_garage": false, "has_pool": false, "has_good_location": true, "location": "Arroios",
+        "average_price": 350000, "average_sqr_meters": 82, "average_price_per_sqr_meters": 4268,
+        "sqr_meters": 82, "price": 385000, "summary": "T2 remodelado {perto do metro}", "score": 7.5}"#;
+
+    #[test]
+    fn strips_code_fences() {
+        assert_eq!(
+            strip_code_fences("```json\n{\"a\": 1}\n```"),
+            "{\"a\": 1}\n"
+        );
+        assert_eq!(
+            strip_code_fences("Aqui está:\n```\n{}\n```\nObrigado"),
+            "{}\n"
+        );
+        assert_eq!(strip_code_fences("```{\"a\": 1}```"), "{\"a\": 1}");
+        assert_eq!(strip_code_fences("{\"a\": 1}"), "{\"a\": 1}");
+    }
+
+    #[test]
+    fn finds_the_first_json_object() {
+        assert_eq!(
+            first_json_object("Resposta: {\"a\": {\"b\": \"}\"}} e {\"c\": 2}"),
+            Some("{\"a\": {\"b\": \"}\"}}".to_string())
+        );
+        assert_eq!(
+            first_json_object("{\"a\": \"aspas \\\" e {\"}"),
+            Some("{\"a\": \"aspas \\\" e {\"}".to_string())
+        );
+        assert_eq!(first_json_object("sem Json"), None);
+    }
+
+    #[test]
+    fn closes_the_braces_the_model_forgot() {
+        assert_eq!(
+            first_json_object("{\"a\": {\"b\": 1},\n"),
+            Some("{\"a\": {\"b\": 1}}".to_string())
+        );
+        assert_eq!(
+            first_json_object("{\"a\": \"cortado"),
+            Some("{\"a\": \"cortado\"}".to_string())
+        );
+    }
+
+    #[test]
+    fn coerces_values_to_the_schema_types() {
+        let integer: Value = json!({"type": "integer"});
+        let number: Value = json!({"type": ["number", "null"]});
+        let boolean: Value = json!({"type": "boolean"});
+        let string: Value = json!({"type": "string"});
+
+        assert_eq!(coerce(&json!("3"), &integer), Some(json!(3)));
+        assert_eq!(coerce(&json!(2.0), &integer), Some(json!(2)));
+        assert_eq!(coerce(&json!(2.5), &integer), None);
+        assert_eq!(coerce(&json!(3), &integer), None);
+        assert_eq!(coerce(&json!("350.000"), &number), Some(json!(350000.0)));
+        assert_eq!(
+            coerce(&json!("€ 1.250.000"), &number),
+            Some(json!(1250000.0))
+        );
+        assert_eq!(coerce(&json!("4.268,5 €/m²"), &number), Some(json!(4268.5)));
+        assert_eq!(coerce(&json!("Sim"), &boolean), Some(json!(true)));
+        assert_eq!(coerce(&json!("não"), &boolean), Some(json!(false)));
+        assert_eq!(coerce(&json!("talvez"), &boolean), None);
+        assert_eq!(coerce(&json!(7), &string), Some(json!("7")));
+    }
+
+    #[test]
+    fn extracts_the_response_around_text_and_fences() {
+        let fenced: String = format!("Claro!\n```json\n{}\n```", ANSWER);
+        let response: LLMRealStateResponse = extract_real_estate_response(&fenced).unwrap();
+
+        assert_eq!(response.url_id, "/imovel/123");
+        assert!(extract_real_estate_response(
+            &ANSWER.replace("\"no_bedrooms\": 2", "\"no_bedrooms\": \"2\"")
+        )
+        .is_ok());
+        assert!(extract_real_estate_response(
+            &ANSWER.replace("\"score\": 7.5", "\"score\": \"n/a\"")
+        )
+        .unwrap_err()
+        .starts_with("the Json does not follow the schema"));
+        assert_eq!(
+            extract_real_estate_response("Não sei").unwrap_err(),
+            "the answer has no Json object"
+        );
+    }
+}
@@ -0,0 +1,215 @@
+use crate::llms::llm_error::LlmError;
+use crate::llms::llm_utils::FREE_LLAMA_MODEL;
+use crate::schemas::llm::LLMRequestBody;
+use crate::utils::time_utils::unix_timestamp;
+use async_trait::async_trait;
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE, RETRY_AFTER};
+use reqwest::{Client, Response, StatusCode};
+use serde_json::json;
+use std::env;
+use std::error::Error;
+use std::time::Duration;
+
+pub type LlmResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
+
+/// How many requests the provider accepts, `None` when there is no limit
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimits {
+    pub per_minute: Option<u32>,
+    pub per_day: Option<u32>,
+}
+
+/// A chat completions endpoint the LLM runner can send the listings to
+#[async_trait]
+pub trait LlmProvider: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn model(&self) -> &str;
+
+    fn rate_limits(&self) -> RateLimits;
+
+    /// Whether the model can be constrained to a Json schema through `response_format`
+    fn supports_response_format(&self) -> bool;
+
+    /// Sends the request and returns the raw body of the response
+    async fn chat_completion(&self, body: &LLMRequestBody) -> Result<String, LlmError>;
+}
+
+/// Any server that speaks the OpenAI chat completions API: OpenRouter, Ollama, llama.cpp...
+pub struct OpenAiCompatibleProvider {
+    name: String,
+    base_url: String,
+    model: String,
+    api_key: Option<String>,
+    rate_limits: RateLimits,
+    response_format: bool,
+    client: Client,
+}
+
+impl OpenAiCompatibleProvider {
+    pub fn new(
+        name: &str,
+        base_url: &str,
+        model: &str,
+        api_key: Option<String>,
+        rate_limits: RateLimits,
+        response_format: bool,
+    ) -> Self {
+        OpenAiCompatibleProvider {
+            name: name.to_string(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            model: model.to_string(),
+            api_key,
+            rate_limits,
+            response_format,
+            client: Client::new(),
+        }
+    }
+
+    fn headers(&self) -> HeaderMap {
+        let mut headers_map: HeaderMap = HeaderMap::new();
+
+        headers_map.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        // Local servers do not need a key
+        if let Some(api_key) = self
+            .api_key
+            .as_ref()
+            .and_then(|api_key| HeaderValue::from_str(&format!("Bearer {}", api_key)).ok())
+        {
+            headers_map.insert(AUTHORIZATION, api_key);
+        }
+
+        headers_map
+    }
+}
+
+/// `Retry-After` in seconds, or OpenRouter's `X-RateLimit-Reset` in epoch milliseconds
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+
+    if let Some(seconds) = header(RETRY_AFTER.as_str()).and_then(|value| value.parse::<u64>().ok())
+    {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    header("x-ratelimit-reset")
+        .and_then(|value| value.parse::<i64>().ok())
+        .map(|reset_millis| {
+            Duration::from_secs((reset_millis / 1000 - unix_timestamp()).max(0) as u64)
+        })
+}
+
+#[async_trait]
+impl LlmProvider for OpenAiCompatibleProvider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn rate_limits(&self) -> RateLimits {
+        self.rate_limits
+    }
+
+    fn supports_response_format(&self) -> bool {
+        self.response_format
+    }
+
+    async fn chat_completion(&self, body: &LLMRequestBody) -> Result<String, LlmError> {
+        let response: Response = self
+            .client
+            .post(format!("{}/chat/completions", self.base_url))
+            .body(json!(body).to_string())
+            .headers(self.headers())
+            .send()
+            .await?;
+
+        let status: StatusCode = response.status();
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            return Err(LlmError::RateLimited(retry_after(response.headers())));
+        }
+
+        let text: String = response.text().await?;
+        if !status.is_success() {
+            return Err(LlmError::Status(status, text));
+        }
+
+        Ok(text)
+    }
+}
+
+fn env_limit(name: &str, default: Option<u32>) -> LlmResult<Option<u32>> {
+    match env::var(name) {
+        Ok(value) if value.is_empty() || value == "none" => Ok(None),
+        Ok(value) => {
+            Ok(Some(value.parse().map_err(|_| {
+                format!("`{}` should be a number or none", name)
+            })?))
+        }
+        Err(_) => Ok(default),
+    }
+}
+
+/// Builds the provider from `LLM_PROVIDER` (open_router, ollama or llama_cpp), `LLM_BASE_URL`,
+/// `LLM_MODEL`, `LLM_API_KEY`, `LLM_REQUESTS_PER_MINUTE`, `LLM_REQUESTS_PER_DAY` and
+/// `LLM_RESPONSE_FORMAT`
+pub fn open_llm_provider() -> LlmResult<Box<dyn LlmProvider>> {
+    let provider: String = env::var("LLM_PROVIDER").unwrap_or("open_router".to_string());
+    let api_key: Option<String> = env::var("LLM_API_KEY").ok();
+
+    let unlimited: RateLimits = RateLimits {
+        per_minute: None,
+        per_day: None,
+    };
+
+    let (base_url, model, api_key, rate_limits): (&str, &str, Option<String>, RateLimits) =
+        match provider.as_str() {
+            "open_router" => {
+                let api_key: String = api_key
+                    .or_else(|| env::var("OPEN_ROUTER_API_KEY").ok())
+                    .ok_or("open_router requires `OPEN_ROUTER_API_KEY` or `LLM_API_KEY`")?;
+                (
+                    "https://openrouter.ai/api/v1",
+                    FREE_LLAMA_MODEL,
+                    Some(api_key),
+                    // Free models have a limit of 20/min and 200/day
+                    RateLimits {
+                        per_minute: Some(20),
+                        per_day: Some(200),
+                    },
+                )
+            }
+            "ollama" => ("http://localhost:11434/v1", "llama3.2", api_key, unlimited),
+            // llama.cpp serves the model it was started with whatever the name sent
+            "llama_cpp" => ("http://localhost:8080/v1", "default", api_key, unlimited),
+            provider => {
+                return Err(format!(
+                    "Invalid LLM provider {}, use `open_router`, `ollama` or `llama_cpp`",
+                    provider
+                )
+                .into())
+            }
+        };
+
+    let base_url: String = env::var("LLM_BASE_URL").unwrap_or(base_url.to_string());
+    let model: String = env::var("LLM_MODEL").unwrap_or(model.to_string());
+    let rate_limits: RateLimits = RateLimits {
+        per_minute: env_limit("LLM_REQUESTS_PER_MINUTE", rate_limits.per_minute)?,
+        per_day: env_limit("LLM_REQUESTS_PER_DAY", rate_limits.per_day)?,
+    };
+    // Ollama and llama.cpp support any schema, on OpenRouter it depends on the model
+    let response_format: bool = env::var("LLM_RESPONSE_FORMAT")
+        .map(|value| value == "true")
+        .unwrap_or(provider != "open_router");
+
+    Ok(Box::new(OpenAiCompatibleProvider::new(
+        &provider,
+        &base_url,
+        &model,
+        api_key,
+        rate_limits,
+        response_format,
+    )))
+}
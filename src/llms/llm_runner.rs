@@ -1,43 +1,105 @@
-use crate::llms::llm_utils::call_real_estate_llm_json;
-use crate::schemas::llm::LLMResponse;
-use crate::utils::cache_utils::{export, spawn_cache};
+use crate::llms::llm_cache::LlmCacheKey;
+use crate::llms::llm_client::{LlmAnswer, LlmClient};
+use crate::llms::llm_utils::FREE_LLAMA_MODEL;
+use crate::schemas::llm::{LLMCacheEntry, LLMDeadLetter, LLMResponse};
+use crate::storage::listing_storage::{open_storage, ListingStorage};
+use crate::utils::cache_utils::PersistentCache;
 use crate::utils::file_utils::{
-    get_content_lines, get_file_read, get_file_write_truncate, write_to_file,
+    get_content_lines, get_file_read, get_file_write_append, get_file_write_truncate, write_to_file,
 };
-use moka::future::Cache;
+use crate::utils::time_utils::{unix_timestamp, SECONDS_PER_DAY};
+use serde_json::json;
 use std::error::Error;
-use std::time::Duration;
 use tokio::fs::File;
 
+/// Answers cached before the keys were content addressed are keyed by the whole line, they were
+/// all made with the free OpenRouter model so they are moved to their new key when it is in use
+async fn legacy_cached_response(
+    cache: &PersistentCache,
+    content_line: &str,
+    cache_key: &LlmCacheKey,
+) -> Result<Option<String>, Box<dyn Error + Send + Sync>> {
+    if cache_key.model != FREE_LLAMA_MODEL {
+        return Ok(None);
+    }
+
+    match cache.remove(content_line).await? {
+        Some(response) => {
+            let entry: LLMCacheEntry = cache_key.to_entry(response.clone());
+            cache
+                .insert(cache_key.key(), json!(entry).to_string())
+                .await?;
+            Ok(Some(response))
+        }
+        None => Ok(None),
+    }
+}
+
 async fn llm_mechanism(
-    key: &str,
+    client: &mut LlmClient,
+    portal: &str,
     input_path: &str,
     output_path: &str,
-    cache: &Cache<String, String>,
-) -> Result<(), Box<dyn Error>> {
+    dead_letter_path: &str,
+    cache: &PersistentCache,
+    storage: &dyn ListingStorage,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
     let read_input: File = get_file_read(input_path).await?;
     let mut write_output: File = get_file_write_truncate(output_path).await?;
+    let mut write_dead_letter: File = get_file_write_append(dead_letter_path).await?;
 
     let content_lines: Vec<String> = get_content_lines(read_input).await?;
 
     for content_line in content_lines {
-        let content: String = match cache.get(&content_line).await {
+        let cache_key: LlmCacheKey = LlmCacheKey::new(portal, &content_line, client.model())?;
+        let key: String = cache_key.key();
+
+        let cached_response: Option<String> = match cache.get(&key).await {
+            Some(entry) => Some(serde_json::from_str::<LLMCacheEntry>(&entry)?.response),
+            None => legacy_cached_response(cache, &content_line, &cache_key).await?,
+        };
+
+        let content: String = match cached_response {
             None => {
                 println!("Cache miss");
-                let llm_response: LLMResponse =
-                    call_real_estate_llm_json(content_line.clone(), key).await?;
-                // let key: &String = &llm_response.choices.first().unwrap().message.content.url_id;
+                let llm_response: LLMResponse = match client
+                    .ask_real_estate_llm(&content_line)
+                    .await?
+                {
+                    LlmAnswer::Valid(llm_response) => llm_response,
+                    LlmAnswer::Invalid(output, error, attempts) => {
+                        let dead_letter: LLMDeadLetter = LLMDeadLetter {
+                            input: content_line,
+                            output,
+                            error,
+                            attempts,
+                            failed_at: unix_timestamp(),
+                        };
+                        write_to_file(&mut write_dead_letter, format!("{}\n", json!(dead_letter)))
+                            .await?;
+                        continue;
+                    }
+                    // Whatever is left comes from the cache on the next run
+                    LlmAnswer::Stopped => return Ok(()),
+                };
                 println!("LLM response id {}", llm_response.id);
                 let llm_response_json: String = serde_json::to_string(&llm_response)?;
-                // Free models have a limit of 20/min and 200/day
-                //TODO add a proper key
-                cache.insert(content_line, llm_response_json.clone()).await;
-                tokio::time::sleep(Duration::from_millis(3000)).await;
+                if let Some(choice) = llm_response.choices.first() {
+                    storage
+                        .save_llm_result(
+                            &choice.message.content.url_id,
+                            &llm_response.model,
+                            &llm_response_json,
+                        )
+                        .await?;
+                }
+                let entry: LLMCacheEntry = cache_key.to_entry(llm_response_json.clone());
+                cache.insert(key, json!(entry).to_string()).await?;
                 llm_response_json
             }
-            Some(content) => {
+            Some(response) => {
                 println!("Cache hit");
-                content
+                response
             }
         };
 
@@ -46,21 +108,41 @@ async fn llm_mechanism(
 
     Ok(())
 }
-pub async fn run(key: &str, input_path: &str, output_path: &str) -> Result<(), Box<dyn Error>> {
-    let cache_path: &str = "llm_cache.txt";
-    let llm_cache_file_read: File = get_file_read(cache_path).await.unwrap();
-    let cache: Cache<String, String> = spawn_cache(Some(llm_cache_file_read)).await?;
+pub async fn run(
+    portal: &str,
+    input_path: &str,
+    output_path: &str,
+    dead_letter_path: &str,
+    max_repairs: u32,
+    cache_ttl_days: Option<i64>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let cache: PersistentCache = PersistentCache::open(
+        "llm_cache.txt",
+        cache_ttl_days.map(|days| days * SECONDS_PER_DAY),
+    )
+    .await?;
+    let storage: Box<dyn ListingStorage> = open_storage()?;
+    let mut client: LlmClient = LlmClient::open(max_repairs).await?;
 
-    match llm_mechanism(key, input_path, output_path, &cache).await {
+    match llm_mechanism(
+        &mut client,
+        portal,
+        input_path,
+        output_path,
+        dead_letter_path,
+        &cache,
+        storage.as_ref(),
+    )
+    .await
+    {
         Ok(_) => {
-            let mut llm_cache_file_writer: File = get_file_write_truncate(cache_path).await?;
-            export(&cache, &mut llm_cache_file_writer).await?;
+            cache.compact().await?;
 
-            Ok(println!("LLM mechanism finished"))
+            println!("LLM mechanism finished");
+            Ok(())
         }
         Err(e) => {
-            let mut llm_cache_file_writer: File = get_file_write_truncate(cache_path).await?;
-            export(&cache, &mut llm_cache_file_writer).await?;
+            cache.compact().await?;
 
             println!("Error: {:?}", e);
             panic!()
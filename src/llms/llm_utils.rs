@@ -1,107 +1,51 @@
+use crate::llms::llm_error::LlmError;
+use crate::llms::llm_output::{extract_real_estate_response, real_estate_response_format};
+use crate::llms::llm_provider::LlmProvider;
 use crate::schemas::llm::{
-    to_llm_request_body_json, LLMMessageResponse, LLMMessageResponseRaw, LLMRealStateResponse,
-    LLMResponse, LLMResponseChoice, LLMResponseRaw, ToLLMRequestBody,
+    to_llm_request_body_json, LLMBodyMessage, LLMMessageResponse, LLMMessageResponseRaw,
+    LLMRealStateResponse, LLMRequestBody, LLMResponse, LLMResponseChoice, LLMResponseRaw,
 };
-use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
-use reqwest::{Client, Response};
-use serde::Serialize;
-use serde_json::json;
-use std::error::Error;
 use std::string::ToString;
 
-pub async fn call_real_estate_llm<T: ToLLMRequestBody + Serialize>(
-    request: T,
-    key: &str,
-) -> reqwest::Result<LLMResponse> {
-    let client: Client = Client::new();
-    let mut headers_map: HeaderMap = HeaderMap::new();
+/// The request for the listing Json, constrained to the response schema when the provider can
+pub fn real_estate_request_body(request: String, provider: &dyn LlmProvider) -> LLMRequestBody {
+    let mut body: LLMRequestBody = to_llm_request_body_json(request, provider.model());
 
-    headers_map.insert(
-        CONTENT_TYPE,
-        HeaderValue::from_str("application/json").unwrap(),
-    );
-    headers_map.insert(
-        "Authorization",
-        HeaderValue::from_str(format!("Bearer {}", key).as_str()).unwrap(),
-    );
-
-    let response: Response = client
-        .post("https://openrouter.ai/api/v1/chat/completions")
-        .body(json!(request.to_llm_request_body()).to_string())
-        .headers(headers_map)
-        .send()
-        .await?;
-
-    let llm_response_raw = response.json::<LLMResponseRaw>().await?;
-
-    let mut llm_response_choices: Vec<LLMResponseChoice> = Vec::new();
-
-    for choice in llm_response_raw.choices {
-        let llm_message_response_raw: LLMMessageResponseRaw = choice.message;
-        let llm_message_response: LLMRealStateResponse =
-            serde_json::from_str(&*llm_message_response_raw.content).unwrap();
-
-        let message_response: LLMMessageResponse = LLMMessageResponse {
-            role: llm_message_response_raw.role,
-            content: llm_message_response,
-        };
-        llm_response_choices.push(LLMResponseChoice {
-            logprobs: choice.logprobs,
-            finish_reason: choice.finish_reason,
-            index: choice.index,
-            message: message_response,
-            refusal: choice.refusal,
-        })
+    if provider.supports_response_format() {
+        body.response_format = Some(real_estate_response_format());
     }
 
-    Ok(LLMResponse {
-        id: llm_response_raw.id,
-        provider: llm_response_raw.provider,
-        model: llm_response_raw.model,
-        object: llm_response_raw.object,
-        created: llm_response_raw.created,
-        choices: llm_response_choices,
-    })
+    body
 }
 
-pub async fn call_real_estate_llm_json(
-    request: String,
-    key: &str,
-) -> Result<LLMResponse, Box<dyn Error>> {
-    let client: Client = Client::new();
-    let mut headers_map: HeaderMap = HeaderMap::new();
-
-    headers_map.insert(
-        CONTENT_TYPE,
-        HeaderValue::from_str("application/json").unwrap(),
-    );
-    headers_map.insert(
-        "Authorization",
-        HeaderValue::from_str(format!("Bearer {}", key).as_str()).unwrap(),
-    );
-
-    let response: Response = client
-        .post("https://openrouter.ai/api/v1/chat/completions")
-        .body(json!(to_llm_request_body_json(request)).to_string())
-        .headers(headers_map)
-        .send()
-        .await?;
-
-    let llm_response_raw_text: String = response.text().await?;
+/// Asks the model to fix its previous answer, telling it what was wrong with it
+pub fn add_repair_messages(body: &mut LLMRequestBody, output: String, error: &str) {
+    body.messages.push(LLMBodyMessage {
+        role: ASSISTANT_ROLE.to_string(),
+        content: output,
+    });
+    body.messages.push(LLMBodyMessage {
+        role: USER_ROLE.to_string(),
+        content: format!("{} {}", REPAIR_CONTENT, error),
+    });
+}
 
+pub fn to_llm_response(
+    llm_response_raw_text: &str,
+    provider: &dyn LlmProvider,
+) -> Result<LLMResponse, LlmError> {
     let llm_response_raw: LLMResponseRaw =
-        serde_json::from_str::<LLMResponseRaw>(&llm_response_raw_text)?;
+        serde_json::from_str::<LLMResponseRaw>(llm_response_raw_text).map_err(|e| {
+            LlmError::InvalidOutput(llm_response_raw_text.to_string(), e.to_string())
+        })?;
 
     let mut llm_response_choices: Vec<LLMResponseChoice> = Vec::new();
 
     for choice in llm_response_raw.choices {
         let llm_message_response_raw: LLMMessageResponseRaw = choice.message;
-        let fixed_raw_content: String = if llm_message_response_raw.content.ends_with("}") {
-            llm_message_response_raw.content
-        } else {
-            format!("{}{}", &*llm_message_response_raw.content, "}")
-        };
-        let llm_message_response: LLMRealStateResponse = serde_json::from_str(&*fixed_raw_content)?;
+        let llm_message_response: LLMRealStateResponse =
+            extract_real_estate_response(&llm_message_response_raw.content)
+                .map_err(|e| LlmError::InvalidOutput(llm_message_response_raw.content, e))?;
 
         let message_response: LLMMessageResponse = LLMMessageResponse {
             role: llm_message_response_raw.role,
@@ -118,7 +62,10 @@ pub async fn call_real_estate_llm_json(
 
     Ok(LLMResponse {
         id: llm_response_raw.id,
-        provider: llm_response_raw.provider,
+        // Only OpenRouter says which provider served the request
+        provider: llm_response_raw
+            .provider
+            .unwrap_or(provider.name().to_string()),
         model: llm_response_raw.model,
         object: llm_response_raw.object,
         created: llm_response_raw.created,
@@ -132,6 +79,9 @@ pub const SYSTEM_ROLE: &str = "system";
 pub const SYSTEM_CONTENT: &str = "You are a real estate guru.";
 
 pub const USER_ROLE: &str = "user";
+pub const ASSISTANT_ROLE: &str = "assistant";
+pub const REPAIR_CONTENT: &str =
+    "Your reply is not a valid JSON with the requested properties, reply again only with the corrected JSON. The problem is:";
 pub const USER_CONTENT: &str = "Based on the following JSON that I will give you in Portuguese from Portugal, only with a JSON with the following properties:
           - url_id
           - no_bedrooms
@@ -0,0 +1,249 @@
+use crate::llms::llm_provider::{LlmResult, RateLimits};
+use crate::utils::file_utils::{get_content_lines, get_file_read, write_atomically};
+use crate::utils::time_utils::{unix_timestamp, SECONDS_PER_DAY};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::time::Duration;
+
+// Longer waits stop the run instead, it is resumed later from the cache
+const MAX_WAIT_SECONDS: i64 = 15 * 60;
+// Without `Retry-After` a 429 blocks the requests for a minute
+const DEFAULT_RETRY_AFTER_SECONDS: u64 = 60;
+
+/// What the limiter remembers between runs, so a restart does not spend the budget again
+#[derive(Deserialize, Serialize, Debug, Default)]
+struct RateLimiterState {
+    minute_tokens: f64,
+    minute_updated_at: i64,
+    /// Days since the unix epoch, the daily budget resets at midnight UTC
+    day: i64,
+    used_today: u32,
+    blocked_until: i64,
+}
+
+pub enum Permit {
+    Granted,
+    /// No request can be made before this unix timestamp
+    Exhausted(i64),
+}
+
+/// Token bucket refilled every minute plus a daily budget
+pub struct RateLimiter {
+    limits: RateLimits,
+    state_path: String,
+    state: RateLimiterState,
+}
+
+impl RateLimiter {
+    pub async fn load(limits: RateLimits, state_path: &str) -> LlmResult<RateLimiter> {
+        let state: RateLimiterState = match get_file_read(state_path).await {
+            Ok(file) => match get_content_lines(file).await?.first() {
+                Some(line) => serde_json::from_str(line)?,
+                None => RateLimiterState::default(),
+            },
+            Err(_) => RateLimiterState {
+                minute_tokens: limits.per_minute.unwrap_or_default() as f64,
+                minute_updated_at: unix_timestamp(),
+                ..RateLimiterState::default()
+            },
+        };
+
+        Ok(RateLimiter {
+            limits,
+            state_path: state_path.to_string(),
+            state,
+        })
+    }
+
+    async fn save(&self) -> LlmResult<()> {
+        write_atomically(&self.state_path, format!("{}\n", json!(self.state))).await?;
+        Ok(())
+    }
+
+    fn refill(&mut self, now: i64) {
+        if let Some(per_minute) = self.limits.per_minute {
+            let elapsed: i64 = (now - self.state.minute_updated_at).max(0);
+            self.state.minute_tokens = (self.state.minute_tokens
+                + elapsed as f64 * per_minute as f64 / 60.0)
+                .min(per_minute as f64);
+            self.state.minute_updated_at = now;
+        }
+
+        let today: i64 = now / SECONDS_PER_DAY;
+        if self.state.day != today {
+            self.state.day = today;
+            self.state.used_today = 0;
+        }
+    }
+
+    /// Waits until a request can be made, or tells when the next one can when that is too long
+    pub async fn acquire(&mut self) -> LlmResult<Permit> {
+        loop {
+            let now: i64 = unix_timestamp();
+            self.refill(now);
+
+            let wait_seconds: i64 = if self.state.blocked_until > now {
+                self.state.blocked_until - now
+            } else if self
+                .limits
+                .per_day
+                .is_some_and(|per_day| self.state.used_today >= per_day)
+            {
+                return Ok(Permit::Exhausted((self.state.day + 1) * SECONDS_PER_DAY));
+            } else if let Some(per_minute) = self
+                .limits
+                .per_minute
+                .filter(|_| self.state.minute_tokens < 1.0)
+            {
+                ((1.0 - self.state.minute_tokens) * 60.0 / per_minute as f64).ceil() as i64
+            } else {
+                if self.limits.per_minute.is_some() {
+                    self.state.minute_tokens -= 1.0;
+                }
+                self.state.used_today += 1;
+                self.save().await?;
+                return Ok(Permit::Granted);
+            };
+
+            if wait_seconds > MAX_WAIT_SECONDS {
+                return Ok(Permit::Exhausted(now + wait_seconds));
+            }
+
+            println!("Rate limit reached, waiting {}s", wait_seconds);
+            tokio::time::sleep(Duration::from_secs(wait_seconds as u64)).await;
+        }
+    }
+
+    /// The provider answered 429, no request is made until `retry_after` passes
+    pub async fn rate_limited(&mut self, retry_after: Option<Duration>) -> LlmResult<()> {
+        let retry_after_seconds: u64 = retry_after
+            .map(|retry_after| retry_after.as_secs())
+            .unwrap_or(DEFAULT_RETRY_AFTER_SECONDS);
+
+        self.state.blocked_until = unix_timestamp() + retry_after_seconds as i64;
+        self.state.minute_tokens = 0.0;
+        self.save().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn state_path(name: &str) -> String {
+        env::temp_dir()
+            .join(format!(
+                "res-rate-limiter-{}-{}.json",
+                name,
+                unix_timestamp()
+            ))
+            .to_string_lossy()
+            .to_string()
+    }
+
+    fn limiter(
+        per_minute: Option<u32>,
+        per_day: Option<u32>,
+        state: RateLimiterState,
+    ) -> RateLimiter {
+        RateLimiter {
+            limits: RateLimits {
+                per_minute,
+                per_day,
+            },
+            state_path: state_path("unused"),
+            state,
+        }
+    }
+
+    #[test]
+    fn refills_the_minute_tokens_up_to_the_limit() {
+        let mut rate_limiter: RateLimiter = limiter(
+            Some(30),
+            None,
+            RateLimiterState {
+                minute_updated_at: 1_000,
+                ..RateLimiterState::default()
+            },
+        );
+
+        rate_limiter.refill(1_010);
+        assert_eq!(rate_limiter.state.minute_tokens, 5.0);
+        rate_limiter.refill(1_005);
+        assert_eq!(rate_limiter.state.minute_tokens, 5.0);
+        rate_limiter.refill(2_000);
+        assert_eq!(rate_limiter.state.minute_tokens, 30.0);
+    }
+
+    #[test]
+    fn resets_the_daily_budget_at_midnight() {
+        let day: i64 = 20_000;
+        let mut rate_limiter: RateLimiter = limiter(
+            None,
+            Some(100),
+            RateLimiterState {
+                day,
+                used_today: 100,
+                ..RateLimiterState::default()
+            },
+        );
+
+        rate_limiter.refill((day + 1) * SECONDS_PER_DAY - 1);
+        assert_eq!(rate_limiter.state.used_today, 100);
+        rate_limiter.refill((day + 1) * SECONDS_PER_DAY);
+        assert_eq!(rate_limiter.state.day, day + 1);
+        assert_eq!(rate_limiter.state.used_today, 0);
+    }
+
+    #[tokio::test]
+    async fn stops_at_the_daily_budget() {
+        let path: String = state_path("daily");
+        let mut rate_limiter: RateLimiter = RateLimiter::load(
+            RateLimits {
+                per_minute: None,
+                per_day: Some(1),
+            },
+            &path,
+        )
+        .await
+        .unwrap();
+
+        assert!(matches!(
+            rate_limiter.acquire().await.unwrap(),
+            Permit::Granted
+        ));
+        let tomorrow: i64 = (unix_timestamp() / SECONDS_PER_DAY + 1) * SECONDS_PER_DAY;
+        assert!(matches!(
+            rate_limiter.acquire().await.unwrap(),
+            Permit::Exhausted(until) if until == tomorrow
+        ));
+
+        let _ = tokio::fs::remove_file(&path).await;
+    }
+
+    #[tokio::test]
+    async fn blocks_until_retry_after_across_runs() {
+        let path: String = state_path("retry-after");
+        let limits: RateLimits = RateLimits {
+            per_minute: Some(60),
+            per_day: None,
+        };
+        let mut rate_limiter: RateLimiter = RateLimiter::load(limits, &path).await.unwrap();
+
+        rate_limiter
+            .rate_limited(Some(Duration::from_secs(3_600)))
+            .await
+            .unwrap();
+        let blocked_until: i64 = rate_limiter.state.blocked_until;
+        assert!(blocked_until >= unix_timestamp() + 3_599);
+
+        let mut reloaded: RateLimiter = RateLimiter::load(limits, &path).await.unwrap();
+        assert!(matches!(
+            reloaded.acquire().await.unwrap(),
+            Permit::Exhausted(until) if until == blocked_until
+        ));
+
+        let _ = tokio::fs::remove_file(&path).await;
+    }
+}
@@ -1,37 +1,108 @@
-use crate::llms::llm_runner;
-use crate::scrappers::{
-    era_scrapper, idealista_scrapper, imovirtual_scrapper, remax_scrapper, supercasas_scrapper,
+use crate::analysis::{
+    duplicates_runner, normalize_runner, price_drops_runner, rental_yield_runner,
+    time_on_market_runner,
 };
+use crate::llms::llm_runner;
+use crate::scrappers::scrapper::{self, scrapper_registry, PortalScrapper};
+use crate::storage::checkpoint_store::CheckpointStore;
+use crate::storage::page_archive::PageArchive;
+use crate::storage::{checkpoint_runner, import_runner, reprocess_runner};
 use dotenv::from_filename;
 use std::env;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+use std::path::Path;
+
+mod analysis {
+    pub mod duplicates_runner;
+    pub mod normalize_runner;
+    pub mod price_drops_runner;
+    pub mod rental_yield_runner;
+    pub mod time_on_market_runner;
+}
 
 mod llms {
+    pub mod llm_cache;
+    pub mod llm_client;
+    pub mod llm_error;
+    pub mod llm_output;
+    pub mod llm_provider;
     pub mod llm_runner;
     pub mod llm_utils;
+    pub mod rate_limiter;
 }
 
 mod schemas {
     pub mod era_listing_raw;
     pub mod idealista_listing_raw;
     pub mod imovirtual_listing_raw;
+    pub mod listing;
+    pub mod listing_history;
     pub mod llm;
+    pub mod property_match;
     pub mod remax_listing_raw;
+    pub mod rental_yield;
     pub mod supercasas_listing_raw;
 }
 mod scrappers {
     pub mod driver;
     pub mod era_scrapper;
+    #[cfg(test)]
+    pub mod fake_fetcher;
+    #[cfg(test)]
+    pub mod fixture_server;
+    pub mod html_page;
     pub mod idealista_scrapper;
     pub mod imovirtual_scrapper;
+    pub mod page_fetcher;
+    pub mod page_wait;
     pub mod remax_scrapper;
+    pub mod scrape_health;
+    pub mod scrape_pool;
+    pub mod scrapper;
+    pub mod scrapper_error;
     pub mod scrapper_utils;
+    pub mod search_spec;
     pub mod supercasas_scrapper;
 }
 
+mod storage {
+    pub mod checkpoint_runner;
+    pub mod checkpoint_store;
+    pub mod import_runner;
+    pub mod json_storage;
+    pub mod listing_storage;
+    #[cfg(test)]
+    pub mod memory_storage;
+    pub mod page_archive;
+    pub mod reprocess_runner;
+    pub mod seen_ids;
+    pub mod sqlite_storage;
+}
+
 mod utils {
     pub mod cache_utils;
+    pub mod env_utils;
     pub mod file_utils;
+    pub mod listing_utils;
+    pub mod minhash_utils;
+    pub mod parsing_utils;
+    pub mod time_utils;
+}
+
+/// The scrappers of the portals listed in `PORTALS`, all of them when it is not set
+fn selected_scrappers(scrappers: &[Box<dyn PortalScrapper>]) -> Vec<&dyn PortalScrapper> {
+    let portals: Option<String> = env::var("PORTALS").ok();
+
+    scrappers
+        .iter()
+        .map(|scrapper| scrapper.as_ref())
+        .filter(|scrapper| {
+            portals.as_ref().is_none_or(|portals| {
+                portals
+                    .split(',')
+                    .any(|portal| portal.trim() == scrapper.portal())
+            })
+        })
+        .collect()
 }
 
 #[tokio::main]
@@ -40,36 +111,152 @@ async fn main() {
     // Securely import sensitive credentials and values from your .env file
     from_filename(&env_file).ok();
 
-    let mode: &str = &*env::var("MODE").expect("env variable `MODE` should be set");
+    let mode: &str = &env::var("MODE").expect("env variable `MODE` should be set");
+    let scrappers: Vec<Box<dyn PortalScrapper>> = scrapper_registry();
 
     match mode {
-        "remax" => {
-            remax_scrapper::run().await;
+        "llm" => {
+            let input: &str =
+                &env::var("INPUT_PATH").expect("env variable `INPUT_PATH` should be set");
+            let output: &str =
+                &env::var("OUTPUT_PATH").expect("env variable `OUTPUT_PATH` should be set");
+
+            let dead_letter: String =
+                env::var("DEAD_LETTER_PATH").unwrap_or("llm_dead_letter.json".to_string());
+            let max_repairs: u32 = env::var("LLM_MAX_REPAIRS")
+                .map(|value| value.parse().expect("`LLM_MAX_REPAIRS` should be a number"))
+                .unwrap_or(2);
+            // The Json storage writes the listings of every portal to `{portal}.json`
+            let portal: String = env::var("PORTAL").unwrap_or_else(|_| {
+                let file_stem: &str = Path::new(input)
+                    .file_stem()
+                    .and_then(|file_stem| file_stem.to_str())
+                    .unwrap_or_default();
+                scrappers
+                    .iter()
+                    .map(|scrapper| scrapper.portal())
+                    .find(|portal| *portal == file_stem)
+                    .unwrap_or("unknown")
+                    .to_string()
+            });
+
+            let cache_ttl_days: Option<i64> = env::var("LLM_CACHE_TTL_DAYS").ok().map(|days| {
+                days.parse()
+                    .expect("`LLM_CACHE_TTL_DAYS` should be a number")
+            });
+
+            let _ = llm_runner::run(
+                &portal,
+                input,
+                output,
+                &dead_letter,
+                max_repairs,
+                cache_ttl_days,
+            )
+            .await;
         }
-        "era" => {
-            era_scrapper::run().await;
+        "normalize" => {
+            let output: &str =
+                &env::var("OUTPUT_PATH").expect("env variable `OUTPUT_PATH` should be set");
+
+            normalize_runner::run(&scrappers, output).await;
         }
-        "supercasas" => {
-            supercasas_scrapper::run().await;
+        "revisit" => {
+            let revisit_after_days: i64 = env::var("REVISIT_AFTER_DAYS")
+                .map(|days| {
+                    days.parse()
+                        .expect("`REVISIT_AFTER_DAYS` should be a number")
+                })
+                .unwrap_or(7);
+
+            for scrapper in selected_scrappers(&scrappers) {
+                scrapper.revisit(revisit_after_days).await;
+            }
         }
-        "imovirtual" => {
-            imovirtual_scrapper::run().await;
+        "price_drops" => {
+            let min_drop_percentage: f64 = env::var("MIN_DROP_PERCENTAGE")
+                .map(|value| {
+                    value
+                        .parse()
+                        .expect("`MIN_DROP_PERCENTAGE` should be a number")
+                })
+                .unwrap_or(5.0);
+            let days: i64 = env::var("DAYS")
+                .map(|days| days.parse().expect("`DAYS` should be a number"))
+                .unwrap_or(30);
+            let output: &str =
+                &env::var("OUTPUT_PATH").expect("env variable `OUTPUT_PATH` should be set");
+
+            price_drops_runner::run(min_drop_percentage, days, output).await;
         }
-        "idealista" => {
-            idealista_scrapper::run().await;
+        "time_on_market" => {
+            let output: &str =
+                &env::var("OUTPUT_PATH").expect("env variable `OUTPUT_PATH` should be set");
+            let summary: String =
+                env::var("SUMMARY_PATH").unwrap_or("time_on_market_by_district.json".to_string());
+
+            time_on_market_runner::run(output, &summary).await;
         }
-        "llm" => {
-            let llm_key: &str = &*env::var("OPEN_ROUTER_API_KEY")
-                .expect("env variable `OPEN_ROUTER_API_KEY` should be set");
+        "duplicates" => {
+            let input: &str =
+                &env::var("INPUT_PATH").expect("env variable `INPUT_PATH` should be set");
+            let output: &str =
+                &env::var("OUTPUT_PATH").expect("env variable `OUTPUT_PATH` should be set");
+            let matches: String =
+                env::var("MATCHES_PATH").unwrap_or("duplicate_matches.json".to_string());
+            let property_ids: String =
+                env::var("PROPERTY_IDS_PATH").unwrap_or("property_ids.json".to_string());
+
+            duplicates_runner::run(input, output, &matches, &property_ids).await;
+        }
+        "rental_yield" => {
             let input: &str =
-                &*env::var("INPUT_PATH").expect("env variable `INPUT_PATH` should be set");
+                &env::var("INPUT_PATH").expect("env variable `INPUT_PATH` should be set");
             let output: &str =
-                &*env::var("OUTPUT_PATH").expect("env variable `OUTPUT_PATH` should be set");
+                &env::var("OUTPUT_PATH").expect("env variable `OUTPUT_PATH` should be set");
+
+            rental_yield_runner::run(input, output).await;
+        }
+        "scrape" => {
+            scrapper::run_all(&selected_scrappers(&scrappers)).await;
+        }
+        "reprocess" => {
+            let archive: PageArchive =
+                PageArchive::from_env().expect("env variable `ARCHIVE_DIR` should be set");
 
-            let _ = llm_runner::run(llm_key, input, output).await;
+            reprocess_runner::run(&selected_scrappers(&scrappers), &archive).await;
+        }
+        "checkpoints" => {
+            checkpoint_runner::show(
+                &selected_scrappers(&scrappers),
+                &CheckpointStore::from_env(),
+            )
+            .await;
         }
-        _ => {
-            println!("Invalid mode provided. Use `remax`, `era`, `supercasas`, idealista, `imovirtual` or llm.");
+        "reset_checkpoints" => {
+            checkpoint_runner::reset(
+                &selected_scrappers(&scrappers),
+                &CheckpointStore::from_env(),
+            )
+            .await;
+        }
+        "import" => {
+            let database_path: String = env::var("DATABASE_PATH").unwrap_or("res.db".to_string());
+
+            import_runner::run(&scrappers, &database_path).await;
         }
+        _ => match scrappers.iter().find(|scrapper| scrapper.portal() == mode) {
+            Some(scrapper) => scrapper.run().await,
+            None => {
+                let portals: Vec<String> = scrappers
+                    .iter()
+                    .map(|scrapper| format!("`{}`", scrapper.portal()))
+                    .collect();
+                println!(
+                    "Invalid mode provided. Use {}, `scrape`, `llm`, `normalize`, `import`, `reprocess`, `checkpoints`, `reset_checkpoints`, `revisit`, `price_drops`, `time_on_market`, `duplicates` or `rental_yield`.",
+                    portals.join(", ")
+                );
+            }
+        },
     }
 }
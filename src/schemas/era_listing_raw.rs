@@ -1,12 +1,37 @@
-use crate::schemas::llm::ToLLMRequestBody;
+use crate::schemas::listing::{Listing, Normalize, Transaction};
+use crate::scrappers::era_scrapper::EraScrapper;
+use crate::scrappers::scrapper::Scrapper;
+use crate::utils::listing_utils::normalize_listing;
 use serde::{Deserialize, Serialize};
 
-#[derive(Deserialize, Serialize)]
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
 pub struct EraListingRaw {
     pub price: String,
     pub description: Option<String>,
     pub details_split_by_string: Vec<String>,
     pub url_id: String,
+    /// The address of the property, freguesia and concelho as "Lordelo do Ouro e Massarelos, Porto"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transaction: Option<Transaction>,
 }
 
-impl ToLLMRequestBody for EraListingRaw {}
+impl Normalize for EraListingRaw {
+    fn normalize(&self) -> Listing {
+        let listing: Listing = normalize_listing(
+            EraScrapper.portal(),
+            &self.url_id,
+            EraScrapper.listing_url(&self.url_id),
+            Some(&self.price),
+            &self.details_split_by_string,
+            self.description.as_deref(),
+            self.location.as_deref(),
+        );
+
+        Listing {
+            transaction: self.transaction.unwrap_or(listing.transaction),
+            ..listing
+        }
+    }
+}
@@ -1,12 +1,38 @@
-use crate::schemas::llm::ToLLMRequestBody;
+use crate::schemas::listing::{Listing, Normalize, Transaction};
+use crate::scrappers::idealista_scrapper::IdealistaScrapper;
+use crate::scrappers::scrapper::Scrapper;
+use crate::utils::listing_utils::normalize_listing;
 use serde::{Deserialize, Serialize};
 
-#[derive(Deserialize, Serialize)]
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
 pub struct IdealistaListingRaw {
     pub price: String,
     pub description: Option<String>,
     pub details_split_by_string: String,
     pub url_id: String,
+    /// The zone under the title, freguesia and concelho as "Santo António, Lisboa", when the
+    /// advertiser shows it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transaction: Option<Transaction>,
 }
 
-impl ToLLMRequestBody for IdealistaListingRaw {}
+impl Normalize for IdealistaListingRaw {
+    fn normalize(&self) -> Listing {
+        let listing: Listing = normalize_listing(
+            IdealistaScrapper.portal(),
+            &self.url_id,
+            IdealistaScrapper.listing_url(&self.url_id),
+            Some(&self.price),
+            std::slice::from_ref(&self.details_split_by_string),
+            self.description.as_deref(),
+            self.location.as_deref(),
+        );
+
+        Listing {
+            transaction: self.transaction.unwrap_or(listing.transaction),
+            ..listing
+        }
+    }
+}
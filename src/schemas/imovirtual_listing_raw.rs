@@ -1,12 +1,38 @@
-use crate::schemas::llm::ToLLMRequestBody;
+use crate::schemas::listing::{Listing, Normalize, Transaction};
+use crate::scrappers::imovirtual_scrapper::ImovirtualScrapper;
+use crate::scrappers::scrapper::Scrapper;
+use crate::utils::listing_utils::normalize_listing;
 use serde::{Deserialize, Serialize};
 
-#[derive(Deserialize, Serialize)]
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
 pub struct ImovirtualListingRaw {
     pub price: Option<String>,
     pub description: String,
     pub details_split_by_string: Vec<String>,
     pub url_id: String,
+    /// The address linking to the map, from the street to the district as
+    /// "Rua Cândido dos Reis, Cacilhas, Almada, Setúbal"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transaction: Option<Transaction>,
 }
 
-impl ToLLMRequestBody for ImovirtualListingRaw {}
+impl Normalize for ImovirtualListingRaw {
+    fn normalize(&self) -> Listing {
+        let listing: Listing = normalize_listing(
+            ImovirtualScrapper.portal(),
+            &self.url_id,
+            ImovirtualScrapper.listing_url(&self.url_id),
+            self.price.as_deref(),
+            &self.details_split_by_string,
+            Some(&self.description),
+            self.location.as_deref(),
+        );
+
+        Listing {
+            transaction: self.transaction.unwrap_or(listing.transaction),
+            ..listing
+        }
+    }
+}
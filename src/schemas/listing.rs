@@ -0,0 +1,110 @@
+use serde::{Deserialize, Serialize};
+
+/// Portal independent view of a listing, built from any `*ListingRaw` through [`Normalize`]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Default)]
+pub struct Listing {
+    pub portal: String,
+    pub url_id: String,
+    pub url: String,
+    /// Whether the listing is on sale or to rent, the price of a rental is its monthly rent
+    #[serde(default)]
+    pub transaction: Transaction,
+    pub price_cents: Option<u64>,
+    pub area_sqr_meters: Option<f32>,
+    pub typology: Option<Typology>,
+    pub no_bedrooms: Option<u32>,
+    pub no_bathrooms: Option<u32>,
+    pub district: Option<String>,
+    pub concelho: Option<String>,
+    pub freguesia: Option<String>,
+    pub property_kind: Option<PropertyKind>,
+    pub features: Vec<String>,
+    #[serde(default)]
+    pub furnished: Option<bool>,
+    /// Shortest contract the landlord of a rental accepts
+    #[serde(default)]
+    pub min_contract_months: Option<u32>,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Raw values that were present but could not be parsed
+    #[serde(default)]
+    pub parse_errors: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Transaction {
+    #[default]
+    Buy,
+    Rent,
+}
+
+impl Transaction {
+    /// How the Portuguese portals name it in their urls
+    pub fn slug(&self) -> &'static str {
+        match self {
+            Transaction::Buy => "comprar",
+            Transaction::Rent => "arrendar",
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Typology {
+    T0,
+    T1,
+    T2,
+    T3,
+    T4,
+    T5,
+    #[serde(rename = "T6+")]
+    T6Plus,
+}
+
+impl Typology {
+    pub fn from_bedrooms(no_bedrooms: u32) -> Typology {
+        match no_bedrooms {
+            0 => Typology::T0,
+            1 => Typology::T1,
+            2 => Typology::T2,
+            3 => Typology::T3,
+            4 => Typology::T4,
+            5 => Typology::T5,
+            _ => Typology::T6Plus,
+        }
+    }
+
+    /// Bedrooms of the typology, the least of them for T6+
+    pub fn no_bedrooms(&self) -> u32 {
+        match self {
+            Typology::T0 => 0,
+            Typology::T1 => 1,
+            Typology::T2 => 2,
+            Typology::T3 => 3,
+            Typology::T4 => 4,
+            Typology::T5 => 5,
+            Typology::T6Plus => 6,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum PropertyKind {
+    Apartment,
+    House,
+    Land,
+    Shop,
+    Office,
+    Warehouse,
+    Garage,
+    Farm,
+    Building,
+}
+
+/// Turns the raw listing of a portal into a [`Listing`]. The `transaction` of a raw listing,
+/// written by `mark_rental` for the listings of a rental search, wins over the one guessed from its
+/// text.
+pub trait Normalize {
+    fn normalize(&self) -> Listing;
+}
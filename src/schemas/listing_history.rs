@@ -0,0 +1,61 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ListingEventKind {
+    PriceChange,
+    DescriptionChange,
+    Delisted,
+}
+
+impl ListingEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ListingEventKind::PriceChange => "price_change",
+            ListingEventKind::DescriptionChange => "description_change",
+            ListingEventKind::Delisted => "delisted",
+        }
+    }
+}
+
+/// Something that changed between two observations of the same listing
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ListingEvent {
+    pub portal: String,
+    pub url_id: String,
+    pub occurred_at: i64,
+    pub kind: ListingEventKind,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct PriceDrop {
+    pub portal: String,
+    pub url_id: String,
+    pub url: String,
+    pub old_price_cents: u64,
+    pub new_price_cents: u64,
+    pub drop_percentage: f64,
+    pub changed_at: i64,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct TimeOnMarket {
+    pub portal: String,
+    pub url_id: String,
+    pub district: Option<String>,
+    pub listed_at: i64,
+    pub delisted_at: Option<i64>,
+    /// Until it was delisted or, for active listings, until now
+    pub days_on_market: f64,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct DistrictTimeOnMarket {
+    pub district: String,
+    pub no_active: u32,
+    pub no_delisted: u32,
+    /// Average over the delisted listings only, active ones are still counting
+    pub average_days_on_market: Option<f64>,
+}
@@ -1,13 +1,14 @@
-use crate::llms::llm_utils::{
-    FREE_LLAMA_MODEL, SYSTEM_CONTENT, SYSTEM_ROLE, USER_CONTENT, USER_ROLE,
-};
+use crate::llms::llm_utils::{SYSTEM_CONTENT, SYSTEM_ROLE, USER_CONTENT, USER_ROLE};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use serde_json::json;
+use serde_json::Value;
 
 #[derive(Deserialize, Serialize)]
 pub struct LLMRequestBody {
     pub model: String,
     pub messages: Vec<LLMBodyMessage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<Value>,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -19,7 +20,7 @@ pub struct LLMBodyMessage {
 #[derive(Deserialize, Serialize, Debug)]
 pub struct LLMResponseRaw {
     pub id: String,
-    pub provider: String,
+    pub provider: Option<String>,
     pub model: String,
     pub object: String,
     pub created: u32,
@@ -66,7 +67,7 @@ pub struct LLMMessageResponse {
     pub content: LLMRealStateResponse,
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, JsonSchema)]
 pub struct LLMRealStateResponse {
     pub url_id: String,
     no_bedrooms: u32,
@@ -84,7 +85,28 @@ pub struct LLMRealStateResponse {
     score: f32,
 }
 
-pub fn to_llm_request_body_json(json: String) -> LLMRequestBody {
+/// A cached answer with what it was computed from
+#[derive(Deserialize, Serialize)]
+pub struct LLMCacheEntry {
+    pub portal: String,
+    pub url_id: String,
+    pub content_hash: String,
+    pub prompt_version: String,
+    pub model: String,
+    pub response: String,
+}
+
+/// A listing the model could not answer in the schema, even after being asked to repair it
+#[derive(Deserialize, Serialize)]
+pub struct LLMDeadLetter {
+    pub input: String,
+    pub output: String,
+    pub error: String,
+    pub attempts: u32,
+    pub failed_at: i64,
+}
+
+pub fn to_llm_request_body_json(json: String, model: &str) -> LLMRequestBody {
     let mut message_vec = Vec::new();
 
     message_vec.push(LLMBodyMessage {
@@ -98,16 +120,8 @@ pub fn to_llm_request_body_json(json: String) -> LLMRequestBody {
     });
 
     LLMRequestBody {
-        model: FREE_LLAMA_MODEL.to_string(),
+        model: model.to_string(),
         messages: message_vec,
-    }
-}
-
-pub trait ToLLMRequestBody {
-    fn to_llm_request_body(&self) -> LLMRequestBody
-    where
-        Self: Serialize,
-    {
-        to_llm_request_body_json(json!(self).to_string())
+        response_format: None,
     }
 }
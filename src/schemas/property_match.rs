@@ -0,0 +1,39 @@
+use serde::{Deserialize, Serialize};
+
+/// Two listings from different portals compared by the matcher, kept to tune the thresholds
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct CandidatePair {
+    pub left_portal: String,
+    pub left_url_id: String,
+    pub right_portal: String,
+    pub right_url_id: String,
+    /// Relative difference, 0.05 means the prices are 5% apart
+    pub price_difference: Option<f64>,
+    pub area_difference: Option<f64>,
+    pub same_typology: Option<bool>,
+    pub same_location: Option<bool>,
+    pub text_similarity: f64,
+    pub is_match: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct PropertyListing {
+    pub portal: String,
+    pub url_id: String,
+    pub url: String,
+    pub price_cents: Option<u64>,
+}
+
+/// The same real property, listed on one or more portals
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Property {
+    pub property_id: String,
+    pub listings: Vec<PropertyListing>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct PropertyIdAssignment {
+    pub portal: String,
+    pub url_id: String,
+    pub property_id: String,
+}
@@ -1,12 +1,37 @@
-use crate::schemas::llm::ToLLMRequestBody;
+use crate::schemas::listing::{Listing, Normalize, Transaction};
+use crate::scrappers::remax_scrapper::RemaxScrapper;
+use crate::scrappers::scrapper::Scrapper;
+use crate::utils::listing_utils::normalize_listing;
 use serde::{Deserialize, Serialize};
 
-#[derive(Deserialize, Serialize)]
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
 pub struct RemaxListingRaw {
     pub price: String,
     pub description: String,
     pub details_split_by_string: Vec<String>,
     pub url_id: String,
+    /// The address under the title, freguesia and concelho as "Arroios, Lisboa"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transaction: Option<Transaction>,
 }
 
-impl ToLLMRequestBody for RemaxListingRaw {}
+impl Normalize for RemaxListingRaw {
+    fn normalize(&self) -> Listing {
+        let listing: Listing = normalize_listing(
+            RemaxScrapper.portal(),
+            &self.url_id,
+            RemaxScrapper.listing_url(&self.url_id),
+            Some(&self.price),
+            &self.details_split_by_string,
+            Some(&self.description),
+            self.location.as_deref(),
+        );
+
+        Listing {
+            transaction: self.transaction.unwrap_or(listing.transaction),
+            ..listing
+        }
+    }
+}
@@ -0,0 +1,21 @@
+use crate::schemas::listing::Typology;
+use serde::{Deserialize, Serialize};
+
+/// A listing for sale with the rent it could make, estimated from comparable rentals
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct RentalYield {
+    pub portal: String,
+    pub url_id: String,
+    pub url: String,
+    pub price_cents: u64,
+    pub concelho: Option<String>,
+    pub freguesia: Option<String>,
+    pub typology: Option<Typology>,
+    pub estimated_monthly_rent_cents: u64,
+    /// Rentals the estimate comes from
+    pub comparables: usize,
+    /// Yearly rent over the price, 0.05 is 5%
+    pub gross_yield: f64,
+    /// Yearly rent after vacancy and expenses over the price with the purchase costs
+    pub net_yield: f64,
+}
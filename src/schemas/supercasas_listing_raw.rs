@@ -1,12 +1,37 @@
-use crate::schemas::llm::ToLLMRequestBody;
+use crate::schemas::listing::{Listing, Normalize, Transaction};
+use crate::scrappers::scrapper::Scrapper;
+use crate::scrappers::supercasas_scrapper::SuperCasasScrapper;
+use crate::utils::listing_utils::normalize_listing;
 use serde::{Deserialize, Serialize};
 
-#[derive(Deserialize, Serialize)]
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
 pub struct SuperCasasListingRaw {
     pub price: String,
     pub description: Option<String>,
     pub details_split_by_string: Vec<String>,
     pub url_id: String,
+    /// The location under the price, freguesia and concelho as "Campo de Ourique, Lisboa"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transaction: Option<Transaction>,
 }
 
-impl ToLLMRequestBody for SuperCasasListingRaw {}
+impl Normalize for SuperCasasListingRaw {
+    fn normalize(&self) -> Listing {
+        let listing: Listing = normalize_listing(
+            SuperCasasScrapper.portal(),
+            &self.url_id,
+            SuperCasasScrapper.listing_url(&self.url_id),
+            Some(&self.price),
+            &self.details_split_by_string,
+            self.description.as_deref(),
+            self.location.as_deref(),
+        );
+
+        Listing {
+            transaction: self.transaction.unwrap_or(listing.transaction),
+            ..listing
+        }
+    }
+}
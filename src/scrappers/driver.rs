@@ -1,43 +1,683 @@
-use rand::prelude::ThreadRng;
-use rand::Rng;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
 use std::env;
-use std::process::Command;
-use std::time::Duration;
-use thirtyfour::error::WebDriverError;
+use std::error::Error;
+use std::net::TcpListener;
+use std::ops::Deref;
+use std::process::{Child, Command};
+use std::sync::{Mutex, Once};
+use std::time::{Duration, Instant};
+use thirtyfour::common::capabilities::firefox::FirefoxPreferences;
 use thirtyfour::{
-    CapabilitiesHelper, ChromeCapabilities, ChromiumLikeCapabilities, DesiredCapabilities,
-    SafariCapabilities, WebDriver,
+    CapabilitiesHelper, ChromiumLikeCapabilities, DesiredCapabilities, FirefoxCapabilities,
+    PageLoadStrategy, Proxy, SafariCapabilities, WebDriver,
 };
+use tokio::runtime::{Handle, RuntimeFlavor};
+use tokio::task::block_in_place;
 
-pub async fn initialize_driver() -> Result<WebDriver, WebDriverError> {
-    let mut rng: ThreadRng = rand::thread_rng();
-    let port_number: i32 = rng.gen_range(1000..=6000);
-    let port_host = format!("http://localhost:{}", port_number);
+pub type DriverResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
 
-    let driver_path: &str =
-        &*env::var("DRIVER_PATH").expect("env variable `DRIVER_PATH` should be set");
+const DRIVER_STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+const STATUS_POLL_INTERVAL: Duration = Duration::from_millis(100);
+const SESSION_QUIT_TIMEOUT: Duration = Duration::from_secs(10);
 
-    Command::new(driver_path)
-        .arg(format!("--port={}", port_number))
-        .spawn()?;
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Browser {
+    Chrome,
+    Chromium,
+    Firefox,
+    Safari,
+}
+
+impl Browser {
+    fn parse(browser: &str) -> DriverResult<Browser> {
+        match browser {
+            "chrome" => Ok(Browser::Chrome),
+            "chromium" => Ok(Browser::Chromium),
+            "firefox" => Ok(Browser::Firefox),
+            "safari" => Ok(Browser::Safari),
+            browser => Err(format!(
+                "Invalid browser {}, use `chrome`, `chromium`, `firefox` or `safari`",
+                browser
+            )
+            .into()),
+        }
+    }
+
+    /// The browser the driver at `driver_path` controls, guessed from its name
+    fn from_driver_path(driver_path: &str) -> DriverResult<Browser> {
+        match driver_path {
+            path if path.contains("chromedriver") => Ok(Browser::Chrome),
+            path if path.contains("geckodriver") => Ok(Browser::Firefox),
+            path if path.contains("safaridriver") => Ok(Browser::Safari),
+            path => Err(format!("Cannot tell the browser of {}, set `BROWSER`", path).into()),
+        }
+    }
+}
+
+fn parse_page_load_strategy(strategy: &str) -> DriverResult<PageLoadStrategy> {
+    match strategy {
+        "normal" => Ok(PageLoadStrategy::Normal),
+        "eager" => Ok(PageLoadStrategy::Eager),
+        "none" => Ok(PageLoadStrategy::None),
+        strategy => Err(format!(
+            "Invalid page load strategy {}, use `normal`, `eager` or `none`",
+            strategy
+        )
+        .into()),
+    }
+}
+
+/// `socks5://host:port` or `[http://]host:port`, used for http and https
+fn parse_proxy(proxy: &str) -> Proxy {
+    let (socks_proxy, http_proxy): (Option<String>, Option<String>) =
+        match proxy.strip_prefix("socks5://") {
+            Some(address) => (Some(address.to_string()), None),
+            None => (None, Some(proxy.trim_start_matches("http://").to_string())),
+        };
+
+    Proxy::Manual {
+        ftp_proxy: None,
+        ssl_proxy: http_proxy.clone(),
+        http_proxy,
+        socks_version: socks_proxy.as_ref().map(|_| 5),
+        socks_proxy,
+        socks_username: None,
+        socks_password: None,
+        no_proxy: None,
+    }
+}
+
+/// How the browser sessions of the scrappers are started
+#[derive(Debug, Clone)]
+pub struct DriverConfig {
+    pub driver_path: String,
+    pub browser: Browser,
+    pub headless: bool,
+    pub window_size: (u32, u32),
+    pub user_agent: Option<String>,
+    /// Sent in `Accept-Language`, the portals answer in it
+    pub language: String,
+    pub proxy: Option<String>,
+    pub page_load_strategy: PageLoadStrategy,
+    /// Path of the browser itself when it is not the one the driver finds
+    pub browser_binary: Option<String>,
+    pub extra_args: Vec<String>,
+}
+
+impl DriverConfig {
+    pub fn new(driver_path: &str, browser: Browser) -> DriverConfig {
+        DriverConfig {
+            driver_path: driver_path.to_string(),
+            browser,
+            headless: false,
+            window_size: (1920, 1080),
+            user_agent: None,
+            language: "pt-PT".to_string(),
+            proxy: None,
+            page_load_strategy: PageLoadStrategy::Normal,
+            browser_binary: None,
+            extra_args: Vec::new(),
+        }
+    }
+
+    pub fn headless(mut self, headless: bool) -> DriverConfig {
+        self.headless = headless;
+        self
+    }
+
+    pub fn window_size(mut self, width: u32, height: u32) -> DriverConfig {
+        self.window_size = (width, height);
+        self
+    }
+
+    pub fn user_agent(mut self, user_agent: Option<String>) -> DriverConfig {
+        self.user_agent = user_agent;
+        self
+    }
+
+    pub fn language(mut self, language: &str) -> DriverConfig {
+        self.language = language.to_string();
+        self
+    }
+
+    pub fn proxy(mut self, proxy: Option<String>) -> DriverConfig {
+        self.proxy = proxy;
+        self
+    }
+
+    pub fn page_load_strategy(mut self, page_load_strategy: PageLoadStrategy) -> DriverConfig {
+        self.page_load_strategy = page_load_strategy;
+        self
+    }
 
-    tokio::time::sleep(Duration::from_secs(1)).await;
+    pub fn browser_binary(mut self, browser_binary: Option<String>) -> DriverConfig {
+        self.browser_binary = browser_binary;
+        self
+    }
 
-    let driver: WebDriver = match driver_path {
-        path if path.contains("chromedriver") => {
-            let mut caps: ChromeCapabilities = DesiredCapabilities::chrome();
-            caps.add_arg("--disable-blink-features=AutomationControlled")?;
-            caps.add_arg("--window-size=1920,1080")?;
-            caps.set_javascript_enabled(true)?;
-            WebDriver::new(port_host, caps).await?
+    pub fn extra_args(mut self, extra_args: Vec<String>) -> DriverConfig {
+        self.extra_args = extra_args;
+        self
+    }
+
+    /// Reads `DRIVER_PATH`, `BROWSER`, `HEADLESS`, `WINDOW_SIZE`, `USER_AGENT`, `BROWSER_LANGUAGE`,
+    /// `PROXY`, `PAGE_LOAD_STRATEGY`, `BROWSER_BINARY` and `BROWSER_ARGS`
+    pub fn from_env() -> DriverResult<DriverConfig> {
+        let driver_path: String =
+            env::var("DRIVER_PATH").map_err(|_| "env variable `DRIVER_PATH` should be set")?;
+        let browser: Browser = match env::var("BROWSER") {
+            Ok(browser) => Browser::parse(&browser)?,
+            Err(_) => Browser::from_driver_path(&driver_path)?,
+        };
+        // Servers have no display to open the browser window in
+        let headless: bool = match env::var("HEADLESS") {
+            Ok(headless) => headless == "true",
+            Err(_) => {
+                cfg!(target_os = "linux")
+                    && env::var("DISPLAY").is_err()
+                    && env::var("WAYLAND_DISPLAY").is_err()
+            }
+        };
+        let (width, height): (u32, u32) = match env::var("WINDOW_SIZE") {
+            Ok(window_size) => window_size
+                .split_once('x')
+                .and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)))
+                .ok_or("`WINDOW_SIZE` should be like 1920x1080")?,
+            Err(_) => (1920, 1080),
+        };
+        let page_load_strategy: PageLoadStrategy = match env::var("PAGE_LOAD_STRATEGY") {
+            Ok(strategy) => parse_page_load_strategy(&strategy)?,
+            Err(_) => PageLoadStrategy::Normal,
+        };
+
+        Ok(DriverConfig::new(&driver_path, browser)
+            .headless(headless)
+            .window_size(width, height)
+            .user_agent(env::var("USER_AGENT").ok())
+            .language(&env::var("BROWSER_LANGUAGE").unwrap_or("pt-PT".to_string()))
+            .proxy(env::var("PROXY").ok())
+            .page_load_strategy(page_load_strategy)
+            .browser_binary(env::var("BROWSER_BINARY").ok())
+            .extra_args(
+                env::var("BROWSER_ARGS")
+                    .map(|args| args.split_whitespace().map(str::to_string).collect())
+                    .unwrap_or_default(),
+            ))
+    }
+
+    fn configure_common<C: CapabilitiesHelper>(&self, caps: &mut C) -> DriverResult<()> {
+        caps.set_javascript_enabled(true)?;
+        caps.set_page_load_strategy(self.page_load_strategy.clone())?;
+        if let Some(proxy) = &self.proxy {
+            caps.set_proxy(parse_proxy(proxy))?;
+        }
+        Ok(())
+    }
+
+    fn configure_chromium<C: ChromiumLikeCapabilities>(&self, caps: &mut C) -> DriverResult<()> {
+        self.configure_common(caps)?;
+        caps.add_arg("--disable-blink-features=AutomationControlled")?;
+        caps.add_arg(&format!(
+            "--window-size={},{}",
+            self.window_size.0, self.window_size.1
+        ))?;
+        caps.add_arg(&format!("--lang={}", self.language))?;
+        caps.add_experimental_option("prefs", json!({ "intl.accept_languages": self.language }))?;
+
+        if self.headless {
+            caps.add_arg("--headless=new")?;
+            // /dev/shm is tiny in containers and makes the tabs crash
+            caps.add_arg("--disable-dev-shm-usage")?;
+        }
+        if let Some(user_agent) = &self.user_agent {
+            caps.add_arg(&format!("--user-agent={}", user_agent))?;
         }
-        path if path.contains("safaridriver") => {
-            let mut caps: SafariCapabilities = DesiredCapabilities::safari();
-            caps.set_javascript_enabled(true)?;
-            WebDriver::new(port_host, caps).await?
+        if let Some(browser_binary) = &self.browser_binary {
+            caps.set_binary(browser_binary)?;
         }
-        _ => panic!("Unsupported driver path"),
-    };
+        for arg in &self.extra_args {
+            caps.add_arg(arg)?;
+        }
+        Ok(())
+    }
+
+    fn firefox_capabilities(&self) -> DriverResult<FirefoxCapabilities> {
+        let mut caps: FirefoxCapabilities = DesiredCapabilities::firefox();
+        self.configure_common(&mut caps)?;
+        caps.add_arg(&format!("--width={}", self.window_size.0))?;
+        caps.add_arg(&format!("--height={}", self.window_size.1))?;
+
+        let mut preferences: FirefoxPreferences = FirefoxPreferences::new();
+        preferences.set("intl.accept_languages", &self.language)?;
+        if let Some(user_agent) = &self.user_agent {
+            preferences.set("general.useragent.override", user_agent)?;
+        }
+        caps.set_preferences(preferences)?;
+
+        if self.headless {
+            caps.set_headless()?;
+        }
+        if let Some(browser_binary) = &self.browser_binary {
+            caps.set_firefox_binary(browser_binary)?;
+        }
+        for arg in &self.extra_args {
+            caps.add_arg(arg)?;
+        }
+        Ok(caps)
+    }
+
+    fn safari_capabilities(&self) -> DriverResult<SafariCapabilities> {
+        if self.headless {
+            return Err("Safari cannot run headless".into());
+        }
+        if self.user_agent.is_some() {
+            return Err("Safari does not support a custom user agent".into());
+        }
+
+        let mut caps: SafariCapabilities = DesiredCapabilities::safari();
+        self.configure_common(&mut caps)?;
+        Ok(caps)
+    }
+
+    /// Starts a browser session on the driver listening at `server_url`
+    pub async fn start_session(&self, server_url: &str) -> DriverResult<WebDriver> {
+        let web_driver: WebDriver = match self.browser {
+            Browser::Chrome => {
+                let mut caps = DesiredCapabilities::chrome();
+                self.configure_chromium(&mut caps)?;
+                WebDriver::new(server_url, caps).await?
+            }
+            Browser::Chromium => {
+                let mut caps = DesiredCapabilities::chromium();
+                self.configure_chromium(&mut caps)?;
+                WebDriver::new(server_url, caps).await?
+            }
+            Browser::Firefox => WebDriver::new(server_url, self.firefox_capabilities()?).await?,
+            Browser::Safari => WebDriver::new(server_url, self.safari_capabilities()?).await?,
+        };
+
+        Ok(web_driver)
+    }
+}
+
+/// Drivers that are running, killed on Ctrl-C as the guards are not dropped when exiting
+static DRIVER_PROCESSES: Mutex<BTreeMap<u32, Child>> = Mutex::new(BTreeMap::new());
+static CTRL_C_HANDLER: Once = Once::new();
+
+fn kill_child(mut child: Child) {
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+fn install_ctrl_c_handler() {
+    CTRL_C_HANDLER.call_once(|| {
+        tokio::spawn(async {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                println!("Stopping the web drivers");
+                let children: BTreeMap<u32, Child> = match DRIVER_PROCESSES.lock() {
+                    Ok(mut processes) => std::mem::take(&mut *processes),
+                    Err(_) => BTreeMap::new(),
+                };
+                children.into_values().for_each(kill_child);
+                std::process::exit(130);
+            }
+        });
+    });
+}
+
+/// A free port chosen by the OS
+fn free_port() -> DriverResult<u16> {
+    Ok(TcpListener::bind(("127.0.0.1", 0))?.local_addr()?.port())
+}
+
+/// A running web driver, killed when dropped
+pub struct DriverProcess {
+    pid: u32,
+    pub server_url: String,
+}
+
+impl DriverProcess {
+    /// Spawns the driver at `driver_path` on a free port and waits until it accepts sessions
+    pub async fn start(driver_path: &str) -> DriverResult<DriverProcess> {
+        install_ctrl_c_handler();
+
+        let port: u16 = free_port()?;
+        let child: Child = Command::new(driver_path)
+            .arg(format!("--port={}", port))
+            .spawn()?;
+        let pid: u32 = child.id();
+        DRIVER_PROCESSES
+            .lock()
+            .map_err(|_| "driver processes lock poisoned")?
+            .insert(pid, child);
+
+        let driver_process: DriverProcess = DriverProcess {
+            pid,
+            server_url: format!("http://localhost:{}", port),
+        };
+        driver_process
+            .wait_until_ready(DRIVER_STARTUP_TIMEOUT)
+            .await?;
+        Ok(driver_process)
+    }
+
+    fn has_exited(&self) -> bool {
+        match DRIVER_PROCESSES.lock() {
+            Ok(mut processes) => processes
+                .get_mut(&self.pid)
+                .is_none_or(|child| !matches!(child.try_wait(), Ok(None))),
+            Err(_) => true,
+        }
+    }
+
+    async fn is_ready(&self, client: &reqwest::Client) -> bool {
+        let response = match client
+            .get(format!("{}/status", self.server_url))
+            .send()
+            .await
+        {
+            Ok(response) => response,
+            Err(_) => return false,
+        };
+        if !response.status().is_success() {
+            return false;
+        }
+
+        match response.json::<Value>().await {
+            Ok(status) => status["value"]["ready"].as_bool().unwrap_or(true),
+            Err(_) => false,
+        }
+    }
+
+    /// Polls the `/status` endpoint of the driver until it is ready
+    async fn wait_until_ready(&self, timeout: Duration) -> DriverResult<()> {
+        let client: reqwest::Client = reqwest::Client::builder()
+            .timeout(STATUS_POLL_INTERVAL * 10)
+            .build()?;
+        let started_at: Instant = Instant::now();
+
+        while started_at.elapsed() < timeout {
+            if self.is_ready(&client).await {
+                println!("Web driver ready at {}", self.server_url);
+                return Ok(());
+            }
+            if self.has_exited() {
+                return Err(
+                    format!("Web driver at {} exited while starting", self.server_url).into(),
+                );
+            }
+            tokio::time::sleep(STATUS_POLL_INTERVAL).await;
+        }
+
+        Err(format!(
+            "Web driver at {} was not ready after {}s",
+            self.server_url,
+            timeout.as_secs_f32()
+        )
+        .into())
+    }
+}
+
+impl Drop for DriverProcess {
+    fn drop(&mut self) {
+        let child: Option<Child> = match DRIVER_PROCESSES.lock() {
+            Ok(mut processes) => processes.remove(&self.pid),
+            Err(_) => None,
+        };
+        if let Some(child) = child {
+            kill_child(child);
+        }
+    }
+}
+
+/// A browser session and the driver running it, the session is quit before the driver is killed
+pub struct DriverSession {
+    web_driver: WebDriver,
+    _driver_process: DriverProcess,
+}
+
+impl Drop for DriverSession {
+    fn drop(&mut self) {
+        // thirtyfour quits dropped sessions in the background, the driver would be killed first
+        let web_driver: WebDriver = self.web_driver.clone();
+        if let Ok(handle) = Handle::try_current() {
+            if handle.runtime_flavor() == RuntimeFlavor::MultiThread {
+                let _ = block_in_place(|| {
+                    handle.block_on(tokio::time::timeout(
+                        SESSION_QUIT_TIMEOUT,
+                        web_driver.quit(),
+                    ))
+                });
+            }
+        }
+    }
+}
+
+impl Deref for DriverSession {
+    type Target = WebDriver;
+
+    fn deref(&self) -> &WebDriver {
+        &self.web_driver
+    }
+}
+
+pub async fn initialize_driver() -> DriverResult<DriverSession> {
+    let config: DriverConfig = DriverConfig::from_env()?;
+    let driver_process: DriverProcess = DriverProcess::start(&config.driver_path).await?;
+    let web_driver: WebDriver = config.start_session(&driver_process.server_url).await?;
+
+    Ok(DriverSession {
+        web_driver,
+        _driver_process: driver_process,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener as AsyncTcpListener;
+
+    fn to_json<T: serde::Serialize>(caps: &T) -> Value {
+        serde_json::to_value(caps).unwrap()
+    }
+
+    #[test]
+    fn guesses_the_browser_from_the_driver_name() {
+        let cases: [(&str, Browser); 4] = [
+            ("/usr/bin/chromedriver", Browser::Chrome),
+            ("C:\\drivers\\chromedriver.exe", Browser::Chrome),
+            ("./geckodriver", Browser::Firefox),
+            ("/usr/bin/safaridriver", Browser::Safari),
+        ];
+
+        for (driver_path, expected) in cases {
+            assert_eq!(Browser::from_driver_path(driver_path).unwrap(), expected);
+        }
+        assert!(Browser::from_driver_path("/usr/bin/msedgedriver").is_err());
+        assert_eq!(Browser::parse("chromium").unwrap(), Browser::Chromium);
+        assert!(Browser::parse("Chrome").is_err());
+    }
+
+    #[test]
+    fn parses_proxies() {
+        assert_eq!(
+            to_json(&parse_proxy("http://10.0.0.1:3128")),
+            json!({"proxyType": "manual", "httpProxy": "10.0.0.1:3128", "sslProxy": "10.0.0.1:3128"})
+        );
+        assert_eq!(
+            to_json(&parse_proxy("10.0.0.1:3128")),
+            to_json(&parse_proxy("http://10.0.0.1:3128"))
+        );
+        assert_eq!(
+            to_json(&parse_proxy("socks5://10.0.0.1:1080")),
+            json!({"proxyType": "manual", "socksProxy": "10.0.0.1:1080", "socksVersion": 5})
+        );
+    }
+
+    #[test]
+    fn builds_chromium_capabilities() {
+        let config: DriverConfig = DriverConfig::new("chromedriver", Browser::Chrome)
+            .headless(true)
+            .window_size(1280, 720)
+            .user_agent(Some("Mozilla/5.0".to_string()))
+            .language("en-GB")
+            .proxy(Some("socks5://10.0.0.1:1080".to_string()))
+            .page_load_strategy(PageLoadStrategy::Eager)
+            .browser_binary(Some("/opt/chrome/chrome".to_string()))
+            .extra_args(vec!["--no-sandbox".to_string()]);
+        let mut caps = DesiredCapabilities::chrome();
+        config.configure_chromium(&mut caps).unwrap();
+        let caps: Value = to_json(&caps);
+
+        assert_eq!(
+            caps["goog:chromeOptions"]["args"],
+            json!([
+                "--disable-blink-features=AutomationControlled",
+                "--window-size=1280,720",
+                "--lang=en-GB",
+                "--headless=new",
+                "--disable-dev-shm-usage",
+                "--user-agent=Mozilla/5.0",
+                "--no-sandbox"
+            ])
+        );
+        assert_eq!(
+            caps["goog:chromeOptions"]["prefs"],
+            json!({"intl.accept_languages": "en-GB"})
+        );
+        assert_eq!(caps["goog:chromeOptions"]["binary"], "/opt/chrome/chrome");
+        assert_eq!(caps["pageLoadStrategy"], "eager");
+        assert_eq!(caps["proxy"]["socksProxy"], "10.0.0.1:1080");
+    }
+
+    #[test]
+    fn builds_firefox_capabilities() {
+        let config: DriverConfig = DriverConfig::new("geckodriver", Browser::Firefox)
+            .headless(true)
+            .user_agent(Some("Mozilla/5.0".to_string()));
+        let caps: Value = to_json(&config.firefox_capabilities().unwrap());
+
+        assert_eq!(
+            caps["moz:firefoxOptions"]["args"],
+            json!(["--width=1920", "--height=1080", "-headless"])
+        );
+        assert_eq!(
+            caps["moz:firefoxOptions"]["prefs"],
+            json!({"intl.accept_languages": "pt-PT", "general.useragent.override": "Mozilla/5.0"})
+        );
+        assert!(caps.get("proxy").is_none());
+    }
+
+    #[test]
+    fn refuses_what_safari_cannot_do() {
+        let config: DriverConfig = DriverConfig::new("safaridriver", Browser::Safari);
+
+        assert!(config.safari_capabilities().is_ok());
+        assert!(config.clone().headless(true).safari_capabilities().is_err());
+        assert!(config
+            .user_agent(Some("Mozilla/5.0".to_string()))
+            .safari_capabilities()
+            .is_err());
+    }
+
+    /// Answers `/status` like a driver that is ready or still starting
+    async fn status_server(ready: bool) -> String {
+        let listener: AsyncTcpListener = AsyncTcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let server_url: String =
+            format!("http://127.0.0.1:{}", listener.local_addr().unwrap().port());
+
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut request: [u8; 1024] = [0; 1024];
+                let _ = stream.read(&mut request).await;
+                let body: String = json!({"value": {"ready": ready}}).to_string();
+                let response: String = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+
+        server_url
+    }
+
+    /// A process that keeps running as the driver listening at `server_url`
+    fn running_driver(server_url: String) -> DriverProcess {
+        let child: Child = Command::new("sleep").arg("30").spawn().unwrap();
+        let pid: u32 = child.id();
+        DRIVER_PROCESSES.lock().unwrap().insert(pid, child);
+
+        DriverProcess { pid, server_url }
+    }
+
+    fn is_running(pid: u32) -> bool {
+        Command::new("kill")
+            .args(["-0", &pid.to_string()])
+            .status()
+            .is_ok_and(|status| status.success())
+    }
+
+    #[test]
+    fn picks_a_free_port() {
+        let port: u16 = free_port().unwrap();
+
+        assert_ne!(port, 0);
+        assert!(TcpListener::bind(("127.0.0.1", port)).is_ok());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn waits_until_the_driver_is_ready() {
+        let driver_process: DriverProcess = running_driver(status_server(true).await);
+
+        assert!(driver_process
+            .wait_until_ready(Duration::from_secs(5))
+            .await
+            .is_ok());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn times_out_when_the_driver_never_gets_ready() {
+        let driver_process: DriverProcess = running_driver(status_server(false).await);
+        let started_at: Instant = Instant::now();
+
+        let error: String = driver_process
+            .wait_until_ready(Duration::from_millis(500))
+            .await
+            .unwrap_err()
+            .to_string();
+
+        assert!(error.contains("was not ready after"), "{}", error);
+        assert!(started_at.elapsed() >= Duration::from_millis(500));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn fails_when_the_driver_exits_while_starting() {
+        // `sleep --port=...` exits at once with an invalid option
+        let error: String = match DriverProcess::start("sleep").await {
+            Ok(_) => panic!("sleep is not a web driver"),
+            Err(e) => e.to_string(),
+        };
+
+        assert!(error.contains("exited while starting"), "{}", error);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn kills_the_driver_when_dropped() {
+        let driver_process: DriverProcess = running_driver("http://127.0.0.1:9".to_string());
+        let pid: u32 = driver_process.pid;
+        assert!(is_running(pid));
+
+        drop(driver_process);
 
-    Ok(driver)
+        assert!(!DRIVER_PROCESSES.lock().unwrap().contains_key(&pid));
+        assert!(!is_running(pid));
+    }
 }
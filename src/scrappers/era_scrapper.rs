@@ -1,154 +1,304 @@
 use crate::schemas::era_listing_raw::EraListingRaw;
-use crate::scrappers::driver::initialize_driver;
-use crate::utils::file_utils::*;
-use serde_json::json;
-use std::time::Duration;
-use thirtyfour::error::WebDriverError;
-use thirtyfour::{By, WebDriver, WebElement};
-use tokio::fs::File;
-use tokio::time::timeout;
-
-async fn get_url_ids(web_driver: &WebDriver, page: u32) -> Result<Vec<String>, WebDriverError> {
-    let _ = web_driver
-        .get(format!(
-            "https://www.era.pt/comprar?ob=1&tp=1,2&page={}&ord=3",
-            page
-        ))
-        .await;
-    tokio::time::sleep(Duration::from_millis(2500)).await;
+use crate::schemas::listing::{PropertyKind, Transaction};
+use crate::scrappers::html_page::{attr, find_in, text, FieldSelector, HtmlPage};
+use crate::scrappers::page_wait::PageWait;
+use crate::scrappers::scrapper::Scrapper;
+use crate::scrappers::scrapper_error::ScrapperError;
+use crate::scrappers::search_spec::SearchSpec;
+use scraper::ElementRef;
 
-    let mut ids: Vec<String> = Vec::new();
+pub struct EraScrapper;
 
-    // The site takes some time to load this information
-    while ids.is_empty() {
-        // Find all divs within it and get IDs
-        let child_divs: Vec<WebElement> = web_driver.find_all(By::ClassName("card")).await?;
+const URL_IDS_CARD: FieldSelector = FieldSelector::new("url_ids", ".card");
+const URL_IDS_LINK: FieldSelector = FieldSelector::new("url_ids", "a");
+const DESCRIPTION: FieldSelector = FieldSelector::new("description", "#detail-description");
+const DETAILS_ITEM: FieldSelector = FieldSelector::new("details_split_by_string", ".detail");
+const PRICE: FieldSelector = FieldSelector::new("price", ".price-value");
+const LOCATION: FieldSelector = FieldSelector::new("location", ".property-address");
 
-        for div in child_divs {
-            ids.push(div.find(By::Tag("a")).await?.attr("href").await?.unwrap());
+/// Era code of a property kind in the `tp` parameter
+fn era_kind(property_kind: PropertyKind) -> Option<&'static str> {
+    match property_kind {
+        PropertyKind::Apartment => Some("1"),
+        PropertyKind::House => Some("2"),
+        PropertyKind::Land => Some("3"),
+        _ => None,
+    }
+}
+
+impl Scrapper for EraScrapper {
+    type ListingRaw = EraListingRaw;
+
+    fn portal(&self) -> &'static str {
+        "era"
+    }
+
+    // Era is searched in the whole country, the districts of the search are not applied
+    fn segments(&self, search: &SearchSpec) -> Vec<String> {
+        if !search.districts.is_empty() {
+            println!(
+                "{} has no search by district, searching the whole country",
+                self.portal()
+            );
+        }
+
+        match search.portal_kinds(self.portal(), era_kind).is_empty() {
+            true => Vec::new(),
+            false => vec![String::new()],
         }
     }
 
-    println!("{:?}", ids);
+    fn listing_page_url(&self, search: &SearchSpec, _segment: &str, page: u32) -> String {
+        let business: &str = match search.transaction {
+            Transaction::Buy => "1",
+            Transaction::Rent => "2",
+        };
+        let kinds: Vec<&str> = search
+            .property_kinds
+            .iter()
+            .filter_map(|property_kind| era_kind(*property_kind))
+            .collect();
 
-    Ok(ids)
-}
+        let mut filters: String = String::new();
+        for (key, value) in search.range_filters(["pmin", "pmax", "amin", "amax"]) {
+            filters.push_str(&format!("&{}={}", key, value));
+        }
+        if !search.typologies.is_empty() {
+            let bedrooms: Vec<String> = search
+                .bedrooms()
+                .iter()
+                .map(|no_bedrooms| no_bedrooms.to_string())
+                .collect();
+            filters.push_str(&format!("&tip={}", bedrooms.join(",")));
+        }
+
+        format!(
+            "https://www.era.pt/{}?ob={}&tp={}{}&page={}&ord=3",
+            search.transaction.slug(),
+            business,
+            kinds.join(","),
+            filters,
+            page
+        )
+    }
+
+    fn listing_url(&self, url_id: &str) -> String {
+        url_id.to_string()
+    }
 
-async fn get_listing(
-    web_driver: &WebDriver,
-    url_id: String,
-) -> Result<EraListingRaw, WebDriverError> {
-    let _ = web_driver.get(&url_id).await;
+    fn selectors(&self) -> Vec<FieldSelector> {
+        vec![
+            URL_IDS_CARD,
+            URL_IDS_LINK,
+            DESCRIPTION,
+            DETAILS_ITEM,
+            PRICE,
+            LOCATION,
+        ]
+    }
+
+    fn listing_page_wait(&self) -> PageWait {
+        PageWait::document_ready().element(URL_IDS_CARD)
+    }
 
-    tokio::time::sleep(Duration::from_millis(500)).await;
+    fn listing_wait(&self) -> PageWait {
+        PageWait::document_ready().element(PRICE)
+    }
+
+    fn get_url_ids(&self, html_page: &HtmlPage) -> Result<Vec<String>, ScrapperError> {
+        let child_divs: Vec<ElementRef> = html_page.find_all(URL_IDS_CARD)?;
 
-    let description: Option<String> = match web_driver.find(By::Id("detail-description")).await {
-        Ok(details_web_element) => Some(details_web_element.text().await?),
-        Err(_) => {
-            println!("Url {} did not have a description", url_id);
-            None
+        let mut ids: Vec<String> = Vec::new();
+        for div in child_divs {
+            if let Some(href) = attr(find_in(div, URL_IDS_LINK)?, "href") {
+                ids.push(href);
+            }
         }
-    };
 
-    let details_vec: Vec<WebElement> = web_driver.find_all(By::ClassName("detail")).await?;
+        Ok(ids)
+    }
+
+    fn get_listing(
+        &self,
+        html_page: &HtmlPage,
+        url_id: String,
+    ) -> Result<EraListingRaw, ScrapperError> {
+        let description: Option<String> = match html_page.find(DESCRIPTION) {
+            Ok(details_element) => Some(text(details_element)),
+            Err(_) => {
+                println!("Url {} did not have a description", url_id);
+                None
+            }
+        };
+
+        let details_vec: Vec<ElementRef> = html_page.find_all(DETAILS_ITEM)?;
+
+        let price: String = html_page.find(PRICE)?.inner_html();
 
-    let price: String = web_driver
-        .find(By::ClassName("price-value"))
-        .await?
-        .inner_html()
-        .await?;
+        let details_split_by_string: Vec<String> = details_vec.into_iter().map(text).collect();
 
-    let mut details_split_by_string: Vec<String> = Vec::new();
+        let location: Option<String> = html_page.find(LOCATION).ok().map(text);
 
-    for div in details_vec {
-        details_split_by_string.push(div.text().await?);
+        let era_listing_raw = EraListingRaw {
+            price,
+            description,
+            details_split_by_string,
+            url_id,
+            location,
+            transaction: None,
+        };
+
+        Ok(era_listing_raw)
     }
 
-    let era_listing_raw = EraListingRaw {
-        price,
-        description,
-        details_split_by_string,
-        url_id,
-    };
+    fn is_last_page(
+        &self,
+        _html_page: &HtmlPage,
+        _segment: &str,
+        page: u32,
+        url_ids: &[String],
+        previous_url_ids: &[String],
+    ) -> Result<bool, ScrapperError> {
+        if url_ids == previous_url_ids {
+            println!(
+                "url_ids from page {} are equal to url_ids from previous page",
+                page
+            );
+            return Ok(true);
+        }
 
-    Ok(era_listing_raw)
+        Ok(false)
+    }
 }
 
-pub async fn era_scrape_mechanism() -> Result<(), WebDriverError> {
-    let web_driver: WebDriver = initialize_driver().await?;
-    let era_ids_read: File = get_file_read("era_ids.txt").await?;
-    let mut era_ids_write: File = get_file_write_append("era_ids.txt").await?;
-    let mut era_write: File = get_file_write_append("era.json").await?;
-    let era_ids: String = get_content_as_string(era_ids_read).await?;
-
-    let mut latest_url_ids: Vec<String> = Vec::new();
-
-    for page in 1.. {
-        println!("\nScrapper mechanism page {}", page);
-
-        // If we cannot get the page loaded in 30 seconds we ignore it and move on
-        let url_ids_vec: Result<Vec<String>, WebDriverError> =
-            timeout(Duration::from_secs(30), get_url_ids(&web_driver, page))
-                .await
-                .unwrap_or_else(|_| {
-                    println!("Scrapper mechanism url_ids timed out after 30 seconds!");
-                    Ok(Vec::new())
-                });
-
-        match url_ids_vec {
-            Ok(url_ids) => {
-                if latest_url_ids == url_ids {
-                    println!(
-                        "url_ids from page {} are equal to url_ids from previous page",
-                        page
-                    );
-                    break;
-                } else {
-                    latest_url_ids = url_ids.clone();
-                    for url_id in url_ids {
-                        if !era_ids.contains(&url_id) {
-                            println!("Url id: {}", &url_id);
-                            match get_listing(&web_driver, url_id).await {
-                                Ok(era_listing) => {
-                                    write_to_file(
-                                        &mut era_write,
-                                        format!("{}\n", json!(era_listing)),
-                                    )
-                                    .await?;
-
-                                    write_to_file(
-                                        &mut era_ids_write,
-                                        format!("{}\n", era_listing.url_id),
-                                    )
-                                    .await?;
-                                }
-                                Err(_) => println!("Failed to grab listing"),
-                            };
-
-                            tokio::time::sleep(Duration::from_millis(500)).await;
-                        } else {
-                            println!("Url id: {} is already scrapped", url_id);
-                        }
-                    }
-                }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schemas::listing::{Listing, Normalize, Typology};
+    use crate::scrappers::fixture_server::FixtureServer;
+    use crate::scrappers::html_page::FetchedPage;
+
+    #[tokio::test]
+    async fn extracts_url_ids_from_search_page() {
+        let server: FixtureServer = FixtureServer::start("era").await;
+        let fetched: FetchedPage = server.fetch("search.html").await;
+        let html_page: HtmlPage = HtmlPage::parse(&fetched);
+
+        let url_ids: Vec<String> = EraScrapper.get_url_ids(&html_page).unwrap();
+
+        assert_eq!(
+            url_ids,
+            vec![
+                "https://www.era.pt/imoveis/apartamento-t3-venda-porto_25341",
+                "https://www.era.pt/imoveis/moradia-t2-venda-braga_25388",
+            ]
+        );
+        assert!(!EraScrapper
+            .is_last_page(&html_page, "", 2, &url_ids, &[])
+            .unwrap());
+        assert!(EraScrapper
+            .is_last_page(&html_page, "", 2, &url_ids, &url_ids)
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn extracts_listings() {
+        let server: FixtureServer = FixtureServer::start("era").await;
+        let first: FetchedPage = server.fetch("listing_1.html").await;
+        let second: FetchedPage = server.fetch("listing_2.html").await;
+
+        assert_eq!(
+            EraScrapper
+                .get_listing(&HtmlPage::parse(&first), "apartamento_25341".to_string())
+                .unwrap(),
+            EraListingRaw {
+                price: "295.000 €".to_string(),
+                description: Some(
+                    "Apartamento T3 com garagem na Boavista.\nPróximo de escolas e comércio."
+                        .to_string()
+                ),
+                details_split_by_string: vec![
+                    "Tipologia T3".to_string(),
+                    "Área útil 110 m²".to_string(),
+                    "Distrito Porto".to_string(),
+                ],
+                url_id: "apartamento_25341".to_string(),
+                location: Some("Lordelo do Ouro e Massarelos, Porto".to_string()),
+                transaction: None,
             }
-            Err(e) => {
-                println!("Error getting url_ids: {}", e);
-                break;
+        );
+        assert_eq!(
+            EraScrapper
+                .get_listing(&HtmlPage::parse(&second), "moradia_25388".to_string())
+                .unwrap(),
+            EraListingRaw {
+                price: "180.000 €".to_string(),
+                description: None,
+                details_split_by_string: vec![
+                    "Tipologia T2".to_string(),
+                    "Área útil 95 m²".to_string(),
+                ],
+                url_id: "moradia_25388".to_string(),
+                location: None,
+                transaction: None,
             }
-        }
+        );
     }
 
-    Ok(())
-}
+    #[tokio::test]
+    async fn normalizes_listings() {
+        let server: FixtureServer = FixtureServer::start("era").await;
+        let fetched: FetchedPage = server.fetch("listing_1.html").await;
+        let seaside_apartment: EraListingRaw = EraListingRaw {
+            price: "240.000 €".to_string(),
+            description: Some(
+                "Apartamento com guarda-roupa no quarto, a 5 minutos do farol.".to_string(),
+            ),
+            details_split_by_string: vec!["Tipologia T1".to_string()],
+            url_id: "apartamento_25400".to_string(),
+            location: None,
+            transaction: Some(Transaction::Rent),
+        };
 
-pub async fn run() {
-    match era_scrape_mechanism().await {
-        Ok(_) => {
-            println!("Era scrapper mechanism finished")
-        }
-        Err(e) => {
-            eprintln!("Error: {:?}", e);
-        }
+        let listing: Listing = EraScrapper
+            .get_listing(&HtmlPage::parse(&fetched), "apartamento_25341".to_string())
+            .unwrap()
+            .normalize();
+        let seaside_listing: Listing = seaside_apartment.normalize();
+
+        assert_eq!(listing.price_cents, Some(29_500_000));
+        assert_eq!(listing.typology, Some(Typology::T3));
+        assert_eq!(listing.district, Some("porto".to_string()));
+        assert_eq!(listing.property_kind, Some(PropertyKind::Apartment));
+        assert_eq!(listing.features, vec!["garage"]);
+        assert_eq!(
+            listing.freguesia,
+            Some("Lordelo do Ouro e Massarelos".to_string())
+        );
+        assert_eq!(listing.concelho, Some("Porto".to_string()));
+        assert_eq!(seaside_listing.district, None);
+        assert_eq!(seaside_listing.transaction, Transaction::Rent);
+        assert_eq!(seaside_listing.freguesia, None);
+    }
+
+    #[test]
+    fn builds_search_urls() {
+        let default: SearchSpec = SearchSpec::default();
+        let apartments_to_rent: SearchSpec = SearchSpec {
+            transaction: Transaction::Rent,
+            property_kinds: vec![PropertyKind::Apartment],
+            min_price: Some(500),
+            typologies: vec![Typology::T1, Typology::T2],
+            ..SearchSpec::default()
+        };
+
+        assert_eq!(
+            EraScrapper.listing_page_url(&default, "", 2),
+            "https://www.era.pt/comprar?ob=1&tp=1,2&page=2&ord=3"
+        );
+        assert_eq!(
+            EraScrapper.listing_page_url(&apartments_to_rent, "", 1),
+            "https://www.era.pt/arrendar?ob=2&tp=1&pmin=500&tip=1,2&page=1&ord=3"
+        );
     }
 }
@@ -0,0 +1,126 @@
+use crate::scrappers::html_page::{FetchedPage, HtmlPage};
+use crate::scrappers::page_fetcher::PageFetcher;
+use crate::scrappers::page_wait::PageWait;
+use crate::scrappers::scrapper_error::ScrapperError;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+const NOT_FOUND_PAGE: &str = "<html><head><title>404</title></head></html>";
+
+enum FakeResponse {
+    Page {
+        status: u16,
+        final_url: String,
+        source: String,
+    },
+    Error(fn() -> ScrapperError),
+}
+
+/// Answers every url with a page or an error set up by the test, urls it does not know are not
+/// found. Pages are checked against their wait the way the Http fetch mode checks them.
+pub struct FakeFetcher {
+    responses: HashMap<String, FakeResponse>,
+    latencies: HashMap<String, Duration>,
+    fetched: Mutex<Vec<String>>,
+}
+
+impl FakeFetcher {
+    pub fn new() -> FakeFetcher {
+        FakeFetcher {
+            responses: HashMap::new(),
+            latencies: HashMap::new(),
+            fetched: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn page(self, url: &str, source: &str) -> FakeFetcher {
+        self.response(url, 200, url, source)
+    }
+
+    /// Answers with a page saved in `tests/fixtures/{portal}`
+    pub fn fixture(self, url: &str, portal: &str, fixture: &str) -> FakeFetcher {
+        let path: PathBuf = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("fixtures")
+            .join(portal)
+            .join(fixture);
+        let source: String = std::fs::read_to_string(&path)
+            .unwrap_or_else(|e| panic!("fixture {} should exist: {}", path.display(), e));
+        self.page(url, &source)
+    }
+
+    /// Answers with `status` after redirecting to `final_url`
+    pub fn response(
+        mut self,
+        url: &str,
+        status: u16,
+        final_url: &str,
+        source: &str,
+    ) -> FakeFetcher {
+        self.responses.insert(
+            url.to_string(),
+            FakeResponse::Page {
+                status,
+                final_url: final_url.to_string(),
+                source: source.to_string(),
+            },
+        );
+        self
+    }
+
+    pub fn error(mut self, url: &str, error: fn() -> ScrapperError) -> FakeFetcher {
+        self.responses
+            .insert(url.to_string(), FakeResponse::Error(error));
+        self
+    }
+
+    /// Takes that long to answer the url, so concurrent fetches finish out of order
+    pub fn latency(mut self, url: &str, latency: Duration) -> FakeFetcher {
+        self.latencies.insert(url.to_string(), latency);
+        self
+    }
+
+    /// How many times the url was fetched
+    pub fn fetches(&self, url: &str) -> usize {
+        self.fetched
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|fetched| *fetched == url)
+            .count()
+    }
+}
+
+#[async_trait]
+impl PageFetcher for FakeFetcher {
+    async fn fetch(&self, url: &str, wait: &PageWait) -> Result<FetchedPage, ScrapperError> {
+        self.fetched.lock().unwrap().push(url.to_string());
+        tokio::time::sleep(self.latencies.get(url).copied().unwrap_or_default()).await;
+
+        let mut fetched_page: FetchedPage = match self.responses.get(url) {
+            Some(FakeResponse::Error(error)) => return Err(error()),
+            Some(FakeResponse::Page {
+                status,
+                final_url,
+                source,
+            }) => FetchedPage {
+                url: final_url.clone(),
+                status: Some(*status),
+                source: source.clone(),
+                never_loaded: None,
+            },
+            None => FetchedPage {
+                url: url.to_string(),
+                status: Some(404),
+                source: NOT_FOUND_PAGE.to_string(),
+                never_loaded: None,
+            },
+        };
+        fetched_page.never_loaded = wait.unmet_by_html(&HtmlPage::parse(&fetched_page));
+
+        Ok(fetched_page)
+    }
+}
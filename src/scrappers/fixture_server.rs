@@ -0,0 +1,110 @@
+use crate::scrappers::html_page::FetchedPage;
+use crate::scrappers::page_fetcher::{HttpFetcher, PageFetcher};
+use crate::scrappers::page_wait::PageWait;
+use std::path::PathBuf;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+/// Serves the saved pages of `tests/fixtures/{portal}` on a free local port, so the scrappers
+/// can be tested without a browser or the network
+pub struct FixtureServer {
+    base_url: String,
+    fetcher: HttpFetcher,
+    handle: JoinHandle<()>,
+}
+
+async fn serve(mut stream: TcpStream, directory: PathBuf) {
+    let mut request: Vec<u8> = Vec::new();
+    let mut buffer: [u8; 1024] = [0; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        match stream.read(&mut buffer).await {
+            Ok(0) | Err(_) => return,
+            Ok(read) => request.extend_from_slice(&buffer[..read]),
+        }
+    }
+
+    let request: String = String::from_utf8_lossy(&request).to_string();
+    let path: &str = request
+        .split_whitespace()
+        .nth(1)
+        .unwrap_or("/")
+        .split('?')
+        .next()
+        .unwrap_or("/");
+
+    let fixture: Option<Vec<u8>> = match path.contains("..") {
+        true => None,
+        false => tokio::fs::read(directory.join(path.trim_start_matches('/')))
+            .await
+            .ok(),
+    };
+    let (status, body): (&str, Vec<u8>) = match fixture {
+        Some(body) => ("200 OK", body),
+        None => (
+            "404 Not Found",
+            b"<html><head><title>404</title></head></html>".to_vec(),
+        ),
+    };
+
+    let header: String = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        body.len()
+    );
+    let _ = stream.write_all(header.as_bytes()).await;
+    let _ = stream.write_all(&body).await;
+}
+
+impl FixtureServer {
+    pub async fn start(portal: &str) -> FixtureServer {
+        let directory: PathBuf = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("fixtures")
+            .join(portal);
+        let listener: TcpListener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("fixture server should bind a local port");
+        let base_url: String = format!(
+            "http://{}",
+            listener
+                .local_addr()
+                .expect("fixture server should have an address")
+        );
+
+        let handle: JoinHandle<()> = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve(stream, directory.clone()));
+            }
+        });
+
+        FixtureServer {
+            base_url,
+            fetcher: HttpFetcher::new("fixture-tests", "pt-PT").expect("client should build"),
+            handle,
+        }
+    }
+
+    pub fn url(&self, fixture: &str) -> String {
+        format!("{}/{}", self.base_url, fixture)
+    }
+
+    /// Downloads a fixture the way the Http fetch mode downloads a page
+    pub async fn fetch(&self, fixture: &str) -> FetchedPage {
+        self.fetch_waiting(fixture, &PageWait::document_ready())
+            .await
+    }
+
+    pub async fn fetch_waiting(&self, fixture: &str, wait: &PageWait) -> FetchedPage {
+        self.fetcher
+            .fetch(&self.url(fixture), wait)
+            .await
+            .unwrap_or_else(|e| panic!("fixture {} should be served: {}", fixture, e))
+    }
+}
+
+impl Drop for FixtureServer {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
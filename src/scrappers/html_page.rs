@@ -0,0 +1,205 @@
+use crate::scrappers::scrapper_error::ScrapperError;
+use scraper::{ElementRef, Html, Node, Selector};
+use serde_json::Value;
+
+/// Elements whose content starts on a new line when rendered, as `innerText` does
+const BLOCK_ELEMENTS: [&str; 32] = [
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "br",
+    "dd",
+    "div",
+    "dl",
+    "dt",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "li",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "tr",
+    "ul",
+];
+
+/// Elements whose content is never rendered
+const HIDDEN_ELEMENTS: [&str; 5] = ["head", "noscript", "script", "style", "template"];
+
+/// Css selector of a field of the pages of a portal, named so the health checks of a run can
+/// tell which field stopped being found when the portal changes its markup
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FieldSelector {
+    /// Field of the `ListingRaw` the selector extracts, or `url_ids` and `pagination`
+    pub field: &'static str,
+    pub css: &'static str,
+}
+
+impl FieldSelector {
+    pub const fn new(field: &'static str, css: &'static str) -> FieldSelector {
+        FieldSelector { field, css }
+    }
+}
+
+const TITLE: FieldSelector = FieldSelector::new("title", "title");
+
+/// A page as downloaded by a [`PageFetcher`](crate::scrappers::page_fetcher::PageFetcher)
+#[derive(Debug, Clone)]
+pub struct FetchedPage {
+    /// Url the page ended up at, after the redirects
+    pub url: String,
+    /// Http status, unknown when the page was loaded by a browser
+    pub status: Option<u16>,
+    pub source: String,
+    /// The conditions of its [`PageWait`](crate::scrappers::page_wait::PageWait) the page still
+    /// did not meet when the wait timed out
+    pub never_loaded: Option<String>,
+}
+
+/// Parsed Html of a [`FetchedPage`], what the scrappers extract the listings from
+pub struct HtmlPage<'a> {
+    fetched: &'a FetchedPage,
+    document: Html,
+}
+
+fn selector(field_selector: FieldSelector) -> Result<Selector, ScrapperError> {
+    Selector::parse(field_selector.css).map_err(|e| {
+        ScrapperError::Extraction(format!(
+            "invalid selector `{}` of {}: {}",
+            field_selector.css, field_selector.field, e
+        ))
+    })
+}
+
+fn missing(field_selector: FieldSelector) -> ScrapperError {
+    ScrapperError::MissingField {
+        field: field_selector.field,
+        css: field_selector.css,
+    }
+}
+
+impl<'a> HtmlPage<'a> {
+    pub fn parse(fetched: &'a FetchedPage) -> HtmlPage<'a> {
+        HtmlPage {
+            fetched,
+            document: Html::parse_document(&fetched.source),
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.fetched.url
+    }
+
+    pub fn status(&self) -> Option<u16> {
+        self.fetched.status
+    }
+
+    pub fn source(&self) -> &str {
+        &self.fetched.source
+    }
+
+    pub fn title(&self) -> String {
+        match self.find(TITLE) {
+            Ok(title) => text(title),
+            Err(_) => String::new(),
+        }
+    }
+
+    /// The first element matching the Css selector
+    pub fn find(&self, field_selector: FieldSelector) -> Result<ElementRef<'_>, ScrapperError> {
+        self.document
+            .select(&selector(field_selector)?)
+            .next()
+            .ok_or_else(|| missing(field_selector))
+    }
+
+    pub fn find_all(
+        &self,
+        field_selector: FieldSelector,
+    ) -> Result<Vec<ElementRef<'_>>, ScrapperError> {
+        Ok(self.document.select(&selector(field_selector)?).collect())
+    }
+
+    /// Json embedded in a script element, like the `__NEXT_DATA__` of Next.js sites
+    pub fn embedded_json(&self, field_selector: FieldSelector) -> Option<Value> {
+        let script: ElementRef = self.find(field_selector).ok()?;
+        serde_json::from_str(&script.text().collect::<String>()).ok()
+    }
+}
+
+/// The first descendant of `element` matching the Css selector
+pub fn find_in(
+    element: ElementRef<'_>,
+    field_selector: FieldSelector,
+) -> Result<ElementRef<'_>, ScrapperError> {
+    element
+        .select(&selector(field_selector)?)
+        .next()
+        .ok_or_else(|| missing(field_selector))
+}
+
+pub fn find_all_in(
+    element: ElementRef<'_>,
+    field_selector: FieldSelector,
+) -> Result<Vec<ElementRef<'_>>, ScrapperError> {
+    Ok(element.select(&selector(field_selector)?).collect())
+}
+
+pub fn attr(element: ElementRef, name: &str) -> Option<String> {
+    element.value().attr(name).map(str::to_string)
+}
+
+fn push_text(element: ElementRef, rendered: &mut String) {
+    let name: &str = element.value().name();
+    if HIDDEN_ELEMENTS.contains(&name) {
+        return;
+    }
+
+    let is_block: bool = BLOCK_ELEMENTS.contains(&name);
+    if is_block {
+        rendered.push('\n');
+    }
+    for child in element.children() {
+        match child.value() {
+            Node::Text(text) => rendered.push_str(text),
+            Node::Element(_) => {
+                if let Some(child) = ElementRef::wrap(child) {
+                    push_text(child, rendered);
+                }
+            }
+            _ => {}
+        }
+    }
+    if is_block {
+        rendered.push('\n');
+    }
+}
+
+/// The text of the element as a browser renders it, one line per block with the whitespace
+/// collapsed, the same a `WebElement` gives
+pub fn text(element: ElementRef) -> String {
+    let mut rendered: String = String::new();
+    push_text(element, &mut rendered);
+
+    rendered
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<&str>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<String>>()
+        .join("\n")
+}
@@ -1,236 +1,358 @@
 use rand::prelude::ThreadRng;
 use rand::Rng;
-use serde_json::json;
+use scraper::ElementRef;
 use std::time::Duration;
-use thirtyfour::{By, WebDriver, WebElement};
 
 use crate::schemas::idealista_listing_raw::IdealistaListingRaw;
-use crate::scrappers::driver::initialize_driver;
-use crate::scrappers::scrapper_utils::PORTUGUESE_DISTRICTS;
-use crate::utils::file_utils::*;
-use thirtyfour::error::WebDriverError;
-use tokio::fs::File;
-use tokio::time::timeout;
-use tokio_retry::strategy::ExponentialBackoff;
-use tokio_retry::Retry;
-
-async fn get_url_ids(
-    web_driver: &WebDriver,
-    page: u32,
-    district: &str,
-) -> Result<Vec<String>, WebDriverError> {
-    let _ = web_driver
-        .get(format!(
-            "https://www.idealista.pt/comprar-casas/{}-distrito/pagina-{}?ordem=atualizado-desc",
-            district, page
-        ))
-        .await;
-
+use crate::schemas::listing::{PropertyKind, Typology};
+use crate::scrappers::html_page::{attr, text, FieldSelector, HtmlPage};
+use crate::scrappers::page_wait::PageWait;
+use crate::scrappers::scrapper::Scrapper;
+use crate::scrappers::scrapper_error::ScrapperError;
+use crate::scrappers::search_spec::SearchSpec;
+
+pub struct IdealistaScrapper;
+
+const URL_IDS_LINK: FieldSelector = FieldSelector::new("url_ids", "a[href^='/imovel']");
+const DESCRIPTION: FieldSelector = FieldSelector::new("description", ".comment");
+const DETAILS: FieldSelector = FieldSelector::new("details_split_by_string", ".details-property");
+const PRICE: FieldSelector = FieldSelector::new("price", ".info-data-price");
+const LOCATION: FieldSelector = FieldSelector::new("location", ".main-info__title-minor");
+const SELECTED_PAGE: FieldSelector = FieldSelector::new("pagination", "li.selected span");
+
+fn random_wait() -> Duration {
     let mut rng: ThreadRng = rand::thread_rng();
     let waiting_time: u64 = rng.gen_range(5000..=15000);
 
-    tokio::time::sleep(Duration::from_millis(waiting_time)).await;
+    Duration::from_millis(waiting_time)
+}
 
-    let mut ids: Vec<String> = Vec::new();
+/// Idealista searches apartments and houses together, filtering them apart with
+/// [`idealista_filters`]
+fn idealista_kind(property_kind: PropertyKind) -> Option<&'static str> {
+    match property_kind {
+        PropertyKind::Apartment | PropertyKind::House => Some("casas"),
+        PropertyKind::Land => Some("terrenos"),
+        PropertyKind::Shop => Some("lojas"),
+        PropertyKind::Office => Some("escritorios"),
+        PropertyKind::Warehouse => Some("armazens"),
+        PropertyKind::Garage => Some("garagens"),
+        PropertyKind::Building => Some("predios"),
+        PropertyKind::Farm => None,
+    }
+}
 
-    // The site takes some time to load this information
-    while ids.is_empty() {
-        let parent_divs: Vec<WebElement> =
-            web_driver.find_all(By::Css("a[href^='/imovel']")).await?;
+fn idealista_typology(typology: Typology) -> &'static str {
+    match typology {
+        Typology::T0 => "t0",
+        Typology::T1 => "t1",
+        Typology::T2 => "t2",
+        Typology::T3 => "t3",
+        Typology::T4 | Typology::T5 | Typology::T6Plus => "t4-t5",
+    }
+}
 
-        for div in parent_divs {
-            // Find all divs within it and get IDs
-            ids.push(div.attr("href").await?.unwrap());
+/// The filters go in the path, e.g. `com-preco-max_300000,t2/`
+fn idealista_filters(search: &SearchSpec, segment: &str) -> String {
+    let mut filters: Vec<String> = search
+        .range_filters(["preco-min", "preco-max", "tamanho-min", "tamanho-max"])
+        .into_iter()
+        .map(|(key, value)| format!("{}_{}", key, value))
+        .collect();
+    // Only the searches of homes have typologies and tell apartments from houses
+    if segment.contains("-casas/") {
+        for typology in &search.typologies {
+            let typology: String = idealista_typology(*typology).to_string();
+            if !filters.contains(&typology) {
+                filters.push(typology);
+            }
+        }
+        if !search.has_homes() {
+            if search.property_kinds.contains(&PropertyKind::Apartment) {
+                filters.push("apartamentos".to_string());
+            }
+            if search.property_kinds.contains(&PropertyKind::House) {
+                filters.push("moradias".to_string());
+            }
         }
     }
 
-    println!("{:?}", ids);
-
-    Ok(ids)
+    match filters.is_empty() {
+        true => String::new(),
+        false => format!("com-{}/", filters.join(",")),
+    }
 }
 
-async fn get_listing(
-    web_driver: &WebDriver,
-    url_id: String,
-) -> Result<IdealistaListingRaw, WebDriverError> {
-    let _ = web_driver
-        .get(format!("https://www.idealista.pt/{}", url_id))
-        .await;
+impl Scrapper for IdealistaScrapper {
+    type ListingRaw = IdealistaListingRaw;
 
-    let mut rng: ThreadRng = rand::thread_rng();
-    let waiting_time: u64 = rng.gen_range(5000..=15000);
+    fn portal(&self) -> &'static str {
+        "idealista"
+    }
+
+    // Searched per district, the portal only shows the first pages of a search
+    fn segments(&self, search: &SearchSpec) -> Vec<String> {
+        search
+            .portal_kinds(self.portal(), idealista_kind)
+            .into_iter()
+            .flat_map(|kind| {
+                search.districts().into_iter().map(move |district| {
+                    format!(
+                        "{}-{}/{}-distrito",
+                        search.transaction.slug(),
+                        kind,
+                        district
+                    )
+                })
+            })
+            .collect()
+    }
+
+    fn listing_page_url(&self, search: &SearchSpec, segment: &str, page: u32) -> String {
+        format!(
+            "https://www.idealista.pt/{}/{}pagina-{}?ordem=atualizado-desc",
+            segment,
+            idealista_filters(search, segment),
+            page
+        )
+    }
+
+    fn newest_first(&self) -> bool {
+        true
+    }
+
+    fn listing_url(&self, url_id: &str) -> String {
+        format!("https://www.idealista.pt/{}", url_id)
+    }
+
+    fn selectors(&self) -> Vec<FieldSelector> {
+        vec![
+            URL_IDS_LINK,
+            DESCRIPTION,
+            DETAILS,
+            PRICE,
+            LOCATION,
+            SELECTED_PAGE,
+        ]
+    }
+
+    // The random delay keeps the requests from looking automated
+    fn listing_page_wait(&self) -> PageWait {
+        PageWait::document_ready()
+            .delay(random_wait())
+            .element(URL_IDS_LINK)
+    }
+
+    fn listing_wait(&self) -> PageWait {
+        PageWait::document_ready()
+            .delay(random_wait())
+            .element(PRICE)
+    }
+
+    fn url_ids_retries(&self) -> usize {
+        2
+    }
 
-    tokio::time::sleep(Duration::from_millis(waiting_time)).await;
-
-    let description: Option<String> = match web_driver.find(By::ClassName("comment")).await {
-        Ok(element) => Some(element.text().await?),
-        Err(_) => None,
-    };
-
-    let details_split_by_string: String = web_driver
-        .find(By::ClassName("details-property"))
-        .await?
-        .text()
-        .await?;
-
-    let price: String = web_driver
-        .find(By::ClassName("info-data-price"))
-        .await?
-        .text()
-        .await?;
-
-    let idealista_listing_raw: IdealistaListingRaw = IdealistaListingRaw {
-        price,
-        description,
-        details_split_by_string,
-        url_id,
-    };
-
-    Ok(idealista_listing_raw)
+    fn listing_retries(&self) -> usize {
+        3
+    }
+
+    fn run_retries(&self) -> usize {
+        20
+    }
+
+    fn get_url_ids(&self, html_page: &HtmlPage) -> Result<Vec<String>, ScrapperError> {
+        let parent_divs: Vec<ElementRef> = html_page.find_all(URL_IDS_LINK)?;
+
+        Ok(parent_divs
+            .into_iter()
+            .filter_map(|div| attr(div, "href"))
+            .collect())
+    }
+
+    fn get_listing(
+        &self,
+        html_page: &HtmlPage,
+        url_id: String,
+    ) -> Result<IdealistaListingRaw, ScrapperError> {
+        let description: Option<String> = html_page.find(DESCRIPTION).ok().map(text);
+
+        let details_split_by_string: String = text(html_page.find(DETAILS)?);
+
+        let price: String = text(html_page.find(PRICE)?);
+
+        let location: Option<String> = html_page.find(LOCATION).ok().map(text);
+
+        let idealista_listing_raw: IdealistaListingRaw = IdealistaListingRaw {
+            price,
+            description,
+            details_split_by_string,
+            url_id,
+            location,
+            transaction: None,
+        };
+
+        Ok(idealista_listing_raw)
+    }
+
+    fn is_last_page(
+        &self,
+        html_page: &HtmlPage,
+        _segment: &str,
+        page: u32,
+        _url_ids: &[String],
+        _previous_url_ids: &[String],
+    ) -> Result<bool, ScrapperError> {
+        let selected_page: String = text(html_page.find(SELECTED_PAGE)?);
+
+        println!("\nSelected page {}", selected_page);
+
+        Ok(selected_page != page.to_string())
+    }
 }
 
-pub async fn idealista_scrape_mechanism() -> Result<(), WebDriverError> {
-    let web_driver: WebDriver = initialize_driver().await?;
-    let idealista_ids_read: File = get_file_read("idealista_ids.txt").await?;
-    let mut idealista_ids_write: File = get_file_write_append("idealista_ids.txt").await?;
-    let mut idealista_write: File = get_file_write_append("idealista.json").await?;
-    let idealista_cache_read: File = get_file_read("idealista_cache.txt").await?;
-    let idealista_cache: Vec<String> = get_content_lines(idealista_cache_read).await?;
-    let idealista_ids: String = get_content_as_string(idealista_ids_read).await?;
-
-    let cached_district: &str = match idealista_cache.first() {
-        None => PORTUGUESE_DISTRICTS[0],
-        Some(value) => value,
-    };
-
-    async fn cached_page() -> u32 {
-        let idealista_cache_read: File = get_file_read("idealista_cache.txt").await.unwrap();
-
-        match get_content_lines(idealista_cache_read)
-            .await
-            .unwrap()
-            .last()
-        {
-            None => 1,
-            Some(value) => value.parse::<u32>().unwrap(),
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schemas::listing::{Listing, Normalize, Transaction};
+    use crate::scrappers::fixture_server::FixtureServer;
+    use crate::scrappers::html_page::FetchedPage;
+
+    #[tokio::test]
+    async fn extracts_url_ids_from_search_page() {
+        let server: FixtureServer = FixtureServer::start("idealista").await;
+        let fetched: FetchedPage = server.fetch("search.html").await;
+        let html_page: HtmlPage = HtmlPage::parse(&fetched);
+
+        assert_eq!(
+            IdealistaScrapper.get_url_ids(&html_page).unwrap(),
+            vec!["/imovel/33512345/", "/imovel/33498761/"]
+        );
+        assert!(!IdealistaScrapper
+            .is_last_page(&html_page, "lisboa", 2, &[], &[])
+            .unwrap());
+        // The portal shows its last page when asked for a page past it
+        assert!(IdealistaScrapper
+            .is_last_page(&html_page, "lisboa", 3, &[], &[])
+            .unwrap());
     }
 
-    for district in PORTUGUESE_DISTRICTS
-        .iter()
-        .skip_while(|&item| *item != cached_district)
-    {
-        for page in cached_page().await.. {
-            println!("\nScrapper mechanism page {}", page);
-            let mut idealista_cache_write_truncate: File =
-                get_file_write_truncate("idealista_cache.txt").await?;
-
-            write_to_file(
-                &mut idealista_cache_write_truncate,
-                format!("{}\n{}", district, page),
-            )
-            .await?;
-
-            // If we cannot get the page loaded in 30 seconds we ignore it and move on
-            let url_ids_vec: Result<Vec<String>, WebDriverError> = timeout(
-                Duration::from_secs(30),
-                Retry::spawn(
-                    ExponentialBackoff::from_millis(500)
-                        .max_delay(Duration::from_secs(30))
-                        .take(2),
-                    || async { get_url_ids(&web_driver, page, district).await },
+    #[tokio::test]
+    async fn extracts_listings() {
+        let server: FixtureServer = FixtureServer::start("idealista").await;
+        let first: FetchedPage = server.fetch("listing_1.html").await;
+        let second: FetchedPage = server.fetch("listing_2.html").await;
+
+        assert_eq!(
+            IdealistaScrapper
+                .get_listing(&HtmlPage::parse(&first), "imovel/33512345/".to_string())
+                .unwrap(),
+            IdealistaListingRaw {
+                price: "690.000 €".to_string(),
+                description: Some(
+                    "Apartamento T2 com vista para a avenida. Prédio com porteiro.".to_string()
                 ),
-            )
-            .await
-            .unwrap_or_else(|_| {
-                println!("Scrapper mechanism url_ids timed out after 30 seconds!");
-                Ok(Vec::new())
-            });
-
-            let selected_page: String = web_driver
-                .find(By::Css("li.selected span"))
-                .await?
-                .text()
-                .await?;
-
-            println!("\nSelected page {}", selected_page);
-
-            if selected_page == page.to_string() {
-                match url_ids_vec {
-                    Ok(url_ids) => {
-                        for url_id in url_ids {
-                            if !idealista_ids.contains(&url_id) {
-                                println!("Url id: {}", url_id);
-                                let idealista_listing: IdealistaListingRaw = Retry::spawn(
-                                    ExponentialBackoff::from_millis(500)
-                                        .max_delay(Duration::from_secs(30))
-                                        .take(3),
-                                    || async { get_listing(&web_driver, url_id.clone()).await },
-                                )
-                                .await?;
-
-                                write_to_file(
-                                    &mut idealista_write,
-                                    format!("{}\n", json!(idealista_listing)),
-                                )
-                                .await?;
-
-                                write_to_file(
-                                    &mut idealista_ids_write,
-                                    format!("{}\n", idealista_listing.url_id),
-                                )
-                                .await?;
-
-                                tokio::time::sleep(Duration::from_millis(500)).await;
-                            } else {
-                                println!("Url id: {} is already scrapped", url_id);
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        println!("Error getting url_ids: {}", e);
-                        break;
-                    }
-                }
-            } else {
-                println!("No more pages after {} for district {}", page, district);
-                let mut idealista_cache_write_truncate: File =
-                    get_file_write_truncate("idealista_cache.txt").await?;
-
-                write_to_file(
-                    &mut idealista_cache_write_truncate,
-                    format!("{}\n{}", district, 1),
-                )
-                .await?;
-                break;
+                details_split_by_string:
+                    "Características específicas\n98 m² área bruta\nT2\n2 casas de banho"
+                        .to_string(),
+                url_id: "imovel/33512345/".to_string(),
+                location: Some("Santo António, Lisboa".to_string()),
+                transaction: None,
             }
-        }
+        );
+        assert_eq!(
+            IdealistaScrapper
+                .get_listing(&HtmlPage::parse(&second), "imovel/33498761/".to_string())
+                .unwrap(),
+            IdealistaListingRaw {
+                price: "2.100.000 €".to_string(),
+                description: None,
+                details_split_by_string: "450 m² área bruta\nT5".to_string(),
+                url_id: "imovel/33498761/".to_string(),
+                location: None,
+                transaction: None,
+            }
+        );
     }
 
-    Ok(())
-}
+    #[tokio::test]
+    async fn normalizes_rentals() {
+        let server: FixtureServer = FixtureServer::start("idealista").await;
+        let fetched: FetchedPage = server.fetch("rental_listing.html").await;
 
-pub async fn run() {
-    match Retry::spawn(
-        ExponentialBackoff::from_millis(500)
-            .max_delay(Duration::from_secs(30))
-            .take(20),
-        || async {
-            println!("Retrying scrapper mechanism");
-            idealista_scrape_mechanism().await
-        },
-    )
-    .await
-    {
-        Ok(_) => {
-            println!("Idealista scrapper mechanism finished");
-            println!("Clearing cache");
-            get_file_write_truncate("idealista_cache.txt")
-                .await
-                .unwrap();
-        }
-        Err(e) => {
-            eprintln!("Error: {:?}", e);
-        }
+        let listing: Listing = IdealistaScrapper
+            .get_listing(&HtmlPage::parse(&fetched), "imovel/33620044/".to_string())
+            .unwrap()
+            .normalize();
+
+        assert_eq!(listing.transaction, Transaction::Rent);
+        assert_eq!(listing.price_cents, Some(115_000));
+        assert_eq!(listing.typology, Some(Typology::T1));
+        assert_eq!(listing.furnished, Some(true));
+        assert_eq!(listing.min_contract_months, Some(12));
+    }
+
+    #[tokio::test]
+    async fn normalizes_listings() {
+        let server: FixtureServer = FixtureServer::start("idealista").await;
+        let fetched: FetchedPage = server.fetch("listing_1.html").await;
+        let big_house: IdealistaListingRaw = IdealistaListingRaw {
+            price: "480.000 €".to_string(),
+            description: Some("Moradia T6 em Vila Real com quintal.".to_string()),
+            details_split_by_string: "300 m² área bruta".to_string(),
+            url_id: "imovel/33700001/".to_string(),
+            location: None,
+            transaction: None,
+        };
+
+        let listing: Listing = IdealistaScrapper
+            .get_listing(&HtmlPage::parse(&fetched), "imovel/33512345/".to_string())
+            .unwrap()
+            .normalize();
+        let big_listing: Listing = big_house.normalize();
+
+        assert_eq!(listing.transaction, Transaction::Buy);
+        assert_eq!(listing.price_cents, Some(69_000_000));
+        assert_eq!(listing.no_bedrooms, Some(2));
+        assert_eq!(listing.no_bathrooms, Some(2));
+        assert_eq!(listing.freguesia, Some("Santo António".to_string()));
+        assert_eq!(listing.concelho, Some("Lisboa".to_string()));
+        assert_eq!(big_listing.typology, Some(Typology::T6Plus));
+        assert_eq!(big_listing.no_bedrooms, None);
+        assert_eq!(big_listing.district, Some("vila-real".to_string()));
+    }
+
+    #[test]
+    fn builds_search_urls() {
+        let default: SearchSpec = SearchSpec::default();
+        let apartments_to_rent: SearchSpec = SearchSpec {
+            transaction: Transaction::Rent,
+            property_kinds: vec![PropertyKind::Apartment, PropertyKind::Land],
+            max_price: Some(1200),
+            typologies: vec![Typology::T2],
+            districts: vec!["faro".to_string()],
+            ..SearchSpec::default()
+        };
+
+        assert_eq!(IdealistaScrapper.segments(&default).len(), 18);
+        assert_eq!(
+            IdealistaScrapper.listing_page_url(&default, "comprar-casas/aveiro-distrito", 2),
+            "https://www.idealista.pt/comprar-casas/aveiro-distrito/pagina-2?ordem=atualizado-desc"
+        );
+        assert_eq!(
+            IdealistaScrapper.segments(&apartments_to_rent),
+            vec![
+                "arrendar-casas/faro-distrito",
+                "arrendar-terrenos/faro-distrito"
+            ]
+        );
+        assert_eq!(
+            IdealistaScrapper.listing_page_url(&apartments_to_rent, "arrendar-casas/faro-distrito", 1),
+            "https://www.idealista.pt/arrendar-casas/faro-distrito/com-preco-max_1200,t2,apartamentos/pagina-1?ordem=atualizado-desc"
+        );
+        assert_eq!(
+            IdealistaScrapper.listing_page_url(&apartments_to_rent, "arrendar-terrenos/faro-distrito", 1),
+            "https://www.idealista.pt/arrendar-terrenos/faro-distrito/com-preco-max_1200/pagina-1?ordem=atualizado-desc"
+        );
     }
 }
@@ -1,156 +1,384 @@
-use serde_json::json;
-use std::time::Duration;
-use thirtyfour::{By, WebDriver, WebElement};
+use scraper::ElementRef;
+use serde_json::Value;
+use url::form_urlencoded::byte_serialize;
 
 use crate::schemas::imovirtual_listing_raw::ImovirtualListingRaw;
-use crate::scrappers::driver::initialize_driver;
-use crate::utils::file_utils::*;
-use thirtyfour::error::WebDriverError;
-use tokio::fs::File;
-use tokio::time::timeout;
-
-async fn get_url_ids(web_driver: &WebDriver, page: u32) -> Result<Vec<String>, WebDriverError> {
-    let _ = web_driver.get(format!("https://www.imovirtual.com/pt/resultados/comprar/apartamento/todo-o-pais?viewType=listing&by=LATEST&direction=DESC&page={}",page)).await;
-    tokio::time::sleep(Duration::from_millis(2500)).await;
-
-    let mut ids: Vec<String> = Vec::new();
-
-    // The site takes some time to load this information
-    while ids.is_empty() {
-        let parent_divs = web_driver
-            .find_all(By::Css("[data-cy='listing-item-link']"))
-            .await?;
-
-        for div in parent_divs {
-            ids.push(div.attr("href").await?.unwrap());
-        }
-    }
+use crate::schemas::listing::{PropertyKind, Typology};
+use crate::scrappers::html_page::{attr, text, FieldSelector, HtmlPage};
+use crate::scrappers::page_fetcher::FetchMode;
+use crate::scrappers::page_wait::PageWait;
+use crate::scrappers::scrapper::Scrapper;
+use crate::scrappers::scrapper_error::ScrapperError;
+use crate::scrappers::search_spec::SearchSpec;
 
-    println!("{:?}", ids);
+pub struct ImovirtualScrapper;
 
-    Ok(ids)
-}
+const URL_IDS_LINK: FieldSelector = FieldSelector::new("url_ids", "[data-cy='listing-item-link']");
+const URL_IDS_NEXT_DATA: FieldSelector = FieldSelector::new("url_ids", "script#__NEXT_DATA__");
+const DESCRIPTION: FieldSelector =
+    FieldSelector::new("description", "[data-cy='adPageAdDescription']");
+const DETAILS_ITEM: FieldSelector = FieldSelector::new("details_split_by_string", ".e15n0fyo2");
+const PRICE: FieldSelector = FieldSelector::new("price", "[data-cy='adPageHeaderPrice']");
+const LOCATION: FieldSelector = FieldSelector::new("location", "a[href='#map']");
+const NO_RESULTS: FieldSelector = FieldSelector::new("pagination", "[data-cy='no-search-results']");
 
-async fn get_listing(
-    web_driver: &WebDriver,
-    url_id: String,
-) -> Result<ImovirtualListingRaw, WebDriverError> {
-    let _ = web_driver
-        .get(format!("https://www.imovirtual.pt/{}", url_id))
-        .await;
-
-    tokio::time::sleep(Duration::from_millis(200)).await;
-
-    let description: String = web_driver
-        .find(By::Css("[data-cy='adPageAdDescription']"))
-        .await?
-        .text()
-        .await?;
-
-    let details_vec: Vec<WebElement> = web_driver.find_all(By::ClassName("e15n0fyo2")).await?;
-
-    let price: Option<String> = match web_driver
-        .find(By::Css("[data-cy='adPageHeaderPrice']"))
-        .await
-    {
-        Ok(element) => Some(element.text().await?),
-        Err(_) => None,
+/// The url ids of the search results in the Json Next.js embeds in the page, for when the links
+/// are not rendered
+fn next_data_url_ids(html_page: &HtmlPage) -> Vec<String> {
+    let next_data: Value = match html_page.embedded_json(URL_IDS_NEXT_DATA) {
+        Some(next_data) => next_data,
+        None => return Vec::new(),
     };
 
-    let mut details_split_by_string: Vec<String> = Vec::new();
+    next_data["props"]["pageProps"]["data"]["searchAds"]["items"]
+        .as_array()
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(|item| item["slug"].as_str())
+                .map(|slug| format!("/pt/anuncio/{}", slug))
+                .collect()
+        })
+        .unwrap_or_default()
+}
 
-    for div in details_vec {
-        details_split_by_string.push(div.text().await?);
+fn imovirtual_kind(property_kind: PropertyKind) -> Option<&'static str> {
+    match property_kind {
+        PropertyKind::Apartment => Some("apartamento"),
+        PropertyKind::House => Some("moradia"),
+        PropertyKind::Land => Some("terreno"),
+        PropertyKind::Shop => Some("loja"),
+        PropertyKind::Office => Some("escritorio"),
+        PropertyKind::Warehouse => Some("armazem"),
+        PropertyKind::Garage => Some("garagem"),
+        PropertyKind::Farm => Some("quinta"),
+        PropertyKind::Building => Some("predio"),
     }
+}
 
-    let imovirtual_listing_raw: ImovirtualListingRaw = ImovirtualListingRaw {
-        price,
-        description,
-        details_split_by_string,
-        url_id,
-    };
+/// Values of the `roomsNumber` parameter for a typology
+fn imovirtual_rooms(typology: Typology) -> Vec<&'static str> {
+    match typology {
+        Typology::T0 => vec!["ZERO"],
+        Typology::T1 => vec!["ONE"],
+        Typology::T2 => vec!["TWO"],
+        Typology::T3 => vec!["THREE"],
+        Typology::T4 => vec!["FOUR"],
+        Typology::T5 => vec!["FIVE"],
+        Typology::T6Plus => vec!["SIX", "SEVEN", "EIGHT", "NINE", "TEN", "MORE"],
+    }
+}
 
-    Ok(imovirtual_listing_raw)
+fn imovirtual_filters(search: &SearchSpec) -> String {
+    let mut filters: String = String::new();
+    for (key, value) in search.range_filters(["priceMin", "priceMax", "areaMin", "areaMax"]) {
+        filters.push_str(&format!("&{}={}", key, value));
+    }
+    if !search.typologies.is_empty() {
+        let rooms: Vec<&str> = search
+            .typologies
+            .iter()
+            .flat_map(|typology| imovirtual_rooms(*typology))
+            .collect();
+        let rooms: String = byte_serialize(format!("[{}]", rooms.join(",")).as_bytes()).collect();
+        filters.push_str(&format!("&roomsNumber={}", rooms));
+    }
+    filters
 }
 
-pub async fn imovirtual_scrape_mechanism() -> Result<(), WebDriverError> {
-    let web_driver: WebDriver = initialize_driver().await?;
-    let imovirtual_ids_read: File = get_file_read("imovirtual_ids.txt").await?;
-    let mut imovirtual_ids_write: File = get_file_write_append("imovirtual_ids.txt").await?;
-    let mut imovirtual_write: File = get_file_write_append("imovirtual.json").await?;
-    let imovirtual_ids: String = get_content_as_string(imovirtual_ids_read).await?;
-
-    for page in 1.. {
-        println!("\nScrapper mechanism page {}", page);
-
-        // If we cannot get the page loaded in 30 seconds we ignore it and move on
-        let url_ids_vec: Result<Vec<String>, WebDriverError> =
-            timeout(Duration::from_secs(30), get_url_ids(&web_driver, page))
-                .await
-                .unwrap_or_else(|_| {
-                    println!("Scrapper mechanism url_ids timed out after 30 seconds!");
-                    Ok(Vec::new())
-                });
-
-        let elements_found: bool = match web_driver
-            .find(By::Css("[data-cy='no-search-results']"))
-            .await
-        {
-            Ok(element) => match element.text().await {
-                Ok(text) => !text.contains("Nenhum resultado encontrado"),
-                Err(_) => true,
-            },
-            Err(_) => true,
+impl Scrapper for ImovirtualScrapper {
+    type ListingRaw = ImovirtualListingRaw;
+
+    fn portal(&self) -> &'static str {
+        "imovirtual"
+    }
+
+    // Every property kind is a separate search, of the whole country unless the search has
+    // districts
+    fn segments(&self, search: &SearchSpec) -> Vec<String> {
+        let locations: Vec<&str> = match search.districts.is_empty() {
+            true => vec!["todo-o-pais"],
+            false => search.districts(),
         };
 
-        if elements_found {
-            match url_ids_vec {
-                Ok(url_ids) => {
-                    for url_id in url_ids {
-                        if !imovirtual_ids.contains(&url_id) {
-                            println!("Url id: {}", url_id);
-                            let imovirtual_listing: ImovirtualListingRaw =
-                                get_listing(&web_driver, url_id).await?;
-
-                            write_to_file(
-                                &mut imovirtual_write,
-                                format!("{}\n", json!(imovirtual_listing)),
-                            )
-                            .await?;
-
-                            write_to_file(
-                                &mut imovirtual_ids_write,
-                                format!("{}\n", imovirtual_listing.url_id),
-                            )
-                            .await?;
-
-                            tokio::time::sleep(Duration::from_millis(500)).await;
-                        } else {
-                            println!("Url id: {} is already scrapped", url_id);
-                        }
-                    }
-                }
-                Err(e) => {
-                    println!("Error getting url_ids: {}", e);
-                    break;
-                }
-            }
-        } else {
-            println!("No more pages after {}", page);
-            break;
+        search
+            .portal_kinds(self.portal(), imovirtual_kind)
+            .into_iter()
+            .flat_map(|kind| {
+                locations.iter().map(move |location| {
+                    format!("{}/{}/{}", search.transaction.slug(), kind, location)
+                })
+            })
+            .collect()
+    }
+
+    fn listing_page_url(&self, search: &SearchSpec, segment: &str, page: u32) -> String {
+        format!(
+            "https://www.imovirtual.com/pt/resultados/{}?viewType=listing{}&by=LATEST&direction=DESC&page={}",
+            segment,
+            imovirtual_filters(search),
+            page
+        )
+    }
+
+    fn newest_first(&self) -> bool {
+        true
+    }
+
+    fn listing_url(&self, url_id: &str) -> String {
+        format!("https://www.imovirtual.pt/{}", url_id)
+    }
+
+    fn selectors(&self) -> Vec<FieldSelector> {
+        vec![
+            URL_IDS_LINK,
+            URL_IDS_NEXT_DATA,
+            DESCRIPTION,
+            DETAILS_ITEM,
+            PRICE,
+            LOCATION,
+            NO_RESULTS,
+        ]
+    }
+
+    // The search results and the listings are rendered by the server
+    fn default_fetch_mode(&self) -> FetchMode {
+        FetchMode::Http
+    }
+
+    fn listing_page_wait(&self) -> PageWait {
+        PageWait::document_ready().any_element(&[URL_IDS_LINK, URL_IDS_NEXT_DATA, NO_RESULTS])
+    }
+
+    fn listing_wait(&self) -> PageWait {
+        PageWait::document_ready().element(DESCRIPTION)
+    }
+
+    fn listing_gone_markers(&self) -> Vec<&'static str> {
+        vec!["Nenhum resultado encontrado"]
+    }
+
+    fn get_url_ids(&self, html_page: &HtmlPage) -> Result<Vec<String>, ScrapperError> {
+        let parent_divs: Vec<ElementRef> = html_page.find_all(URL_IDS_LINK)?;
+        let ids: Vec<String> = parent_divs
+            .into_iter()
+            .filter_map(|div| attr(div, "href"))
+            .collect();
+
+        if ids.is_empty() {
+            return Ok(next_data_url_ids(html_page));
         }
+
+        Ok(ids)
     }
 
-    Ok(())
+    fn get_listing(
+        &self,
+        html_page: &HtmlPage,
+        url_id: String,
+    ) -> Result<ImovirtualListingRaw, ScrapperError> {
+        let description: String = text(html_page.find(DESCRIPTION)?);
+
+        let details_vec: Vec<ElementRef> = html_page.find_all(DETAILS_ITEM)?;
+
+        let price: Option<String> = html_page.find(PRICE).ok().map(text);
+
+        let details_split_by_string: Vec<String> = details_vec.into_iter().map(text).collect();
+
+        let location: Option<String> = html_page.find(LOCATION).ok().map(text);
+
+        let imovirtual_listing_raw: ImovirtualListingRaw = ImovirtualListingRaw {
+            price,
+            description,
+            details_split_by_string,
+            url_id,
+            location,
+            transaction: None,
+        };
+
+        Ok(imovirtual_listing_raw)
+    }
+
+    fn is_last_page(
+        &self,
+        html_page: &HtmlPage,
+        _segment: &str,
+        _page: u32,
+        _url_ids: &[String],
+        _previous_url_ids: &[String],
+    ) -> Result<bool, ScrapperError> {
+        let elements_found: bool = match html_page.find(NO_RESULTS) {
+            Ok(element) => !text(element).contains("Nenhum resultado encontrado"),
+            Err(_) => true,
+        };
+
+        Ok(!elements_found)
+    }
 }
 
-pub async fn run() {
-    match imovirtual_scrape_mechanism().await {
-        Ok(_) => {
-            println!("imovirtual scrapper mechanism finished")
-        }
-        Err(e) => {
-            eprintln!("Error: {:?}", e);
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schemas::listing::{Listing, Normalize};
+    use crate::scrappers::fixture_server::FixtureServer;
+    use crate::scrappers::html_page::FetchedPage;
+
+    #[tokio::test]
+    async fn extracts_url_ids_from_search_page() {
+        let server: FixtureServer = FixtureServer::start("imovirtual").await;
+        let fetched: FetchedPage = server.fetch("search.html").await;
+        let html_page: HtmlPage = HtmlPage::parse(&fetched);
+
+        let url_ids: Vec<String> = ImovirtualScrapper.get_url_ids(&html_page).unwrap();
+
+        assert_eq!(
+            url_ids,
+            vec![
+                "/pt/anuncio/apartamento-t2-em-almada-ID1fXyZ",
+                "/pt/anuncio/apartamento-t1-em-setubal-ID1fAbC",
+            ]
+        );
+        assert!(!ImovirtualScrapper
+            .is_last_page(&html_page, "", 1, &url_ids, &[])
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn extracts_url_ids_from_next_data() {
+        let server: FixtureServer = FixtureServer::start("imovirtual").await;
+        let fetched: FetchedPage = server.fetch("next_data_search.html").await;
+
+        assert_eq!(
+            ImovirtualScrapper
+                .get_url_ids(&HtmlPage::parse(&fetched))
+                .unwrap(),
+            vec![
+                "/pt/anuncio/apartamento-t3-em-faro-ID1fDeF",
+                "/pt/anuncio/apartamento-t0-em-coimbra-ID1fGhI",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn detects_past_last_page() {
+        let server: FixtureServer = FixtureServer::start("imovirtual").await;
+        let fetched: FetchedPage = server.fetch("empty_search.html").await;
+        let html_page: HtmlPage = HtmlPage::parse(&fetched);
+
+        assert!(ImovirtualScrapper
+            .get_url_ids(&html_page)
+            .unwrap()
+            .is_empty());
+        assert!(ImovirtualScrapper
+            .is_last_page(&html_page, "", 40, &[], &[])
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn extracts_listings() {
+        let server: FixtureServer = FixtureServer::start("imovirtual").await;
+        let first: FetchedPage = server.fetch("listing_1.html").await;
+        let second: FetchedPage = server.fetch("listing_2.html").await;
+
+        assert_eq!(
+            ImovirtualScrapper
+                .get_listing(&HtmlPage::parse(&first), "ID1fXyZ".to_string())
+                .unwrap(),
+            ImovirtualListingRaw {
+                price: Some("275 000 €".to_string()),
+                description:
+                    "Apartamento T2 com vista rio, a 5 minutos do ferry.\nInclui lugar de garagem."
+                        .to_string(),
+                details_split_by_string: vec![
+                    "Área:\n75 m²".to_string(),
+                    "Tipologia:\nT2".to_string(),
+                    "Andar:\n3º".to_string(),
+                ],
+                url_id: "ID1fXyZ".to_string(),
+                location: Some("Rua Cândido dos Reis, Cacilhas, Almada, Setúbal".to_string()),
+                transaction: None,
+            }
+        );
+        assert_eq!(
+            ImovirtualScrapper
+                .get_listing(&HtmlPage::parse(&second), "ID1fAbC".to_string())
+                .unwrap(),
+            ImovirtualListingRaw {
+                price: None,
+                description: "T1 para investimento, arrendado.".to_string(),
+                details_split_by_string: vec!["Área:\n48 m²".to_string()],
+                url_id: "ID1fAbC".to_string(),
+                location: None,
+                transaction: None,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn normalizes_listings() {
+        let server: FixtureServer = FixtureServer::start("imovirtual").await;
+        let fetched: FetchedPage = server.fetch("listing_1.html").await;
+        let located_apartment: ImovirtualListingRaw = ImovirtualListingRaw {
+            price: Some("1 100 €/mês".to_string()),
+            description: "Apartamento T1 em Castelo Branco.".to_string(),
+            details_split_by_string: vec![
+                "Concelho: Castelo Branco".to_string(),
+                "Freguesia:\nCastelo Branco".to_string(),
+            ],
+            url_id: "ID1fQrS".to_string(),
+            location: None,
+            transaction: None,
+        };
+
+        let listing: Listing = ImovirtualScrapper
+            .get_listing(&HtmlPage::parse(&fetched), "ID1fXyZ".to_string())
+            .unwrap()
+            .normalize();
+        let located_listing: Listing = located_apartment.normalize();
+
+        assert_eq!(listing.price_cents, Some(27_500_000));
+        assert_eq!(listing.area_sqr_meters, Some(75.0));
+        assert_eq!(listing.typology, Some(Typology::T2));
+        assert_eq!(listing.features, vec!["garage"]);
+        assert_eq!(listing.freguesia, Some("Cacilhas".to_string()));
+        assert_eq!(listing.concelho, Some("Almada".to_string()));
+        assert_eq!(located_listing.district, Some("castelo-branco".to_string()));
+        assert_eq!(located_listing.concelho, Some("Castelo Branco".to_string()));
+        assert_eq!(
+            located_listing.freguesia,
+            Some("Castelo Branco".to_string())
+        );
+    }
+
+    #[test]
+    fn builds_search_urls() {
+        let default: SearchSpec = SearchSpec::default();
+        let houses_in_porto: SearchSpec = SearchSpec {
+            property_kinds: vec![PropertyKind::House],
+            min_area: Some(100),
+            typologies: vec![Typology::T3],
+            districts: vec!["porto".to_string()],
+            ..SearchSpec::default()
+        };
+
+        assert_eq!(
+            ImovirtualScrapper.segments(&default),
+            vec![
+                "comprar/apartamento/todo-o-pais",
+                "comprar/moradia/todo-o-pais"
+            ]
+        );
+        assert_eq!(
+            ImovirtualScrapper.listing_page_url(&default, "comprar/apartamento/todo-o-pais", 2),
+            "https://www.imovirtual.com/pt/resultados/comprar/apartamento/todo-o-pais?viewType=listing&by=LATEST&direction=DESC&page=2"
+        );
+        assert_eq!(
+            ImovirtualScrapper.segments(&houses_in_porto),
+            vec!["comprar/moradia/porto"]
+        );
+        assert_eq!(
+            ImovirtualScrapper.listing_page_url(&houses_in_porto, "comprar/moradia/porto", 1),
+            "https://www.imovirtual.com/pt/resultados/comprar/moradia/porto?viewType=listing&areaMin=100&roomsNumber=%5BTHREE%5D&by=LATEST&direction=DESC&page=1"
+        );
     }
 }
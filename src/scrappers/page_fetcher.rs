@@ -0,0 +1,143 @@
+use crate::scrappers::html_page::{FetchedPage, HtmlPage};
+use crate::scrappers::page_wait::PageWait;
+use crate::scrappers::scrapper_error::ScrapperError;
+use async_trait::async_trait;
+use reqwest::header::{HeaderMap, HeaderValue, ACCEPT_LANGUAGE};
+use std::env;
+use std::error::Error;
+use std::time::Duration;
+use thirtyfour::WebDriver;
+
+// Some portals refuse the requests without the user agent of a browser
+const DEFAULT_USER_AGENT: &str = "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/131.0.0.0 Safari/537.36";
+const HTTP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How the pages of a portal are downloaded
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FetchMode {
+    /// A browser driven through WebDriver, for the pages rendered with JavaScript
+    Browser,
+    /// A plain Http request, for the pages whose data is in the Html the server sends
+    Http,
+}
+
+impl FetchMode {
+    fn parse(fetch_mode: &str) -> Result<FetchMode, Box<dyn Error + Send + Sync>> {
+        match fetch_mode {
+            "browser" => Ok(FetchMode::Browser),
+            "http" => Ok(FetchMode::Http),
+            fetch_mode => {
+                Err(format!("Invalid fetch mode {}, use `browser` or `http`", fetch_mode).into())
+            }
+        }
+    }
+
+    /// The mode `FETCH_MODES` (like `era=http,imovirtual=browser`) sets for the portal
+    pub fn from_env(portal: &str) -> Result<Option<FetchMode>, Box<dyn Error + Send + Sync>> {
+        let fetch_modes: String = match env::var("FETCH_MODES") {
+            Ok(fetch_modes) => fetch_modes,
+            Err(_) => return Ok(None),
+        };
+
+        for portal_mode in fetch_modes.split(',') {
+            match portal_mode.split_once('=') {
+                Some((name, fetch_mode)) if name.trim() == portal => {
+                    return FetchMode::parse(fetch_mode.trim()).map(Some)
+                }
+                Some(_) => {}
+                None => {
+                    return Err(format!(
+                        "`FETCH_MODES` should be like era=http, got {}",
+                        portal_mode
+                    )
+                    .into())
+                }
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+/// Downloads the pages the scrappers extract the listings from
+#[async_trait]
+pub trait PageFetcher: Send + Sync {
+    /// Loads `url` and waits for it to meet the conditions of `wait`, the page is returned even
+    /// when it never does so the caller can tell why
+    async fn fetch(&self, url: &str, wait: &PageWait) -> Result<FetchedPage, ScrapperError>;
+}
+
+pub struct WebDriverFetcher<'a> {
+    web_driver: &'a WebDriver,
+}
+
+impl WebDriverFetcher<'_> {
+    pub fn new(web_driver: &WebDriver) -> WebDriverFetcher<'_> {
+        WebDriverFetcher { web_driver }
+    }
+}
+
+#[async_trait]
+impl PageFetcher for WebDriverFetcher<'_> {
+    async fn fetch(&self, url: &str, wait: &PageWait) -> Result<FetchedPage, ScrapperError> {
+        // Pages that take too long to load are read as they are
+        let _ = self.web_driver.get(url).await;
+        let never_loaded: Option<String> = wait.wait_in_browser(self.web_driver).await;
+
+        Ok(FetchedPage {
+            url: self.web_driver.current_url().await?.to_string(),
+            status: None,
+            source: self.web_driver.source().await?,
+            never_loaded,
+        })
+    }
+}
+
+/// Fetches the Html the server sends without running its JavaScript, far cheaper than a browser
+pub struct HttpFetcher {
+    client: reqwest::Client,
+}
+
+impl HttpFetcher {
+    pub fn new(user_agent: &str, language: &str) -> Result<HttpFetcher, reqwest::Error> {
+        let mut headers: HeaderMap = HeaderMap::new();
+        if let Ok(language) = HeaderValue::from_str(language) {
+            headers.insert(ACCEPT_LANGUAGE, language);
+        }
+
+        Ok(HttpFetcher {
+            client: reqwest::Client::builder()
+                .user_agent(user_agent)
+                .default_headers(headers)
+                .timeout(HTTP_TIMEOUT)
+                .build()?,
+        })
+    }
+
+    /// Uses the `USER_AGENT` and `BROWSER_LANGUAGE` of the browser sessions
+    pub fn from_env() -> Result<HttpFetcher, reqwest::Error> {
+        HttpFetcher::new(
+            &env::var("USER_AGENT").unwrap_or(DEFAULT_USER_AGENT.to_string()),
+            &env::var("BROWSER_LANGUAGE").unwrap_or("pt-PT".to_string()),
+        )
+    }
+}
+
+#[async_trait]
+impl PageFetcher for HttpFetcher {
+    async fn fetch(&self, url: &str, wait: &PageWait) -> Result<FetchedPage, ScrapperError> {
+        tokio::time::sleep(wait.delay).await;
+        let response: reqwest::Response = self.client.get(url).send().await?;
+
+        let mut fetched_page: FetchedPage = FetchedPage {
+            url: response.url().to_string(),
+            status: Some(response.status().as_u16()),
+            source: response.text().await?,
+            never_loaded: None,
+        };
+        // What the server sent is all there will be, waiting longer would not change it
+        fetched_page.never_loaded = wait.unmet_by_html(&HtmlPage::parse(&fetched_page));
+
+        Ok(fetched_page)
+    }
+}
@@ -0,0 +1,290 @@
+use crate::scrappers::html_page::{FieldSelector, HtmlPage};
+use serde_json::{json, Value};
+use std::fmt;
+use std::time::{Duration, Instant};
+use thirtyfour::WebDriver;
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+// Everything the conditions look at, read in a single round trip to the browser
+const PAGE_STATE_SCRIPT: &str = "
+    return {
+        ready: document.readyState === 'complete',
+        counts: arguments[0].map(css => {
+            try { return document.querySelectorAll(css).length; } catch (e) { return 0; }
+        }),
+        resources: performance.getEntriesByType('resource').length,
+    };
+";
+
+/// Something a page has to show before it is extracted
+#[derive(Debug, Clone, PartialEq)]
+pub enum WaitCondition {
+    /// The document and its resources finished loading
+    DocumentReady,
+    /// At least that many elements match the selector
+    Elements(FieldSelector, usize),
+    /// An element matches any of the selectors, like the results or the no results message
+    AnyElement(Vec<FieldSelector>),
+    /// No resource started loading for that long
+    NetworkIdle(Duration),
+}
+
+impl fmt::Display for WaitCondition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WaitCondition::DocumentReady => write!(f, "document ready"),
+            WaitCondition::Elements(selector, 1) => {
+                write!(f, "{} `{}`", selector.field, selector.css)
+            }
+            WaitCondition::Elements(selector, count) => {
+                write!(f, "{} {} `{}`", count, selector.field, selector.css)
+            }
+            WaitCondition::AnyElement(selectors) => {
+                let selectors: Vec<String> = selectors
+                    .iter()
+                    .map(|selector| format!("{} `{}`", selector.field, selector.css))
+                    .collect();
+                write!(f, "any of {}", selectors.join(", "))
+            }
+            WaitCondition::NetworkIdle(quiet) => {
+                write!(f, "network idle for {}ms", quiet.as_millis())
+            }
+        }
+    }
+}
+
+/// What the browser reported about the page on a poll
+struct PageState {
+    ready: bool,
+    /// Elements matching each of the selectors of the conditions, in order
+    counts: Vec<usize>,
+    resources: u64,
+}
+
+/// When a page is ready to be extracted: the conditions it has to meet, checked every
+/// `poll_interval` until they are met or `timeout` passes
+#[derive(Debug, Clone)]
+pub struct PageWait {
+    /// Time given to the page before looking at it at all, also spaces out the requests
+    pub delay: Duration,
+    pub conditions: Vec<WaitCondition>,
+    pub timeout: Duration,
+    pub poll_interval: Duration,
+}
+
+impl PageWait {
+    /// Waits for the document to finish loading, the base of every other wait
+    pub fn document_ready() -> PageWait {
+        PageWait {
+            delay: Duration::ZERO,
+            conditions: vec![WaitCondition::DocumentReady],
+            timeout: DEFAULT_TIMEOUT,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+        }
+    }
+
+    pub fn element(self, selector: FieldSelector) -> PageWait {
+        self.elements(selector, 1)
+    }
+
+    pub fn elements(mut self, selector: FieldSelector, count: usize) -> PageWait {
+        self.conditions
+            .push(WaitCondition::Elements(selector, count));
+        self
+    }
+
+    pub fn any_element(mut self, selectors: &[FieldSelector]) -> PageWait {
+        self.conditions
+            .push(WaitCondition::AnyElement(selectors.to_vec()));
+        self
+    }
+
+    pub fn network_idle(mut self, quiet: Duration) -> PageWait {
+        self.conditions.push(WaitCondition::NetworkIdle(quiet));
+        self
+    }
+
+    pub fn delay(mut self, delay: Duration) -> PageWait {
+        self.delay = delay;
+        self
+    }
+
+    fn selectors(&self) -> Vec<FieldSelector> {
+        self.conditions
+            .iter()
+            .flat_map(|condition| match condition {
+                WaitCondition::Elements(selector, _) => vec![*selector],
+                WaitCondition::AnyElement(selectors) => selectors.clone(),
+                _ => Vec::new(),
+            })
+            .collect()
+    }
+
+    /// The conditions not met, `idle_for` being how long the number of resources did not change
+    fn unmet(&self, state: &PageState, idle_for: Duration) -> Vec<&WaitCondition> {
+        let mut counts = state.counts.iter();
+
+        self.conditions
+            .iter()
+            .filter(|condition| match condition {
+                WaitCondition::DocumentReady => !state.ready,
+                WaitCondition::Elements(_, count) => {
+                    counts.next().copied().unwrap_or_default() < *count
+                }
+                WaitCondition::AnyElement(selectors) => {
+                    // Every count is read, the next conditions come after them
+                    let matched: usize = selectors
+                        .iter()
+                        .map(|_| counts.next().copied().unwrap_or_default())
+                        .filter(|count| *count > 0)
+                        .count();
+                    matched == 0
+                }
+                WaitCondition::NetworkIdle(quiet) => idle_for < *quiet,
+            })
+            .collect()
+    }
+
+    fn describe(unmet: &[&WaitCondition]) -> String {
+        unmet
+            .iter()
+            .map(|condition| condition.to_string())
+            .collect::<Vec<String>>()
+            .join(" and ")
+    }
+
+    /// The conditions the Html does not meet, for pages that cannot change once downloaded
+    pub fn unmet_by_html(&self, html_page: &HtmlPage) -> Option<String> {
+        let state: PageState = PageState {
+            ready: true,
+            counts: self
+                .selectors()
+                .into_iter()
+                .map(|selector| {
+                    html_page
+                        .find_all(selector)
+                        .map(|elements| elements.len())
+                        .unwrap_or_default()
+                })
+                .collect(),
+            resources: 0,
+        };
+
+        let unmet: Vec<&WaitCondition> = self.unmet(&state, Duration::MAX);
+        match unmet.is_empty() {
+            true => None,
+            false => Some(PageWait::describe(&unmet)),
+        }
+    }
+
+    async fn browser_state(&self, web_driver: &WebDriver) -> Option<PageState> {
+        let selectors: Vec<&str> = self
+            .selectors()
+            .into_iter()
+            .map(|selector| selector.css)
+            .collect();
+        let state: Value = web_driver
+            .execute(PAGE_STATE_SCRIPT, vec![json!(selectors)])
+            .await
+            .ok()?
+            .json()
+            .clone();
+
+        Some(PageState {
+            ready: state["ready"].as_bool().unwrap_or_default(),
+            counts: state["counts"]
+                .as_array()
+                .map(|counts| {
+                    counts
+                        .iter()
+                        .map(|count| count.as_u64().unwrap_or_default() as usize)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            resources: state["resources"].as_u64().unwrap_or_default(),
+        })
+    }
+
+    /// Polls the page the browser shows until it meets the conditions, returns the ones still
+    /// unmet when the wait timed out
+    pub async fn wait_in_browser(&self, web_driver: &WebDriver) -> Option<String> {
+        tokio::time::sleep(self.delay).await;
+
+        let started_at: Instant = Instant::now();
+        let mut resources: Option<u64> = None;
+        let mut idle_since: Instant = started_at;
+
+        loop {
+            let unmet: Vec<&WaitCondition> = match self.browser_state(web_driver).await {
+                Some(state) => {
+                    if resources != Some(state.resources) {
+                        resources = Some(state.resources);
+                        idle_since = Instant::now();
+                    }
+                    self.unmet(&state, idle_since.elapsed())
+                }
+                // The page can be between two documents, it is read again on the next poll
+                None => self.conditions.iter().collect(),
+            };
+
+            if unmet.is_empty() {
+                return None;
+            }
+            if started_at.elapsed() >= self.timeout {
+                return Some(PageWait::describe(&unmet));
+            }
+
+            tokio::time::sleep(self.poll_interval).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scrappers::fixture_server::FixtureServer;
+    use crate::scrappers::html_page::FetchedPage;
+
+    const URL_IDS_LIST: FieldSelector = FieldSelector::new("url_ids", ".pl-0");
+    const PRICE: FieldSelector = FieldSelector::new("price", "main h2 b");
+    const NO_RESULTS: FieldSelector = FieldSelector::new("no_results", ".no-results");
+
+    #[tokio::test]
+    async fn page_showing_the_elements_loaded() {
+        let server: FixtureServer = FixtureServer::start("remax").await;
+        let wait: PageWait = PageWait::document_ready().element(URL_IDS_LIST);
+
+        let fetched: FetchedPage = server.fetch_waiting("search.html", &wait).await;
+
+        assert_eq!(fetched.never_loaded, None);
+    }
+
+    #[tokio::test]
+    async fn page_missing_the_elements_never_loaded() {
+        let server: FixtureServer = FixtureServer::start("remax").await;
+        let wait: PageWait = PageWait::document_ready()
+            .element(URL_IDS_LIST)
+            .element(PRICE);
+
+        let fetched: FetchedPage = server.fetch_waiting("listing_1.html", &wait).await;
+
+        assert_eq!(fetched.never_loaded, Some("url_ids `.pl-0`".to_string()));
+    }
+
+    #[tokio::test]
+    async fn any_element_is_enough() {
+        let server: FixtureServer = FixtureServer::start("remax").await;
+        let wait: PageWait = PageWait::document_ready()
+            .any_element(&[NO_RESULTS, URL_IDS_LIST])
+            .elements(PRICE, 2);
+
+        let fetched: FetchedPage = server.fetch_waiting("search.html", &wait).await;
+
+        assert_eq!(
+            fetched.never_loaded,
+            Some("2 price `main h2 b`".to_string())
+        );
+    }
+}
@@ -1,133 +1,326 @@
-use serde_json::json;
-use std::time::Duration;
-use thirtyfour::{By, WebDriver, WebElement};
+use scraper::ElementRef;
+use serde_json::{json, Map, Value};
+use url::form_urlencoded::byte_serialize;
 
+use crate::schemas::listing::PropertyKind;
 use crate::schemas::remax_listing_raw::RemaxListingRaw;
-use crate::scrappers::driver::initialize_driver;
-use crate::utils::file_utils::*;
-use thirtyfour::error::WebDriverError;
-use tokio::fs::File;
-use tokio::time::timeout;
+use crate::scrappers::html_page::{attr, find_all_in, text, FieldSelector, HtmlPage};
+use crate::scrappers::page_wait::PageWait;
+use crate::scrappers::scrapper::Scrapper;
+use crate::scrappers::scrapper_error::ScrapperError;
+use crate::scrappers::search_spec::SearchSpec;
 
-async fn get_url_ids(web_driver: &WebDriver, page: u32) -> Result<Vec<String>, WebDriverError> {
-    let _ = web_driver.get(format!("https://www.remax.pt/pt/comprar/imoveis/habitacao/r/r/r/t?s=%7B%7D&p={}&o=-ContractDate",page)).await;
-    tokio::time::sleep(Duration::from_millis(2500)).await;
+pub struct RemaxScrapper;
 
-    let mut ids: Vec<String> = Vec::new();
+const URL_IDS_LIST: FieldSelector = FieldSelector::new("url_ids", ".pl-0");
+const URL_IDS_LINK: FieldSelector = FieldSelector::new("url_ids", "a");
+const DESCRIPTION: FieldSelector = FieldSelector::new("description", "#description");
+const DETAILS: FieldSelector = FieldSelector::new("details_split_by_string", "#details");
+const DETAILS_ITEM: FieldSelector = FieldSelector::new("details_split_by_string", ".flex");
+const PRICE: FieldSelector = FieldSelector::new("price", "main h2 b");
+const LOCATION: FieldSelector = FieldSelector::new("location", ".listing-address");
 
-    // The site takes some time to load this information
-    while ids.is_empty() {
-        let parent_div = web_driver.find(By::ClassName("pl-0")).await?;
+/// Remax name of a property kind, apartments and houses searched together are homes
+fn remax_kind(property_kind: PropertyKind, homes: bool) -> Option<&'static str> {
+    match property_kind {
+        PropertyKind::Apartment | PropertyKind::House if homes => Some("habitacao"),
+        PropertyKind::Apartment => Some("apartamento"),
+        PropertyKind::House => Some("moradia"),
+        PropertyKind::Land => Some("terreno"),
+        PropertyKind::Shop => Some("loja"),
+        PropertyKind::Office => Some("escritorio"),
+        PropertyKind::Warehouse => Some("armazem"),
+        PropertyKind::Garage => Some("garagem"),
+        PropertyKind::Farm => Some("quinta"),
+        PropertyKind::Building => Some("predio"),
+    }
+}
+
+/// The filters go in the `s` parameter as Json
+fn remax_filters(search: &SearchSpec) -> String {
+    let mut filters: Map<String, Value> = Map::new();
+    for (key, value) in search.range_filters(["pmin", "pmax", "amin", "amax"]) {
+        filters.insert(key.to_string(), json!(value));
+    }
+    if !search.typologies.is_empty() {
+        filters.insert("t".to_string(), json!(search.bedrooms()));
+    }
 
-        // Find all divs within it and get IDs
-        let child_divs: Vec<WebElement> = parent_div.find_all(By::Tag("a")).await?;
+    byte_serialize(Value::Object(filters).to_string().as_bytes()).collect()
+}
 
-        for div in child_divs {
-            ids.push(div.attr("href").await?.unwrap());
-        }
+impl Scrapper for RemaxScrapper {
+    type ListingRaw = RemaxListingRaw;
+
+    fn portal(&self) -> &'static str {
+        "remax"
     }
 
-    println!("{:?}", ids);
+    // The whole country is searched at once unless the search has districts
+    fn segments(&self, search: &SearchSpec) -> Vec<String> {
+        let homes: bool = search.has_homes();
+        let districts: Vec<&str> = match search.districts.is_empty() {
+            true => vec!["r"],
+            false => search.districts(),
+        };
 
-    Ok(ids)
-}
+        search
+            .portal_kinds(self.portal(), |property_kind| {
+                remax_kind(property_kind, homes)
+            })
+            .into_iter()
+            .flat_map(|kind| {
+                districts.iter().map(move |district| {
+                    format!(
+                        "{}/imoveis/{}/{}",
+                        search.transaction.slug(),
+                        kind,
+                        district
+                    )
+                })
+            })
+            .collect()
+    }
 
-async fn get_listing(
-    web_driver: &WebDriver,
-    url_id: String,
-) -> Result<RemaxListingRaw, WebDriverError> {
-    let _ = web_driver
-        .get(format!("https://www.remax.pt/{}", url_id))
-        .await;
+    fn listing_page_url(&self, search: &SearchSpec, segment: &str, page: u32) -> String {
+        format!(
+            "https://www.remax.pt/pt/{}/r/r/t?s={}&p={}&o=-ContractDate",
+            segment,
+            remax_filters(search),
+            page
+        )
+    }
 
-    tokio::time::sleep(Duration::from_millis(200)).await;
+    fn newest_first(&self) -> bool {
+        true
+    }
 
-    let description = web_driver.find(By::Id("description")).await?.text().await?;
-    let details_div = web_driver.find(By::Id("details")).await?;
-    let details_vec = details_div.find_all(By::ClassName("flex")).await?;
+    fn listing_url(&self, url_id: &str) -> String {
+        format!("https://www.remax.pt/{}", url_id)
+    }
+
+    fn selectors(&self) -> Vec<FieldSelector> {
+        vec![
+            URL_IDS_LIST,
+            URL_IDS_LINK,
+            DESCRIPTION,
+            DETAILS,
+            DETAILS_ITEM,
+            PRICE,
+            LOCATION,
+        ]
+    }
+
+    // The search results and the listings are rendered by JavaScript
+    fn listing_page_wait(&self) -> PageWait {
+        PageWait::document_ready().element(URL_IDS_LIST)
+    }
 
-    let price: String = web_driver
-        .find(By::Tag("main"))
-        .await?
-        .find(By::Tag("h2"))
-        .await?
-        .find(By::Tag("b"))
-        .await?
-        .text()
-        .await?;
+    fn listing_wait(&self) -> PageWait {
+        PageWait::document_ready().element(PRICE)
+    }
+
+    fn get_url_ids(&self, html_page: &HtmlPage) -> Result<Vec<String>, ScrapperError> {
+        let parent_div: ElementRef = html_page.find(URL_IDS_LIST)?;
 
-    let mut details_split_by_string: Vec<String> = Vec::new();
+        let child_divs: Vec<ElementRef> = find_all_in(parent_div, URL_IDS_LINK)?;
 
-    for div in details_vec {
-        details_split_by_string.push(div.text().await?);
+        Ok(child_divs
+            .into_iter()
+            .filter_map(|div| attr(div, "href"))
+            .collect())
     }
 
-    let remax_listing_raw = RemaxListingRaw {
-        price,
-        description,
-        details_split_by_string,
-        url_id,
-    };
+    fn get_listing(
+        &self,
+        html_page: &HtmlPage,
+        url_id: String,
+    ) -> Result<RemaxListingRaw, ScrapperError> {
+        let description: String = text(html_page.find(DESCRIPTION)?);
+        let details_div: ElementRef = html_page.find(DETAILS)?;
+        let details_vec: Vec<ElementRef> = find_all_in(details_div, DETAILS_ITEM)?;
+
+        let price: String = text(html_page.find(PRICE)?);
+
+        let details_split_by_string: Vec<String> = details_vec.into_iter().map(text).collect();
+
+        let location: Option<String> = html_page.find(LOCATION).ok().map(text);
 
-    Ok(remax_listing_raw)
+        let remax_listing_raw = RemaxListingRaw {
+            price,
+            description,
+            details_split_by_string,
+            url_id,
+            location,
+            transaction: None,
+        };
+
+        Ok(remax_listing_raw)
+    }
+
+    // Remax keeps answering past the last page, with no listings
+    fn is_last_page(
+        &self,
+        _html_page: &HtmlPage,
+        _segment: &str,
+        page: u32,
+        url_ids: &[String],
+        _previous_url_ids: &[String],
+    ) -> Result<bool, ScrapperError> {
+        Ok(page > 1 && url_ids.is_empty())
+    }
 }
 
-pub async fn remax_scrape_mechanism() -> Result<(), WebDriverError> {
-    let web_driver: WebDriver = initialize_driver().await?;
-    let remax_ids_read: File = get_file_read("remax_ids.txt").await?;
-    let mut remax_ids_write: File = get_file_write_append("remax_ids.txt").await?;
-    let mut remax_write: File = get_file_write_append("remax.json").await?;
-    let remax_ids: String = get_content_as_string(remax_ids_read).await?;
-
-    for page in 1.. {
-        println!("\nScrapper mechanism page {}", page);
-
-        // If we cannot get the page loaded in 30 seconds we ignore it and move on
-        let url_ids_vec: Result<Vec<String>, WebDriverError> =
-            timeout(Duration::from_secs(30), get_url_ids(&web_driver, page))
-                .await
-                .unwrap_or_else(|_| {
-                    println!("Scrapper mechanism url_ids timed out after 30 seconds!");
-                    Ok(Vec::new())
-                });
-
-        match url_ids_vec {
-            Ok(url_ids) => {
-                for url_id in url_ids {
-                    if !remax_ids.contains(&url_id) {
-                        println!("Url id: {}", url_id);
-                        let remax_listing: RemaxListingRaw =
-                            get_listing(&web_driver, url_id).await?;
-
-                        write_to_file(&mut remax_write, format!("{}\n", json!(remax_listing)))
-                            .await?;
-
-                        write_to_file(&mut remax_ids_write, format!("{}\n", remax_listing.url_id))
-                            .await?;
-
-                        tokio::time::sleep(Duration::from_millis(500)).await;
-                    } else {
-                        println!("Url id: {} is already scrapped", url_id);
-                    }
-                }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schemas::listing::{Listing, Normalize, Transaction};
+    use crate::scrappers::fixture_server::FixtureServer;
+    use crate::scrappers::html_page::FetchedPage;
+
+    #[tokio::test]
+    async fn extracts_url_ids_from_search_page() {
+        let server: FixtureServer = FixtureServer::start("remax").await;
+        let fetched: FetchedPage = server.fetch("search.html").await;
+
+        let url_ids: Vec<String> = RemaxScrapper
+            .get_url_ids(&HtmlPage::parse(&fetched))
+            .unwrap();
+
+        assert_eq!(
+            url_ids,
+            vec![
+                "/pt/imoveis/apartamento-t2-venda-arroios-lisboa/122361016-55",
+                "/pt/imoveis/moradia-t4-venda-cascais/121371134-12",
+                "/pt/imoveis/terreno-venda-sintra/122901001-7",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn extracts_listings() {
+        let server: FixtureServer = FixtureServer::start("remax").await;
+        let first: FetchedPage = server.fetch("listing_1.html").await;
+        let second: FetchedPage = server.fetch("listing_2.html").await;
+
+        assert_eq!(
+            RemaxScrapper
+                .get_listing(&HtmlPage::parse(&first), "122361016-55".to_string())
+                .unwrap(),
+            RemaxListingRaw {
+                price: "385 000 €".to_string(),
+                description: "Apartamento T2 totalmente remodelado, junto ao metro de Arroios.\nCozinha equipada e varanda com vista desafogada.".to_string(),
+                details_split_by_string: vec![
+                    "Área Útil 82 m²".to_string(),
+                    "Tipologia T2".to_string(),
+                    "Ano de construção 1950".to_string(),
+                    "Concelho Lisboa".to_string(),
+                ],
+                url_id: "122361016-55".to_string(),
+                location: Some("Arroios, Lisboa".to_string()),
+                transaction: None,
             }
-            Err(e) => {
-                println!("Error getting url_ids: {}", e);
-                break;
+        );
+        assert_eq!(
+            RemaxScrapper
+                .get_listing(&HtmlPage::parse(&second), "121371134-12".to_string())
+                .unwrap(),
+            RemaxListingRaw {
+                price: "1 250 000 €".to_string(),
+                description: "Moradia isolada com piscina e jardim.".to_string(),
+                details_split_by_string: vec![
+                    "Área Bruta 320 m²".to_string(),
+                    "Tipologia\nT4".to_string(),
+                ],
+                url_id: "121371134-12".to_string(),
+                location: None,
+                transaction: None,
             }
-        }
+        );
     }
 
-    Ok(())
-}
+    #[tokio::test]
+    async fn fails_on_a_page_without_listings() {
+        let server: FixtureServer = FixtureServer::start("remax").await;
+        let fetched: FetchedPage = server.fetch("listing_1.html").await;
+
+        assert!(matches!(
+            RemaxScrapper.get_url_ids(&HtmlPage::parse(&fetched)),
+            Err(ScrapperError::MissingField {
+                field: "url_ids",
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn detects_removed_listing() {
+        let server: FixtureServer = FixtureServer::start("remax").await;
+        let fetched: FetchedPage = server.fetch("removed.html").await;
+
+        assert_eq!(
+            RemaxScrapper.listing_gone_reason(&HtmlPage::parse(&fetched), "removed.html"),
+            Some("answered 404".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn normalizes_listings() {
+        let server: FixtureServer = FixtureServer::start("remax").await;
+        let fetched: FetchedPage = server.fetch("listing_1.html").await;
+        let bragança_house: RemaxListingRaw = RemaxListingRaw {
+            price: "210 000 €".to_string(),
+            description: "Moradia em Bragança com guarda-roupa embutido, não mobilada.".to_string(),
+            details_split_by_string: vec!["Tipologia T3".to_string()],
+            url_id: "121400000-1".to_string(),
+            location: None,
+            transaction: None,
+        };
+
+        let listing: Listing = RemaxScrapper
+            .get_listing(&HtmlPage::parse(&fetched), "122361016-55".to_string())
+            .unwrap()
+            .normalize();
+        let bragança_listing: Listing = bragança_house.normalize();
+
+        assert_eq!(listing.price_cents, Some(38_500_000));
+        assert_eq!(listing.area_sqr_meters, Some(82.0));
+        assert_eq!(listing.no_bedrooms, Some(2));
+        assert_eq!(listing.concelho, Some("Lisboa".to_string()));
+        assert_eq!(listing.district, Some("lisboa".to_string()));
+        assert_eq!(listing.features, vec!["balcony"]);
+        assert_eq!(listing.freguesia, Some("Arroios".to_string()));
+        assert_eq!(bragança_listing.district, Some("braganca".to_string()));
+        assert_eq!(bragança_listing.no_bedrooms, Some(3));
+        assert_eq!(bragança_listing.furnished, Some(false));
+    }
+
+    #[test]
+    fn builds_search_urls() {
+        let default: SearchSpec = SearchSpec::default();
+        let land_to_rent: SearchSpec = SearchSpec {
+            transaction: Transaction::Rent,
+            property_kinds: vec![PropertyKind::Land],
+            max_price: Some(1000),
+            districts: vec!["lisboa".to_string()],
+            ..SearchSpec::default()
+        };
 
-pub async fn run() {
-    match remax_scrape_mechanism().await {
-        Ok(_) => {
-            println!("Remax scrapper mechanism finished")
-        }
-        Err(e) => {
-            eprintln!("Error: {:?}", e);
-        }
+        assert_eq!(
+            RemaxScrapper.segments(&default),
+            vec!["comprar/imoveis/habitacao/r"]
+        );
+        assert_eq!(
+            RemaxScrapper.listing_page_url(&default, "comprar/imoveis/habitacao/r", 2),
+            "https://www.remax.pt/pt/comprar/imoveis/habitacao/r/r/r/t?s=%7B%7D&p=2&o=-ContractDate"
+        );
+        assert_eq!(
+            RemaxScrapper.segments(&land_to_rent),
+            vec!["arrendar/imoveis/terreno/lisboa"]
+        );
+        assert_eq!(
+            RemaxScrapper.listing_page_url(&land_to_rent, "arrendar/imoveis/terreno/lisboa", 1),
+            "https://www.remax.pt/pt/arrendar/imoveis/terreno/lisboa/r/r/t?s=%7B%22pmax%22%3A1000%7D&p=1&o=-ContractDate"
+        );
     }
 }
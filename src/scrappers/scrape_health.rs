@@ -0,0 +1,427 @@
+use crate::schemas::listing::Listing;
+use crate::scrappers::html_page::FieldSelector;
+use crate::scrappers::scrapper_error::ScrapperError;
+use crate::utils::env_utils::env_number;
+use crate::utils::file_utils::{get_file_write_append, write_to_file};
+use crate::utils::time_utils::unix_timestamp;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::env;
+use std::error::Error;
+use std::fmt;
+use tokio::fs::File;
+
+type HealthResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
+
+// Errors are grouped by their message, which can carry a url
+const MAX_ERROR_LENGTH: usize = 120;
+
+/// When a run is considered broken instead of unlucky
+pub struct HealthThresholds {
+    /// Listings a portal has to attempt before the ratios are checked
+    pub min_listings: u32,
+    /// Ratio of the listings missing a field above which its selectors are considered broken
+    pub max_missing_ratio: f64,
+    /// Ratio of the listings failing for any reason above which the run is considered broken
+    pub max_error_ratio: f64,
+    /// Listing pages of a segment in a row without url ids above which their selectors are
+    /// considered broken
+    pub max_empty_pages: u32,
+    /// Listing pages in a row that failed to load, whatever their segment, above which the run is
+    /// considered broken
+    pub max_failed_pages: u32,
+}
+
+impl HealthThresholds {
+    /// Reads `HEALTH_MIN_LISTINGS` (defaults to 20), `HEALTH_MAX_MISSING_RATIO` (0.9),
+    /// `HEALTH_MAX_ERROR_RATIO` (0.5), `HEALTH_MAX_EMPTY_PAGES` (3) and `HEALTH_MAX_FAILED_PAGES` (3)
+    pub fn from_env() -> HealthResult<HealthThresholds> {
+        Ok(HealthThresholds {
+            min_listings: env_number("HEALTH_MIN_LISTINGS", 20)?,
+            max_missing_ratio: env_number("HEALTH_MAX_MISSING_RATIO", 0.9)?,
+            max_error_ratio: env_number("HEALTH_MAX_ERROR_RATIO", 0.5)?,
+            max_empty_pages: env_number("HEALTH_MAX_EMPTY_PAGES", 3)?.max(1),
+            max_failed_pages: env_number("HEALTH_MAX_FAILED_PAGES", 3)?.max(1),
+        })
+    }
+}
+
+/// The portals of a run whose selectors stopped matching, not worth retrying until they are fixed
+#[derive(Debug)]
+pub struct SelectorsBroken(pub Vec<String>);
+
+impl fmt::Display for SelectorsBroken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.join("; "))
+    }
+}
+
+impl Error for SelectorsBroken {}
+
+/// What went right and wrong scraping a portal in a run, a line of the health report
+#[derive(Serialize)]
+pub struct PortalHealth {
+    portal: String,
+    run_id: i64,
+    started_at: i64,
+    finished_at: Option<i64>,
+    pages: u32,
+    empty_pages: u32,
+    /// Pages that failed to load, not counted as empty
+    failed_pages: u32,
+    /// Empty pages at the end of each segment, the segments are crawled at the same time
+    #[serde(skip)]
+    empty_pages_in_a_row: BTreeMap<String, u32>,
+    #[serde(skip)]
+    failed_pages_in_a_row: u32,
+    #[serde(skip)]
+    pages_with_url_ids: u32,
+    /// Listings attempted, not counting the gone ones
+    listings: u32,
+    gone: u32,
+    failed: u32,
+    /// Listings without each field, because its element is missing or it is empty
+    missing_fields: BTreeMap<String, u32>,
+    errors: BTreeMap<String, u32>,
+    /// Why the portal was stopped, `None` while it is healthy
+    broken: Option<String>,
+    #[serde(skip)]
+    selectors: Vec<FieldSelector>,
+}
+
+fn is_empty(value: &Value) -> bool {
+    match value {
+        Value::Null => true,
+        Value::String(text) => text.trim().is_empty(),
+        Value::Array(values) => values.is_empty(),
+        _ => false,
+    }
+}
+
+impl PortalHealth {
+    pub fn new(portal: &str, run_id: i64, selectors: Vec<FieldSelector>) -> PortalHealth {
+        PortalHealth {
+            portal: portal.to_string(),
+            run_id,
+            started_at: unix_timestamp(),
+            finished_at: None,
+            pages: 0,
+            empty_pages: 0,
+            failed_pages: 0,
+            empty_pages_in_a_row: BTreeMap::new(),
+            failed_pages_in_a_row: 0,
+            pages_with_url_ids: 0,
+            listings: 0,
+            gone: 0,
+            failed: 0,
+            missing_fields: BTreeMap::new(),
+            errors: BTreeMap::new(),
+            broken: None,
+            selectors,
+        }
+    }
+
+    pub fn broken(&self) -> Option<&str> {
+        self.broken.as_deref()
+    }
+
+    fn record_error(&mut self, error: &ScrapperError) {
+        if let ScrapperError::MissingField { field, .. } = error {
+            *self.missing_fields.entry(field.to_string()).or_default() += 1;
+        }
+
+        let message: String = match error {
+            ScrapperError::PageNeverLoaded { waiting_for, .. } => {
+                format!("page never loaded, waiting for {}", waiting_for)
+            }
+            error => error.to_string(),
+        };
+        let message: String = message.chars().take(MAX_ERROR_LENGTH).collect();
+        *self.errors.entry(message).or_default() += 1;
+    }
+
+    /// Counts a listing page of `segment`, failed when it did not load and empty when it loaded
+    /// without url ids
+    pub fn record_page(
+        &mut self,
+        segment: &str,
+        page: u32,
+        url_ids: &Result<Option<Vec<String>>, ScrapperError>,
+    ) {
+        self.pages += 1;
+        let empty_pages_in_a_row: &mut u32 = self
+            .empty_pages_in_a_row
+            .entry(segment.to_string())
+            .or_default();
+        if page == 1 {
+            *empty_pages_in_a_row = 0;
+        }
+
+        match url_ids {
+            Ok(Some(url_ids)) if !url_ids.is_empty() => {
+                self.pages_with_url_ids += 1;
+                *empty_pages_in_a_row = 0;
+                self.failed_pages_in_a_row = 0;
+            }
+            Ok(Some(_)) => {
+                self.empty_pages += 1;
+                *empty_pages_in_a_row += 1;
+            }
+            Ok(None) => {}
+            Err(e) => {
+                self.failed_pages += 1;
+                self.failed_pages_in_a_row += 1;
+                self.record_error(e);
+            }
+        }
+    }
+
+    pub fn record_listing(&mut self, listing: &Result<(String, Listing), ScrapperError>) {
+        match listing {
+            Ok((listing_raw, _)) => {
+                self.listings += 1;
+
+                let fields: Value = serde_json::from_str(listing_raw).unwrap_or_default();
+                for (field, value) in fields.as_object().into_iter().flatten() {
+                    if field != "url_id" && is_empty(value) {
+                        *self.missing_fields.entry(field.clone()).or_default() += 1;
+                    }
+                }
+            }
+            Err(ScrapperError::ListingGone(_)) => self.gone += 1,
+            Err(e) => {
+                self.listings += 1;
+                self.failed += 1;
+                self.record_error(e);
+            }
+        }
+    }
+
+    fn selectors_of(&self, field: &str) -> String {
+        self.selectors
+            .iter()
+            .filter(|selector| selector.field == field)
+            .map(|selector| format!("`{}`", selector.css))
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+
+    fn most_common_error(&self) -> &str {
+        self.errors
+            .iter()
+            .max_by_key(|(_, count)| **count)
+            .map(|(error, _)| error.as_str())
+            .unwrap_or_default()
+    }
+
+    /// Why the portal looks broken so far, `finished` once every page was crawled
+    fn diagnose(&self, thresholds: &HealthThresholds, finished: bool) -> Option<String> {
+        let empty_segment: bool = self
+            .empty_pages_in_a_row
+            .values()
+            .any(|empty_pages| *empty_pages >= thresholds.max_empty_pages);
+        if empty_segment || (finished && self.pages > 0 && self.pages_with_url_ids == 0) {
+            return Some(format!(
+                "selectors broken for field url_ids ({}) of {}: {} listing pages without url ids",
+                self.selectors_of("url_ids"),
+                self.portal,
+                self.empty_pages + self.failed_pages
+            ));
+        }
+
+        if self.failed_pages_in_a_row >= thresholds.max_failed_pages {
+            return Some(format!(
+                "{} {} listing pages in a row failed, mostly with: {}",
+                self.failed_pages_in_a_row,
+                self.portal,
+                self.most_common_error()
+            ));
+        }
+
+        if self.listings < thresholds.min_listings {
+            return None;
+        }
+
+        let broken_field: Option<(&String, &u32)> =
+            self.missing_fields.iter().find(|(_, no_missing)| {
+                **no_missing as f64 / self.listings as f64 >= thresholds.max_missing_ratio
+            });
+        if let Some((field, no_missing)) = broken_field {
+            return Some(format!(
+                "selectors broken for field {} ({}) of {}: missing in {} of {} listings",
+                field,
+                self.selectors_of(field),
+                self.portal,
+                no_missing,
+                self.listings
+            ));
+        }
+
+        if self.failed as f64 / self.listings as f64 >= thresholds.max_error_ratio {
+            return Some(format!(
+                "{} of {} {} listings failed, mostly with: {}",
+                self.failed,
+                self.listings,
+                self.portal,
+                self.most_common_error()
+            ));
+        }
+
+        None
+    }
+
+    /// Marks the portal as broken when a threshold is crossed
+    pub fn check(&mut self, thresholds: &HealthThresholds, finished: bool) {
+        if self.broken.is_some() {
+            return;
+        }
+
+        self.broken = self.diagnose(thresholds, finished);
+        if let Some(reason) = &self.broken {
+            println!("Stopping {}, {}", self.portal, reason);
+        }
+    }
+
+    /// Appends the health of the run as a Json line to `HEALTH_PATH` (defaults to
+    /// `scrape_health.json`)
+    pub async fn write(&mut self) -> HealthResult<()> {
+        self.finished_at = Some(unix_timestamp());
+
+        let path: String = env::var("HEALTH_PATH").unwrap_or("scrape_health.json".to_string());
+        let mut health_write: File = get_file_write_append(&path).await?;
+        write_to_file(&mut health_write, format!("{}\n", json!(self))).await?;
+
+        println!(
+            "{} health: {} pages ({} empty, {} failed), {} listings, {} failed, {} gone, missing {:?}",
+            self.portal,
+            self.pages,
+            self.empty_pages,
+            self.failed_pages,
+            self.listings,
+            self.failed,
+            self.gone,
+            self.missing_fields
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schemas::listing::Normalize;
+    use crate::schemas::remax_listing_raw::RemaxListingRaw;
+
+    const PRICE: FieldSelector = FieldSelector::new("price", "main h2 b");
+    const URL_IDS: FieldSelector = FieldSelector::new("url_ids", ".pl-0");
+
+    fn thresholds() -> HealthThresholds {
+        HealthThresholds {
+            min_listings: 4,
+            max_missing_ratio: 0.75,
+            max_error_ratio: 0.5,
+            max_empty_pages: 2,
+            max_failed_pages: 2,
+        }
+    }
+
+    fn listing(price: &str) -> Result<(String, Listing), ScrapperError> {
+        let listing_raw: RemaxListingRaw = RemaxListingRaw {
+            price: price.to_string(),
+            description: "T2 com varanda".to_string(),
+            details_split_by_string: vec!["Tipologia T2".to_string()],
+            url_id: "1".to_string(),
+            location: None,
+            transaction: None,
+        };
+        Ok((json!(listing_raw).to_string(), listing_raw.normalize()))
+    }
+
+    fn missing_price() -> Result<(String, Listing), ScrapperError> {
+        Err(ScrapperError::MissingField {
+            field: PRICE.field,
+            css: PRICE.css,
+        })
+    }
+
+    #[test]
+    fn breaks_when_a_field_is_missing_from_most_listings() {
+        let mut health: PortalHealth = PortalHealth::new("remax", 1, vec![PRICE, URL_IDS]);
+        health.record_page("lisboa", 1, &Ok(Some(vec!["1".to_string()])));
+        health.record_listing(&listing("100 000 €"));
+        health.record_listing(&listing(""));
+        health.record_listing(&missing_price());
+        health.check(&thresholds(), false);
+        // Too few listings to tell
+        assert_eq!(health.broken(), None);
+
+        health.record_listing(&missing_price());
+        health.check(&thresholds(), false);
+
+        assert_eq!(
+            health.broken(),
+            Some("selectors broken for field price (`main h2 b`) of remax: missing in 3 of 4 listings")
+        );
+    }
+
+    #[test]
+    fn gone_listings_are_not_failures() {
+        let mut health: PortalHealth = PortalHealth::new("remax", 1, vec![PRICE, URL_IDS]);
+        health.record_page("lisboa", 1, &Ok(Some(vec!["1".to_string()])));
+        for _ in 0..4 {
+            health.record_listing(&Err(ScrapperError::ListingGone("answered 404".to_string())));
+        }
+        health.record_listing(&listing("100 000 €"));
+        health.check(&thresholds(), true);
+
+        assert_eq!(health.broken(), None);
+    }
+
+    #[test]
+    fn breaks_on_empty_listing_pages_of_a_segment() {
+        let mut health: PortalHealth = PortalHealth::new("remax", 1, vec![PRICE, URL_IDS]);
+
+        // Segments crawled at the same time do not add up, a new crawl of a segment starts over
+        health.record_page("lisboa", 1, &Ok(Some(Vec::new())));
+        health.record_page("porto", 1, &Ok(Some(Vec::new())));
+        health.record_page("lisboa", 1, &Ok(Some(Vec::new())));
+        health.check(&thresholds(), false);
+        assert_eq!(health.broken(), None);
+
+        health.record_page("porto", 2, &Ok(Some(Vec::new())));
+        health.check(&thresholds(), false);
+
+        assert_eq!(
+            health.broken(),
+            Some("selectors broken for field url_ids (`.pl-0`) of remax: 4 listing pages without url ids")
+        );
+    }
+
+    #[test]
+    fn counts_failed_listing_pages_apart_from_empty_ones() {
+        let mut health: PortalHealth = PortalHealth::new("remax", 1, vec![PRICE, URL_IDS]);
+        let never_loaded: Result<Option<Vec<String>>, ScrapperError> =
+            Err(ScrapperError::PageNeverLoaded {
+                url: "https://www.remax.pt/pt/comprar".to_string(),
+                waiting_for: "`.pl-0`".to_string(),
+            });
+
+        health.record_page("lisboa", 1, &Ok(Some(vec!["1".to_string()])));
+        health.record_page("lisboa", 2, &never_loaded);
+        health.record_page("porto", 1, &never_loaded);
+        health.record_page("setubal", 1, &Ok(Some(vec!["2".to_string()])));
+        health.record_page("faro", 1, &never_loaded);
+        health.check(&thresholds(), true);
+        assert_eq!(health.broken(), None);
+        assert_eq!((health.empty_pages, health.failed_pages), (0, 3));
+
+        health.record_page("braga", 1, &never_loaded);
+        health.check(&thresholds(), false);
+
+        assert_eq!(
+            health.broken(),
+            Some("2 remax listing pages in a row failed, mostly with: page never loaded, waiting for `.pl-0`")
+        );
+    }
+}
@@ -0,0 +1,1054 @@
+use crate::schemas::listing::{Listing, Transaction};
+use crate::scrappers::driver::{initialize_driver, DriverSession};
+use crate::scrappers::page_fetcher::{FetchMode, HttpFetcher, PageFetcher, WebDriverFetcher};
+use crate::scrappers::scrape_health::{HealthThresholds, PortalHealth, SelectorsBroken};
+use crate::scrappers::scrapper::{mark_rental, PortalScrapper};
+use crate::scrappers::scrapper_error::ScrapperError;
+use crate::scrappers::search_spec::SearchSpec;
+use crate::storage::checkpoint_store::{Checkpoint, CheckpointStore};
+use crate::storage::listing_storage::ListingStorage;
+use crate::storage::page_archive::PageArchive;
+use crate::utils::env_utils::env_number;
+use futures::future::try_join_all;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::env;
+use std::error::Error;
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+pub type PoolResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
+
+/// How many browser sessions scrape at the same time and how hard each portal is hit
+pub struct PoolConfig {
+    /// Browser sessions working at the same time, the global concurrency limit
+    pub concurrency: usize,
+    /// Jobs in flight on the same host at the same time
+    pub host_concurrency: usize,
+    /// Minimum time between the start of two jobs on the same host
+    pub host_delay: Duration,
+    /// Known listings in a row after which an incremental crawl stops paginating a segment,
+    /// `None` for a full crawl
+    pub stop_after_known: Option<usize>,
+}
+
+impl PoolConfig {
+    /// Reads `SCRAPE_CONCURRENCY` (defaults to 1), `HOST_CONCURRENCY` (defaults to the
+    /// concurrency), `HOST_DELAY_MS` (defaults to 500) and `CRAWL_MODE`, full (the default) or
+    /// incremental stopping after `STOP_AFTER_KNOWN` (defaults to 10) known listings in a row
+    pub fn from_env() -> PoolResult<PoolConfig> {
+        let concurrency: usize = env_number("SCRAPE_CONCURRENCY", 1)?.max(1);
+        let stop_after_known: Option<usize> =
+            match env::var("CRAWL_MODE").as_deref().unwrap_or("full") {
+                "full" => None,
+                "incremental" => Some(env_number("STOP_AFTER_KNOWN", 10)?.max(1)),
+                crawl_mode => {
+                    return Err(format!(
+                        "`CRAWL_MODE` should be full or incremental, not {}",
+                        crawl_mode
+                    )
+                    .into())
+                }
+            };
+
+        Ok(PoolConfig {
+            concurrency,
+            host_concurrency: env_number("HOST_CONCURRENCY", concurrency)?.max(1),
+            host_delay: Duration::from_millis(env_number("HOST_DELAY_MS", 500)?),
+            stop_after_known,
+        })
+    }
+}
+
+enum JobKind {
+    ListingPage {
+        segment_index: usize,
+        page: u32,
+        previous_url_ids: Vec<String>,
+        /// Listings already known in a row at the end of the previous pages
+        known_in_a_row: usize,
+    },
+    Listing {
+        url_id: String,
+        sequence: u64,
+    },
+}
+
+struct ScrapeJob {
+    portal_index: usize,
+    host: String,
+    kind: JobKind,
+}
+
+struct HostSlots {
+    in_flight: usize,
+    next_start: Instant,
+}
+
+struct QueueState {
+    jobs: VecDeque<ScrapeJob>,
+    /// Jobs queued or in flight, the queue is done when it reaches zero
+    pending: usize,
+    hosts: HashMap<String, HostSlots>,
+}
+
+enum NextJob {
+    Job(ScrapeJob),
+    Wait(Option<Instant>),
+    Done,
+}
+
+/// Jobs shared by the sessions, handed out in order skipping the hosts that are at their limit
+struct JobQueue {
+    state: std::sync::Mutex<QueueState>,
+    notify: Notify,
+    host_concurrency: usize,
+    host_delay: Duration,
+}
+
+impl JobQueue {
+    fn new(config: &PoolConfig) -> JobQueue {
+        JobQueue {
+            state: std::sync::Mutex::new(QueueState {
+                jobs: VecDeque::new(),
+                pending: 0,
+                hosts: HashMap::new(),
+            }),
+            notify: Notify::new(),
+            host_concurrency: config.host_concurrency,
+            host_delay: config.host_delay,
+        }
+    }
+
+    fn push(&self, job: ScrapeJob) {
+        if let Ok(mut state) = self.state.lock() {
+            state.pending += 1;
+            state.jobs.push_back(job);
+        }
+        self.notify.notify_waiters();
+    }
+
+    fn try_next(&self) -> NextJob {
+        let mut guard = match self.state.lock() {
+            Ok(guard) => guard,
+            Err(_) => return NextJob::Done,
+        };
+        let state: &mut QueueState = &mut guard;
+        if state.pending == 0 {
+            return NextJob::Done;
+        }
+
+        let now: Instant = Instant::now();
+        let mut wake_at: Option<Instant> = None;
+        for index in 0..state.jobs.len() {
+            if let Some(slots) = state.hosts.get(&state.jobs[index].host) {
+                if slots.in_flight >= self.host_concurrency {
+                    continue;
+                }
+                if slots.next_start > now {
+                    wake_at = Some(wake_at.map_or(slots.next_start, |at| at.min(slots.next_start)));
+                    continue;
+                }
+            }
+
+            if let Some(job) = state.jobs.remove(index) {
+                let slots: &mut HostSlots =
+                    state.hosts.entry(job.host.clone()).or_insert(HostSlots {
+                        in_flight: 0,
+                        next_start: now,
+                    });
+                slots.in_flight += 1;
+                slots.next_start = now + self.host_delay;
+                return NextJob::Job(job);
+            }
+        }
+
+        NextJob::Wait(wake_at)
+    }
+
+    /// The next job whose host can take it, `None` once every job is done
+    async fn next(&self) -> Option<ScrapeJob> {
+        loop {
+            // Registered before looking at the queue so a push in between is not missed
+            let notified = self.notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            match self.try_next() {
+                NextJob::Job(job) => return Some(job),
+                NextJob::Done => return None,
+                NextJob::Wait(Some(wake_at)) => {
+                    let _ = tokio::time::timeout_at(wake_at.into(), notified).await;
+                }
+                NextJob::Wait(None) => notified.await,
+            }
+        }
+    }
+
+    fn finish(&self, job: &ScrapeJob) {
+        if let Ok(mut state) = self.state.lock() {
+            state.pending -= 1;
+            if let Some(slots) = state.hosts.get_mut(&job.host) {
+                slots.in_flight -= 1;
+            }
+        }
+        self.notify.notify_waiters();
+    }
+}
+
+/// What a finished job leaves to be stored, in the order the jobs were queued
+enum Commit {
+    Listing {
+        portal_index: usize,
+        url_id: String,
+        listing: Option<Box<(String, Listing)>>,
+    },
+    PageDone {
+        portal_index: usize,
+        segment_index: usize,
+        page: u32,
+    },
+    SegmentDone {
+        portal_index: usize,
+        segment_index: usize,
+    },
+}
+
+struct PortalRun<'a> {
+    scrapper: &'a dyn PortalScrapper,
+    host: String,
+    segments: Vec<String>,
+    run_id: i64,
+    no_listings: u32,
+    /// Url ids queued in this run, a listing shown in two segments is only scrapped once
+    queued_ids: HashSet<String>,
+    /// Page each segment resumes from, advanced once every listing of a page was stored
+    next_pages: HashMap<usize, u32>,
+    finished_segments: HashSet<usize>,
+    checkpoint: Option<(usize, u32)>,
+    /// The last listing stored, kept in the checkpoint
+    last_seen_id: Option<String>,
+    health: PortalHealth,
+}
+
+impl PortalRun<'_> {
+    /// The first segment that is not finished and the page it resumes from
+    fn resume_point(&self) -> (usize, u32) {
+        (0..self.segments.len())
+            .find(|segment_index| !self.finished_segments.contains(segment_index))
+            .map(|segment_index| (segment_index, self.next_pages[&segment_index]))
+            .unwrap_or((self.segments.len() - 1, 1))
+    }
+}
+
+struct PoolState<'a> {
+    portals: Vec<PortalRun<'a>>,
+    next_sequence: u64,
+    next_commit: u64,
+    /// Commits of finished jobs waiting for the jobs queued before them
+    commits: BTreeMap<u64, Commit>,
+}
+
+impl PoolState<'_> {
+    fn reserve_sequence(&mut self) -> u64 {
+        self.next_sequence += 1;
+        self.next_sequence - 1
+    }
+}
+
+/// Crawls the listing pages of several portals with a pool of browser sessions. Pages of a segment
+/// are walked in order while the listings they show are scrapped by every session, and what is
+/// scrapped is stored in the order it was queued so the output and the checkpoints do not depend
+/// on which session finished first.
+struct ScrapePool<'a> {
+    storage: &'a dyn ListingStorage,
+    queue: JobQueue,
+    fetch_modes: Vec<FetchMode>,
+    http_fetcher: HttpFetcher,
+    archive: Option<PageArchive>,
+    checkpoints: &'a CheckpointStore,
+    search: &'a SearchSpec,
+    stop_after_known: Option<usize>,
+    thresholds: HealthThresholds,
+    state: tokio::sync::Mutex<PoolState<'a>>,
+    /// Held while storing the commits, so they are stored in order without holding `state`
+    flushing: tokio::sync::Mutex<()>,
+}
+
+impl<'a> ScrapePool<'a> {
+    async fn start(
+        scrappers: &[&'a dyn PortalScrapper],
+        storage: &'a dyn ListingStorage,
+        checkpoints: &'a CheckpointStore,
+        search: &'a SearchSpec,
+        config: &PoolConfig,
+    ) -> PoolResult<ScrapePool<'a>> {
+        let queue: JobQueue = JobQueue::new(config);
+        let mut portals: Vec<PortalRun<'a>> = Vec::new();
+
+        for (portal_index, scrapper) in scrappers.iter().enumerate() {
+            let segments: Vec<String> = scrapper.segment_names(search);
+            if segments.is_empty() {
+                println!("Nothing to search on {}", scrapper.portal());
+            }
+            let checkpoint: Option<Checkpoint> = checkpoints.get(scrapper.portal()).await?;
+            let (first_segment_index, first_page): (usize, u32) = checkpoint
+                .as_ref()
+                .and_then(|checkpoint| {
+                    segments
+                        .iter()
+                        .position(|item| *item == checkpoint.segment)
+                        .map(|segment_index| (segment_index, checkpoint.page))
+                })
+                .unwrap_or((0, 1));
+            if let Some(checkpoint) = &checkpoint {
+                println!(
+                    "Resuming {} from {} page {}, where run {} stopped",
+                    scrapper.portal(),
+                    checkpoint.segment,
+                    checkpoint.page,
+                    checkpoint.run_id
+                );
+            }
+
+            let run_id: i64 = storage.start_run(scrapper.portal()).await?;
+            let mut portal_run: PortalRun<'a> = PortalRun {
+                scrapper: *scrapper,
+                host: scrapper.host(),
+                segments,
+                run_id,
+                no_listings: 0,
+                queued_ids: HashSet::new(),
+                next_pages: HashMap::new(),
+                finished_segments: (0..first_segment_index).collect(),
+                checkpoint: None,
+                last_seen_id: checkpoint.and_then(|checkpoint| checkpoint.last_seen_id),
+                health: PortalHealth::new(scrapper.portal(), run_id, scrapper.field_selectors()),
+            };
+
+            for segment_index in first_segment_index..portal_run.segments.len() {
+                let page: u32 = if segment_index == first_segment_index {
+                    first_page
+                } else {
+                    1
+                };
+                portal_run.next_pages.insert(segment_index, page);
+                queue.push(ScrapeJob {
+                    portal_index,
+                    host: portal_run.host.clone(),
+                    kind: JobKind::ListingPage {
+                        segment_index,
+                        page,
+                        previous_url_ids: Vec::new(),
+                        known_in_a_row: 0,
+                    },
+                });
+            }
+
+            portals.push(portal_run);
+        }
+
+        Ok(ScrapePool {
+            storage,
+            queue,
+            fetch_modes: scrappers
+                .iter()
+                .map(|scrapper| scrapper.fetch_mode())
+                .collect::<PoolResult<Vec<FetchMode>>>()?,
+            http_fetcher: HttpFetcher::from_env()?,
+            archive: PageArchive::from_env(),
+            checkpoints,
+            search,
+            stop_after_known: config.stop_after_known,
+            thresholds: HealthThresholds::from_env()?,
+            state: tokio::sync::Mutex::new(PoolState {
+                portals,
+                next_sequence: 0,
+                next_commit: 0,
+                commits: BTreeMap::new(),
+            }),
+            flushing: tokio::sync::Mutex::new(()),
+        })
+    }
+
+    /// Stores the commits that are next in order
+    async fn flush(&self) -> PoolResult<()> {
+        let _flushing = self.flushing.lock().await;
+
+        loop {
+            let commit: Commit = {
+                let mut state = self.state.lock().await;
+                let next_commit: u64 = state.next_commit;
+                match state.commits.remove(&next_commit) {
+                    Some(commit) => {
+                        state.next_commit += 1;
+                        commit
+                    }
+                    None => return Ok(()),
+                }
+            };
+
+            let portal_index: usize = match commit {
+                Commit::Listing {
+                    portal_index,
+                    url_id,
+                    listing,
+                } => {
+                    if let Some((listing_raw, listing)) = listing.map(|listing| *listing) {
+                        let run_id: i64 = self.state.lock().await.portals[portal_index].run_id;
+                        self.storage
+                            .save_listing(run_id, &listing_raw, &listing)
+                            .await?;
+
+                        let mut state = self.state.lock().await;
+                        let portal_run: &mut PortalRun = &mut state.portals[portal_index];
+                        portal_run.no_listings += 1;
+                        portal_run.last_seen_id = Some(url_id);
+                    }
+                    continue;
+                }
+                Commit::PageDone {
+                    portal_index,
+                    segment_index,
+                    page,
+                } => {
+                    self.state.lock().await.portals[portal_index]
+                        .next_pages
+                        .insert(segment_index, page + 1);
+                    portal_index
+                }
+                Commit::SegmentDone {
+                    portal_index,
+                    segment_index,
+                } => {
+                    let mut state = self.state.lock().await;
+                    let portal_run: &mut PortalRun = &mut state.portals[portal_index];
+                    portal_run.finished_segments.insert(segment_index);
+                    portal_run.next_pages.insert(segment_index, 1);
+                    portal_index
+                }
+            };
+
+            let checkpoint: Option<Checkpoint> = {
+                let mut state = self.state.lock().await;
+                let portal_run: &mut PortalRun = &mut state.portals[portal_index];
+                let resume_point: (usize, u32) = portal_run.resume_point();
+                let changed: bool = portal_run.checkpoint != Some(resume_point);
+                portal_run.checkpoint = Some(resume_point);
+
+                changed.then(|| {
+                    let (segment_index, page): (usize, u32) = resume_point;
+                    Checkpoint::new(
+                        portal_run.scrapper.portal(),
+                        &portal_run.segments[segment_index],
+                        page,
+                        portal_run.last_seen_id.clone(),
+                        portal_run.run_id,
+                    )
+                })
+            };
+            if let Some(checkpoint) = checkpoint {
+                self.checkpoints.save(&checkpoint).await?;
+            }
+        }
+    }
+
+    async fn commit(&self, commit: Commit) -> PoolResult<()> {
+        {
+            let mut state = self.state.lock().await;
+            let sequence: u64 = state.reserve_sequence();
+            state.commits.insert(sequence, commit);
+        }
+        self.flush().await
+    }
+
+    async fn scrape_listing_page(
+        &self,
+        fetcher: &dyn PageFetcher,
+        portal_index: usize,
+        segment_index: usize,
+        page: u32,
+        previous_url_ids: &[String],
+        known_in_a_row: usize,
+    ) -> PoolResult<()> {
+        let (scrapper, segment, host): (&dyn PortalScrapper, String, String) = {
+            let state = self.state.lock().await;
+            let portal_run: &PortalRun = &state.portals[portal_index];
+            (
+                portal_run.scrapper,
+                portal_run.segments[segment_index].clone(),
+                portal_run.host.clone(),
+            )
+        };
+        println!(
+            "\nScrapper mechanism {} {} page {}",
+            scrapper.portal(),
+            segment,
+            page
+        );
+
+        let page_url_ids: Result<Option<Vec<String>>, ScrapperError> = scrapper
+            .page_url_ids(fetcher, self.search, &segment, page, previous_url_ids)
+            .await;
+        {
+            let mut state = self.state.lock().await;
+            let health: &mut PortalHealth = &mut state.portals[portal_index].health;
+            health.record_page(&segment, page, &page_url_ids);
+            health.check(&self.thresholds, false);
+            // The pages of a broken portal are left for the next run, the checkpoint stays
+            if health.broken().is_some() {
+                return Ok(());
+            }
+        }
+
+        let url_ids: Vec<String> = match page_url_ids {
+            Ok(Some(url_ids)) => url_ids,
+            Ok(None) => {
+                println!("No more pages after {} for segment {}", page, segment);
+                return self
+                    .commit(Commit::SegmentDone {
+                        portal_index,
+                        segment_index,
+                    })
+                    .await;
+            }
+            // The segment is left unfinished so the checkpoint stays on this page for the next run
+            Err(e) => {
+                println!(
+                    "Error getting url_ids, leaving segment {} at page {}: {}",
+                    segment, page, e
+                );
+                return Ok(());
+            }
+        };
+
+        let queued_now: Vec<bool> = {
+            let mut state = self.state.lock().await;
+            let queued_ids: &mut HashSet<String> = &mut state.portals[portal_index].queued_ids;
+            url_ids
+                .iter()
+                .map(|url_id| queued_ids.insert(url_id.clone()))
+                .collect()
+        };
+        // Looked up without holding the state, the other sessions keep storing meanwhile
+        let mut looked_up_ids: Vec<(&String, bool, bool)> = Vec::new();
+        for (url_id, queued_now) in url_ids.iter().zip(queued_now) {
+            let is_seen: bool =
+                queued_now && self.storage.is_seen(scrapper.portal(), url_id).await?;
+            looked_up_ids.push((url_id, queued_now, is_seen));
+        }
+
+        // Only the listings stored by a previous run are known, the ones queued earlier in this
+        // run are skipped without counting
+        let mut state = self.state.lock().await;
+        let mut known_in_a_row: usize = known_in_a_row;
+        for (url_id, queued_now, is_seen) in looked_up_ids {
+            if !queued_now {
+                println!("Url id: {} is already queued", url_id);
+                continue;
+            }
+            if is_seen {
+                println!("Url id: {} is already scrapped", url_id);
+                known_in_a_row += 1;
+                continue;
+            }
+            known_in_a_row = 0;
+
+            let sequence: u64 = state.reserve_sequence();
+            self.queue.push(ScrapeJob {
+                portal_index,
+                host: host.clone(),
+                kind: JobKind::Listing {
+                    url_id: url_id.clone(),
+                    sequence,
+                },
+            });
+        }
+
+        let sequence: u64 = state.reserve_sequence();
+        state.commits.insert(
+            sequence,
+            Commit::PageDone {
+                portal_index,
+                segment_index,
+                page,
+            },
+        );
+
+        // Newest first, past that many known listings the rest of the segment was seen before
+        let stop_after_known: Option<usize> =
+            self.stop_after_known.filter(|_| scrapper.newest_first());
+        if stop_after_known.is_some_and(|stop_after_known| known_in_a_row >= stop_after_known) {
+            println!(
+                "Stopping segment {} at page {} after {} known listings in a row",
+                segment, page, known_in_a_row
+            );
+            let sequence: u64 = state.reserve_sequence();
+            state.commits.insert(
+                sequence,
+                Commit::SegmentDone {
+                    portal_index,
+                    segment_index,
+                },
+            );
+            drop(state);
+            return self.flush().await;
+        }
+
+        self.queue.push(ScrapeJob {
+            portal_index,
+            host,
+            kind: JobKind::ListingPage {
+                segment_index,
+                page: page + 1,
+                previous_url_ids: url_ids,
+                known_in_a_row,
+            },
+        });
+        drop(state);
+
+        self.flush().await
+    }
+
+    async fn scrape_listing(
+        &self,
+        fetcher: &dyn PageFetcher,
+        portal_index: usize,
+        url_id: &str,
+        sequence: u64,
+    ) -> PoolResult<()> {
+        let scrapper: &dyn PortalScrapper = self.state.lock().await.portals[portal_index].scrapper;
+        println!("Url id: {}", url_id);
+
+        let scrapped: Result<(String, Listing), ScrapperError> = scrapper
+            .scrape_listing(fetcher, self.archive.as_ref(), url_id)
+            .await
+            .map(|scrapped| match self.search.transaction {
+                Transaction::Rent => mark_rental(scrapped),
+                Transaction::Buy => scrapped,
+            });
+
+        let mut state = self.state.lock().await;
+        let health: &mut PortalHealth = &mut state.portals[portal_index].health;
+        health.record_listing(&scrapped);
+        health.check(&self.thresholds, false);
+
+        let listing: Option<Box<(String, Listing)>> = match scrapped {
+            Ok(listing) => Some(Box::new(listing)),
+            Err(e) => {
+                println!("Failed to grab listing {}: {}", url_id, e);
+                None
+            }
+        };
+        state.commits.insert(
+            sequence,
+            Commit::Listing {
+                portal_index,
+                url_id: url_id.to_string(),
+                listing,
+            },
+        );
+        drop(state);
+
+        self.flush().await
+    }
+
+    async fn run_job(
+        &self,
+        browser_fetcher: Option<&dyn PageFetcher>,
+        job: &ScrapeJob,
+    ) -> PoolResult<()> {
+        let fetcher: &dyn PageFetcher = match (self.fetch_modes[job.portal_index], browser_fetcher)
+        {
+            (FetchMode::Browser, Some(browser_fetcher)) => browser_fetcher,
+            _ => &self.http_fetcher,
+        };
+
+        // The jobs of a broken portal are dropped, the listings only keep their place in the order
+        let mut state = self.state.lock().await;
+        if state.portals[job.portal_index].health.broken().is_some() {
+            if let JobKind::Listing { url_id, sequence } = &job.kind {
+                state.commits.insert(
+                    *sequence,
+                    Commit::Listing {
+                        portal_index: job.portal_index,
+                        url_id: url_id.clone(),
+                        listing: None,
+                    },
+                );
+                drop(state);
+                return self.flush().await;
+            }
+            return Ok(());
+        }
+        drop(state);
+
+        match &job.kind {
+            JobKind::ListingPage {
+                segment_index,
+                page,
+                previous_url_ids,
+                known_in_a_row,
+            } => {
+                self.scrape_listing_page(
+                    fetcher,
+                    job.portal_index,
+                    *segment_index,
+                    *page,
+                    previous_url_ids,
+                    *known_in_a_row,
+                )
+                .await
+            }
+            JobKind::Listing { url_id, sequence } => {
+                self.scrape_listing(fetcher, job.portal_index, url_id, *sequence)
+                    .await
+            }
+        }
+    }
+
+    /// Takes jobs until there are none left
+    async fn work(&self, browser_fetcher: Option<&dyn PageFetcher>) -> PoolResult<()> {
+        while let Some(job) = self.queue.next().await {
+            let result: PoolResult<()> = self.run_job(browser_fetcher, &job).await;
+            self.queue.finish(&job);
+            result?;
+        }
+
+        Ok(())
+    }
+
+    /// Clears the checkpoint of the portals whose every segment was crawled, so they start over
+    /// from the first page next time. The others resume from the page that failed.
+    async fn clear_finished_checkpoints(&self) -> PoolResult<()> {
+        let state = self.state.lock().await;
+        for portal_run in &state.portals {
+            let portal: &str = portal_run.scrapper.portal();
+            if portal_run.health.broken().is_some() {
+                continue;
+            }
+            if portal_run.finished_segments.len() < portal_run.segments.len() {
+                println!("Keeping {} checkpoint, a listing page failed", portal);
+                continue;
+            }
+            println!("Clearing {} checkpoint", portal);
+            self.checkpoints.clear(portal).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Stores the end of the run and the health report of every portal, failing when any of them
+    /// is broken
+    async fn finish(&self) -> PoolResult<()> {
+        self.clear_finished_checkpoints().await?;
+
+        let mut state = self.state.lock().await;
+        let mut broken: Vec<String> = Vec::new();
+
+        for portal_run in &mut state.portals {
+            self.storage
+                .finish_run(
+                    portal_run.run_id,
+                    portal_run.scrapper.portal(),
+                    portal_run.no_listings,
+                )
+                .await?;
+
+            portal_run.health.check(&self.thresholds, true);
+            portal_run.health.write().await?;
+            if let Some(reason) = portal_run.health.broken() {
+                broken.push(reason.to_string());
+            }
+        }
+
+        match broken.is_empty() {
+            true => Ok(()),
+            false => Err(Box::new(SelectorsBroken(broken))),
+        }
+    }
+}
+
+/// Scrapes every portal of `scrappers` with `config.concurrency` workers, each with its own browser
+/// session unless every portal is fetched over Http
+pub async fn scrape(
+    scrappers: &[&dyn PortalScrapper],
+    storage: &dyn ListingStorage,
+    checkpoints: &CheckpointStore,
+    search: &SearchSpec,
+    config: &PoolConfig,
+) -> PoolResult<()> {
+    let pool: ScrapePool =
+        ScrapePool::start(scrappers, storage, checkpoints, search, config).await?;
+
+    let sessions: Vec<DriverSession> = if pool.fetch_modes.contains(&FetchMode::Browser) {
+        try_join_all((0..config.concurrency).map(|_| initialize_driver())).await?
+    } else {
+        Vec::new()
+    };
+    let browser_fetchers: Vec<Option<WebDriverFetcher>> = if sessions.is_empty() {
+        (0..config.concurrency).map(|_| None).collect()
+    } else {
+        sessions
+            .iter()
+            .map(|session| Some(WebDriverFetcher::new(session)))
+            .collect()
+    };
+    println!(
+        "Scraping with {} workers and {} browser sessions",
+        browser_fetchers.len(),
+        sessions.len()
+    );
+
+    try_join_all(browser_fetchers.iter().map(|browser_fetcher| {
+        pool.work(
+            browser_fetcher
+                .as_ref()
+                .map(|browser_fetcher| browser_fetcher as &dyn PageFetcher),
+        )
+    }))
+    .await?;
+
+    pool.finish().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schemas::listing::PropertyKind;
+    use crate::scrappers::fake_fetcher::FakeFetcher;
+    use crate::scrappers::remax_scrapper::RemaxScrapper;
+    use crate::scrappers::scrapper::Scrapper;
+    use crate::storage::memory_storage::MemoryStorage;
+    use crate::utils::time_utils::unix_timestamp;
+
+    fn search_page(url_ids: &[&str]) -> String {
+        let links: String = url_ids
+            .iter()
+            .map(|url_id| format!("<a href=\"{}\">{}</a>", url_id, url_id))
+            .collect();
+        format!(
+            "<html><body><div class=\"pl-0\">{}</div></body></html>",
+            links
+        )
+    }
+
+    fn checkpoint_dir(name: &str) -> String {
+        env::temp_dir()
+            .join(format!("res-checkpoints-{}-{}", name, unix_timestamp()))
+            .to_string_lossy()
+            .to_string()
+    }
+
+    #[tokio::test]
+    async fn stores_listings_in_order_and_scrapes_them_once() {
+        let search: SearchSpec = SearchSpec {
+            property_kinds: vec![PropertyKind::Apartment],
+            districts: vec!["lisboa".to_string(), "porto".to_string()],
+            ..SearchSpec::default()
+        };
+        let segments: Vec<String> = RemaxScrapper.segments(&search);
+        let page_url = |segment_index: usize| {
+            RemaxScrapper.listing_page_url(&search, &segments[segment_index], 1)
+        };
+        let listing = |fetcher: FakeFetcher, url_id: &str, latency: u64| {
+            fetcher
+                .fixture(
+                    &RemaxScrapper.listing_url(url_id),
+                    "remax",
+                    "listing_1.html",
+                )
+                .latency(
+                    &RemaxScrapper.listing_url(url_id),
+                    Duration::from_millis(latency),
+                )
+        };
+
+        // The slowest listings are queued first, the one in both districts is only queued once
+        let mut fetcher: FakeFetcher = FakeFetcher::new()
+            .page(
+                &page_url(0),
+                &search_page(&["lisboa-1", "lisboa-2", "both", "seen"]),
+            )
+            .page(&page_url(1), &search_page(&["both", "porto-1"]))
+            .latency(&page_url(1), Duration::from_millis(100));
+        for (url_id, latency) in [
+            ("lisboa-1", 300),
+            ("lisboa-2", 200),
+            ("both", 0),
+            ("porto-1", 0),
+        ] {
+            fetcher = listing(fetcher, url_id, latency);
+        }
+        let storage: MemoryStorage = MemoryStorage::default();
+        storage.mark_seen("remax", "seen");
+        let checkpoint_dir: String = checkpoint_dir("order");
+        let checkpoints: CheckpointStore = CheckpointStore::new(&checkpoint_dir);
+        let config: PoolConfig = PoolConfig {
+            concurrency: 4,
+            host_concurrency: 4,
+            host_delay: Duration::ZERO,
+            stop_after_known: None,
+        };
+
+        let pool: ScrapePool =
+            ScrapePool::start(&[&RemaxScrapper], &storage, &checkpoints, &search, &config)
+                .await
+                .unwrap();
+        try_join_all((0..config.concurrency).map(|_| pool.work(Some(&fetcher))))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            storage.saved(),
+            vec!["lisboa-1", "lisboa-2", "both", "porto-1"]
+        );
+        assert_eq!(fetcher.fetches(&RemaxScrapper.listing_url("both")), 1);
+        assert_eq!(fetcher.fetches(&RemaxScrapper.listing_url("seen")), 0);
+        pool.clear_finished_checkpoints().await.unwrap();
+        assert!(checkpoints.get("remax").await.unwrap().is_none());
+
+        let _ = tokio::fs::remove_dir_all(&checkpoint_dir).await;
+    }
+
+    /// Crawls Lisboa, newest first, over two known listings, and Porto showing the new listings
+    /// of Lisboa again. Returns the fetcher to count the listing pages fetched.
+    async fn crawl_known_listings(stop_after_known: Option<usize>) -> FakeFetcher {
+        let search: SearchSpec = SearchSpec {
+            property_kinds: vec![PropertyKind::Apartment],
+            districts: vec!["lisboa".to_string(), "porto".to_string()],
+            ..SearchSpec::default()
+        };
+        let segments: Vec<String> = RemaxScrapper.segments(&search);
+        let page_url = |segment_index: usize, page: u32| {
+            RemaxScrapper.listing_page_url(&search, &segments[segment_index], page)
+        };
+        // The new listings are still being scrapped when Porto shows them
+        let mut fetcher: FakeFetcher = FakeFetcher::new()
+            .page(
+                &page_url(0, 1),
+                &search_page(&["new-1", "new-2", "known-1"]),
+            )
+            .page(&page_url(0, 2), &search_page(&["known-2", "known-3"]))
+            .page(&page_url(0, 3), &search_page(&["known-4"]))
+            .page(&page_url(1, 1), &search_page(&["new-1", "new-2"]))
+            .latency(&page_url(1, 1), Duration::from_millis(100));
+        for url_id in ["new-1", "new-2"] {
+            fetcher = fetcher
+                .fixture(
+                    &RemaxScrapper.listing_url(url_id),
+                    "remax",
+                    "listing_1.html",
+                )
+                .latency(
+                    &RemaxScrapper.listing_url(url_id),
+                    Duration::from_millis(300),
+                );
+        }
+        let storage: MemoryStorage = MemoryStorage::default();
+        for url_id in ["known-1", "known-2", "known-3", "known-4"] {
+            storage.mark_seen("remax", url_id);
+        }
+        let checkpoint_dir: String = checkpoint_dir("known");
+        let checkpoints: CheckpointStore = CheckpointStore::new(&checkpoint_dir);
+        let config: PoolConfig = PoolConfig {
+            concurrency: 4,
+            host_concurrency: 4,
+            host_delay: Duration::ZERO,
+            stop_after_known,
+        };
+
+        let pool: ScrapePool =
+            ScrapePool::start(&[&RemaxScrapper], &storage, &checkpoints, &search, &config)
+                .await
+                .unwrap();
+        try_join_all((0..config.concurrency).map(|_| pool.work(Some(&fetcher))))
+            .await
+            .unwrap();
+
+        assert_eq!(storage.saved(), vec!["new-1", "new-2"]);
+        let _ = tokio::fs::remove_dir_all(&checkpoint_dir).await;
+        fetcher
+    }
+
+    #[tokio::test]
+    async fn stops_an_incremental_crawl_after_known_listings() {
+        let search: SearchSpec = SearchSpec {
+            property_kinds: vec![PropertyKind::Apartment],
+            districts: vec!["lisboa".to_string(), "porto".to_string()],
+            ..SearchSpec::default()
+        };
+        let segments: Vec<String> = RemaxScrapper.segments(&search);
+        let page_url = |segment_index: usize, page: u32| {
+            RemaxScrapper.listing_page_url(&search, &segments[segment_index], page)
+        };
+
+        let incremental: FakeFetcher = crawl_known_listings(Some(2)).await;
+        let full: FakeFetcher = crawl_known_listings(None).await;
+
+        // Lisboa stops after known-2 and known-3, Porto showing only queued listings goes on
+        assert_eq!(incremental.fetches(&page_url(0, 2)), 1);
+        assert_eq!(incremental.fetches(&page_url(0, 3)), 0);
+        assert_eq!(incremental.fetches(&page_url(1, 2)), 1);
+        assert_eq!(full.fetches(&page_url(0, 3)), 1);
+        assert_eq!(full.fetches(&page_url(0, 4)), 1);
+    }
+
+    #[tokio::test]
+    async fn keeps_the_checkpoint_on_a_failed_page() {
+        let search: SearchSpec = SearchSpec {
+            property_kinds: vec![PropertyKind::Apartment],
+            districts: vec!["lisboa".to_string(), "porto".to_string()],
+            ..SearchSpec::default()
+        };
+        let segments: Vec<String> = RemaxScrapper.segments(&search);
+        let page_url = |segment_index: usize, page: u32| {
+            RemaxScrapper.listing_page_url(&search, &segments[segment_index], page)
+        };
+        // Lisboa fails on its second page while Porto is crawled to its end
+        let fetcher: FakeFetcher = FakeFetcher::new()
+            .page(&page_url(0, 1), &search_page(&["lisboa-1"]))
+            .error(&page_url(0, 2), || {
+                ScrapperError::Extraction("connection reset".to_string())
+            })
+            .page(&page_url(1, 1), &search_page(&["porto-1"]))
+            .page(&page_url(1, 2), &search_page(&[]))
+            .latency(&page_url(1, 1), Duration::from_millis(50))
+            .fixture(
+                &RemaxScrapper.listing_url("lisboa-1"),
+                "remax",
+                "listing_1.html",
+            )
+            .fixture(
+                &RemaxScrapper.listing_url("porto-1"),
+                "remax",
+                "listing_1.html",
+            );
+        let storage: MemoryStorage = MemoryStorage::default();
+        let checkpoint_dir: String = checkpoint_dir("failed-page");
+        let checkpoints: CheckpointStore = CheckpointStore::new(&checkpoint_dir);
+        let config: PoolConfig = PoolConfig {
+            concurrency: 2,
+            host_concurrency: 2,
+            host_delay: Duration::ZERO,
+            stop_after_known: None,
+        };
+
+        let pool: ScrapePool =
+            ScrapePool::start(&[&RemaxScrapper], &storage, &checkpoints, &search, &config)
+                .await
+                .unwrap();
+        try_join_all((0..config.concurrency).map(|_| pool.work(Some(&fetcher))))
+            .await
+            .unwrap();
+        pool.clear_finished_checkpoints().await.unwrap();
+
+        let checkpoint: Checkpoint = checkpoints.get("remax").await.unwrap().unwrap();
+        assert_eq!(
+            (checkpoint.segment, checkpoint.page),
+            (segments[0].clone(), 2)
+        );
+        assert_eq!(storage.saved(), vec!["lisboa-1", "porto-1"]);
+
+        let _ = tokio::fs::remove_dir_all(&checkpoint_dir).await;
+    }
+}
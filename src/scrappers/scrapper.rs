@@ -0,0 +1,673 @@
+use crate::schemas::listing::{Listing, Normalize, Transaction};
+use crate::scrappers::driver::{initialize_driver, DriverSession};
+use crate::scrappers::html_page::{FetchedPage, FieldSelector, HtmlPage};
+use crate::scrappers::page_fetcher::{FetchMode, HttpFetcher, PageFetcher, WebDriverFetcher};
+use crate::scrappers::page_wait::PageWait;
+use crate::scrappers::scrape_health::SelectorsBroken;
+use crate::scrappers::scrape_pool::{self, PoolConfig};
+use crate::scrappers::scrapper_error::ScrapperError;
+use crate::scrappers::search_spec::SearchSpec;
+use crate::scrappers::{
+    era_scrapper::EraScrapper, idealista_scrapper::IdealistaScrapper,
+    imovirtual_scrapper::ImovirtualScrapper, remax_scrapper::RemaxScrapper,
+    supercasas_scrapper::SuperCasasScrapper,
+};
+use crate::storage::checkpoint_store::CheckpointStore;
+use crate::storage::listing_storage::{open_storage, ListingStorage};
+use crate::storage::page_archive::PageArchive;
+use crate::utils::time_utils::{unix_timestamp, SECONDS_PER_DAY};
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::error::Error;
+use std::time::Duration;
+use tokio::time::timeout;
+use tokio_retry::strategy::ExponentialBackoff;
+use tokio_retry::{Retry, RetryIf};
+use url::Url;
+
+/// How long the url ids of a listing page, retries included, may take before the page is failed
+const URL_IDS_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Everything a portal needs to provide to be crawled by the [`scrape_pool`]. The extraction
+/// works on the Html of the page so it is shared by every [`FetchMode`].
+pub trait Scrapper: Send + Sync {
+    type ListingRaw: Serialize + DeserializeOwned + Normalize + Send;
+
+    /// Name of the portal, used as `MODE` and as prefix of the output files
+    fn portal(&self) -> &'static str;
+
+    /// Search segments that are paginated independently, e.g. the property kinds and districts
+    /// of the search
+    fn segments(&self, _search: &SearchSpec) -> Vec<String> {
+        vec![String::new()]
+    }
+
+    /// Url of a page of the segment, with the filters of the search the portal supports
+    fn listing_page_url(&self, search: &SearchSpec, segment: &str, page: u32) -> String;
+
+    /// Whether the listing pages show the newest listings first, so an incremental crawl can stop
+    /// once it reaches the listings it already knows
+    fn newest_first(&self) -> bool {
+        false
+    }
+
+    fn listing_url(&self, url_id: &str) -> String;
+
+    /// Every Css selector of the portal, kept in one place at the top of its file. Where the portal
+    /// keeps every field, the place to update when it changes its markup.
+    fn selectors(&self) -> Vec<FieldSelector>;
+
+    /// How the pages are downloaded unless `FETCH_MODES` says otherwise
+    fn default_fetch_mode(&self) -> FetchMode {
+        FetchMode::Browser
+    }
+
+    /// What a listing page has to show before extracting the url ids
+    fn listing_page_wait(&self) -> PageWait {
+        PageWait::document_ready()
+    }
+
+    /// What a listing has to show before extracting it
+    fn listing_wait(&self) -> PageWait {
+        PageWait::document_ready()
+    }
+
+    /// Extra attempts at getting the url ids of a page
+    fn url_ids_retries(&self) -> usize {
+        0
+    }
+
+    /// Extra attempts at getting a listing
+    fn listing_retries(&self) -> usize {
+        0
+    }
+
+    /// Extra attempts at the whole scrapper mechanism
+    fn run_retries(&self) -> usize {
+        0
+    }
+
+    /// Extracts the url ids of a listing page, empty while it is still loading
+    fn get_url_ids(&self, html_page: &HtmlPage) -> Result<Vec<String>, ScrapperError>;
+
+    /// Extracts the listing of its page
+    fn get_listing(
+        &self,
+        html_page: &HtmlPage,
+        url_id: String,
+    ) -> Result<Self::ListingRaw, ScrapperError>;
+
+    /// Texts only present in the page the portal shows instead of a removed listing
+    fn listing_gone_markers(&self) -> Vec<&'static str> {
+        Vec::new()
+    }
+
+    /// Why the listing page is gone, if it answered not found, was redirected, is a not found
+    /// page or contains one of the [`Scrapper::listing_gone_markers`]
+    fn listing_gone_reason(&self, html_page: &HtmlPage, url_id: &str) -> Option<String> {
+        if let Some(status @ (404 | 410)) = html_page.status() {
+            return Some(format!("answered {}", status));
+        }
+
+        let current_path: String = match Url::parse(html_page.url()) {
+            Ok(current_url) => normalize_path(current_url.path()),
+            Err(_) => normalize_path(html_page.url()),
+        };
+        let listing_path: String = match Url::parse(&self.listing_url(url_id)) {
+            Ok(listing_url) => normalize_path(listing_url.path()),
+            Err(_) => normalize_path(url_id),
+        };
+
+        if current_path != listing_path {
+            return Some(format!("redirected to {}", html_page.url()));
+        }
+
+        let title: String = html_page.title().to_lowercase();
+        if title.contains("404") || title.contains("não encontrad") || title.contains("not found")
+        {
+            return Some(format!("not found page \"{}\"", title));
+        }
+
+        self.listing_gone_markers()
+            .iter()
+            .find(|marker| html_page.source().contains(*marker))
+            .map(|marker| format!("page contains \"{}\"", marker))
+    }
+
+    /// Whether the listing page is past the last page of the segment
+    fn is_last_page(
+        &self,
+        _html_page: &HtmlPage,
+        _segment: &str,
+        _page: u32,
+        _url_ids: &[String],
+        _previous_url_ids: &[String],
+    ) -> Result<bool, ScrapperError> {
+        Ok(false)
+    }
+}
+
+/// Object safe view of a [`Scrapper`] so portals can be kept in a registry.
+#[async_trait]
+pub trait PortalScrapper: Send + Sync {
+    fn portal(&self) -> &'static str;
+
+    /// Parses a line of the portal output file into a normalized [`Listing`]
+    fn normalize_line(&self, line: &str) -> serde_json::Result<Listing>;
+
+    /// Host of the portal, the politeness limits of the scrape pool apply per host
+    fn host(&self) -> String;
+
+    fn segment_names(&self, search: &SearchSpec) -> Vec<String>;
+
+    fn field_selectors(&self) -> Vec<FieldSelector>;
+
+    fn newest_first(&self) -> bool;
+
+    fn portal_run_retries(&self) -> usize;
+
+    /// How the pages are downloaded, the default of the portal or the one of `FETCH_MODES`
+    fn fetch_mode(&self) -> Result<FetchMode, Box<dyn Error + Send + Sync>>;
+
+    /// Loads a listing page and returns its url ids, retrying and giving up after 30 seconds.
+    /// `None` once past the last page of the segment.
+    async fn page_url_ids(
+        &self,
+        fetcher: &dyn PageFetcher,
+        search: &SearchSpec,
+        segment: &str,
+        page: u32,
+        previous_url_ids: &[String],
+    ) -> Result<Option<Vec<String>>, ScrapperError>;
+
+    /// Loads a listing, archiving its page when there is an `archive`, and returns its Json and
+    /// its normalized [`Listing`]
+    async fn scrape_listing(
+        &self,
+        fetcher: &dyn PageFetcher,
+        archive: Option<&PageArchive>,
+        url_id: &str,
+    ) -> Result<(String, Listing), ScrapperError>;
+
+    /// Extracts a listing from a page fetched before, like the archived ones
+    fn extract_listing(
+        &self,
+        fetched_page: &FetchedPage,
+        url_id: &str,
+    ) -> Result<(String, Listing), ScrapperError>;
+
+    async fn run(&self);
+
+    async fn revisit(&self, revisit_after_days: i64);
+}
+
+#[async_trait]
+impl<S: Scrapper> PortalScrapper for S {
+    fn portal(&self) -> &'static str {
+        Scrapper::portal(self)
+    }
+
+    fn normalize_line(&self, line: &str) -> serde_json::Result<Listing> {
+        serde_json::from_str::<S::ListingRaw>(line).map(|listing_raw| listing_raw.normalize())
+    }
+
+    fn host(&self) -> String {
+        Url::parse(&self.listing_url(""))
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+            .unwrap_or(Scrapper::portal(self).to_string())
+    }
+
+    fn segment_names(&self, search: &SearchSpec) -> Vec<String> {
+        self.segments(search)
+    }
+
+    fn field_selectors(&self) -> Vec<FieldSelector> {
+        self.selectors()
+    }
+
+    fn newest_first(&self) -> bool {
+        Scrapper::newest_first(self)
+    }
+
+    fn portal_run_retries(&self) -> usize {
+        self.run_retries()
+    }
+
+    fn fetch_mode(&self) -> Result<FetchMode, Box<dyn Error + Send + Sync>> {
+        Ok(FetchMode::from_env(Scrapper::portal(self))?.unwrap_or(self.default_fetch_mode()))
+    }
+
+    async fn page_url_ids(
+        &self,
+        fetcher: &dyn PageFetcher,
+        search: &SearchSpec,
+        segment: &str,
+        page: u32,
+        previous_url_ids: &[String],
+    ) -> Result<Option<Vec<String>>, ScrapperError> {
+        match timeout(
+            URL_IDS_TIMEOUT,
+            Retry::spawn(retry_strategy(self.url_ids_retries()), || {
+                fetch_url_ids(self, fetcher, search, segment, page, previous_url_ids)
+            }),
+        )
+        .await
+        {
+            Ok(url_ids) => url_ids,
+            Err(_) => Err(ScrapperError::PageNeverLoaded {
+                url: self.listing_page_url(search, segment, page),
+                waiting_for: format!("its url ids for {}s", URL_IDS_TIMEOUT.as_secs()),
+            }),
+        }
+    }
+
+    async fn scrape_listing(
+        &self,
+        fetcher: &dyn PageFetcher,
+        archive: Option<&PageArchive>,
+        url_id: &str,
+    ) -> Result<(String, Listing), ScrapperError> {
+        let listing_raw: S::ListingRaw =
+            fetch_listing_with_retries(self, fetcher, archive, url_id).await?;
+        Ok((json!(listing_raw).to_string(), listing_raw.normalize()))
+    }
+
+    fn extract_listing(
+        &self,
+        fetched_page: &FetchedPage,
+        url_id: &str,
+    ) -> Result<(String, Listing), ScrapperError> {
+        let listing_raw: S::ListingRaw = extract_listing(self, fetched_page, url_id.to_string())?;
+        Ok((json!(listing_raw).to_string(), listing_raw.normalize()))
+    }
+
+    async fn run(&self) {
+        run(self).await
+    }
+
+    async fn revisit(&self, revisit_after_days: i64) {
+        revisit(self, revisit_after_days).await
+    }
+}
+
+/// Marks a listing scraped from a rental search as a rental, also in the `transaction` of its raw
+/// Json so it is still one when normalized again
+pub fn mark_rental((listing_raw, mut listing): (String, Listing)) -> (String, Listing) {
+    listing.transaction = Transaction::Rent;
+    let listing_raw: String = match serde_json::from_str::<Value>(&listing_raw) {
+        Ok(Value::Object(mut fields)) => {
+            fields.insert("transaction".to_string(), json!(Transaction::Rent));
+            Value::Object(fields).to_string()
+        }
+        _ => listing_raw,
+    };
+    (listing_raw, listing)
+}
+
+pub fn scrapper_registry() -> Vec<Box<dyn PortalScrapper>> {
+    vec![
+        Box::new(RemaxScrapper),
+        Box::new(EraScrapper),
+        Box::new(SuperCasasScrapper),
+        Box::new(ImovirtualScrapper),
+        Box::new(IdealistaScrapper),
+    ]
+}
+
+fn retry_strategy(retries: usize) -> impl Iterator<Item = Duration> {
+    ExponentialBackoff::from_millis(500)
+        .max_delay(Duration::from_secs(30))
+        .take(retries)
+}
+
+/// Loads a listing page and extracts its url ids, `None` once past the last page of the segment.
+/// A page that never loaded is only an error when it is not past the last page.
+async fn fetch_url_ids<S: Scrapper>(
+    scrapper: &S,
+    fetcher: &dyn PageFetcher,
+    search: &SearchSpec,
+    segment: &str,
+    page: u32,
+    previous_url_ids: &[String],
+) -> Result<Option<Vec<String>>, ScrapperError> {
+    let fetched_page: FetchedPage = fetcher
+        .fetch(
+            &scrapper.listing_page_url(search, segment, page),
+            &scrapper.listing_page_wait(),
+        )
+        .await?;
+    let html_page: HtmlPage = HtmlPage::parse(&fetched_page);
+
+    if let Some(waiting_for) = &fetched_page.never_loaded {
+        return match scrapper.is_last_page(&html_page, segment, page, &[], previous_url_ids) {
+            Ok(true) => Ok(None),
+            _ => Err(ScrapperError::PageNeverLoaded {
+                url: fetched_page.url.clone(),
+                waiting_for: waiting_for.clone(),
+            }),
+        };
+    }
+
+    let url_ids: Vec<String> = scrapper.get_url_ids(&html_page)?;
+    println!("{:?}", url_ids);
+    if scrapper.is_last_page(&html_page, segment, page, &url_ids, previous_url_ids)? {
+        return Ok(None);
+    }
+
+    Ok(Some(url_ids))
+}
+
+/// Ignores repeated and trailing slashes, e.g. `https://www.idealista.pt//imovel/1/`
+fn normalize_path(path: &str) -> String {
+    path.split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<&str>>()
+        .join("/")
+}
+
+fn extract_listing<S: Scrapper>(
+    scrapper: &S,
+    fetched_page: &FetchedPage,
+    url_id: String,
+) -> Result<S::ListingRaw, ScrapperError> {
+    let html_page: HtmlPage = HtmlPage::parse(fetched_page);
+
+    if let Some(reason) = scrapper.listing_gone_reason(&html_page, &url_id) {
+        return Err(ScrapperError::ListingGone(reason));
+    }
+    // Checked after whether it is gone, the page of a removed listing never shows its fields
+    if let Some(waiting_for) = &fetched_page.never_loaded {
+        return Err(ScrapperError::PageNeverLoaded {
+            url: fetched_page.url.clone(),
+            waiting_for: waiting_for.clone(),
+        });
+    }
+
+    scrapper.get_listing(&html_page, url_id)
+}
+
+async fn fetch_listing<S: Scrapper>(
+    scrapper: &S,
+    fetcher: &dyn PageFetcher,
+    archive: Option<&PageArchive>,
+    url_id: String,
+) -> Result<S::ListingRaw, ScrapperError> {
+    let fetched_page: FetchedPage = fetcher
+        .fetch(&scrapper.listing_url(&url_id), &scrapper.listing_wait())
+        .await?;
+
+    // Archived before extracting, the pages the extraction fails on are the interesting ones
+    if let Some(archive) = archive {
+        if let Err(e) = archive
+            .save(scrapper.portal(), &url_id, &fetched_page)
+            .await
+        {
+            println!("Failed to archive listing {}: {}", url_id, e);
+        }
+    }
+
+    extract_listing(scrapper, &fetched_page, url_id)
+}
+
+/// Retries fetching a listing unless it is gone, which retrying would not change
+async fn fetch_listing_with_retries<S: Scrapper>(
+    scrapper: &S,
+    fetcher: &dyn PageFetcher,
+    archive: Option<&PageArchive>,
+    url_id: &str,
+) -> Result<S::ListingRaw, ScrapperError> {
+    RetryIf::spawn(
+        retry_strategy(scrapper.listing_retries()),
+        || fetch_listing(scrapper, fetcher, archive, url_id.to_string()),
+        |e: &ScrapperError| !e.is_listing_gone(),
+    )
+    .await
+}
+
+pub async fn run<S: Scrapper>(scrapper: &S) {
+    let portal: &str = scrapper.portal();
+    let storage: Box<dyn ListingStorage> = match open_storage() {
+        Ok(storage) => storage,
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            return;
+        }
+    };
+
+    match RetryIf::spawn(
+        retry_strategy(scrapper.run_retries()),
+        || async {
+            println!("Running {} scrapper mechanism", portal);
+            scrape_portals(&[scrapper as &dyn PortalScrapper], storage.as_ref()).await
+        },
+        |e: &_| is_worth_retrying(Box::as_ref(e)),
+    )
+    .await
+    {
+        Ok(_) => println!("{} scrapper mechanism finished", portal),
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+        }
+    }
+}
+
+/// Broken selectors fail the same way until they are fixed
+fn is_worth_retrying(e: &(dyn Error + Send + Sync + 'static)) -> bool {
+    !e.is::<SelectorsBroken>()
+}
+
+async fn scrape_portals(
+    scrappers: &[&dyn PortalScrapper],
+    storage: &dyn ListingStorage,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let checkpoints: CheckpointStore = CheckpointStore::from_env();
+    scrape_pool::scrape(
+        scrappers,
+        storage,
+        &checkpoints,
+        &SearchSpec::from_env()?,
+        &PoolConfig::from_env()?,
+    )
+    .await
+}
+
+/// Scrapes several portals at once sharing the browser sessions of the pool
+pub async fn run_all(scrappers: &[&dyn PortalScrapper]) {
+    let storage: Box<dyn ListingStorage> = match open_storage() {
+        Ok(storage) => storage,
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            return;
+        }
+    };
+    let portals: Vec<&str> = scrappers.iter().map(|scrapper| scrapper.portal()).collect();
+    let run_retries: usize = scrappers
+        .iter()
+        .map(|scrapper| scrapper.portal_run_retries())
+        .max()
+        .unwrap_or(0);
+
+    match RetryIf::spawn(
+        retry_strategy(run_retries),
+        || async {
+            println!("Running {} scrapper mechanism", portals.join(", "));
+            scrape_portals(scrappers, storage.as_ref()).await
+        },
+        |e: &_| is_worth_retrying(Box::as_ref(e)),
+    )
+    .await
+    {
+        Ok(_) => println!("{} scrapper mechanism finished", portals.join(", ")),
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+        }
+    }
+}
+
+/// Stores a new observation of the listing, or its delisting when its page is gone. Any other error
+/// leaves it to be revisited on the next run.
+async fn revisit_listing<S: Scrapper>(
+    scrapper: &S,
+    storage: &dyn ListingStorage,
+    fetcher: &dyn PageFetcher,
+    archive: Option<&PageArchive>,
+    url_id: &str,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    println!("Url id: {}", url_id);
+
+    match fetch_listing_with_retries(scrapper, fetcher, archive, url_id).await {
+        Ok(listing_raw) => {
+            let events = storage
+                .save_observation(&json!(listing_raw).to_string(), &listing_raw.normalize())
+                .await?;
+
+            for event in events {
+                println!(
+                    "Url id: {} {} from {:?} to {:?}",
+                    url_id,
+                    event.kind.as_str(),
+                    event.old_value,
+                    event.new_value
+                );
+            }
+        }
+        Err(ScrapperError::ListingGone(reason)) => {
+            println!(
+                "Url id: {} is gone, marking as delisted: {}",
+                url_id, reason
+            );
+            storage.save_delisting(scrapper.portal(), url_id).await?;
+        }
+        Err(e) => println!("Url id: {} could not be fetched: {}", url_id, e),
+    };
+
+    Ok(())
+}
+
+/// Fetches again the listings not observed in the last `revisit_after_days` to track their changes
+pub async fn revisit_mechanism<S: Scrapper>(
+    scrapper: &S,
+    storage: &dyn ListingStorage,
+    revisit_after_days: i64,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let portal: &str = scrapper.portal();
+    let observed_before: i64 = unix_timestamp() - revisit_after_days * SECONDS_PER_DAY;
+    let url_ids: Vec<String> = storage.listings_to_revisit(portal, observed_before).await?;
+
+    println!("{} {} listings to revisit", url_ids.len(), portal);
+    if url_ids.is_empty() {
+        return Ok(());
+    }
+
+    let session: Option<DriverSession> = match PortalScrapper::fetch_mode(scrapper)? {
+        FetchMode::Browser => Some(initialize_driver().await?),
+        FetchMode::Http => None,
+    };
+    let browser_fetcher: Option<WebDriverFetcher> = session
+        .as_ref()
+        .map(|session| WebDriverFetcher::new(session));
+    let http_fetcher: HttpFetcher = HttpFetcher::from_env()?;
+    let fetcher: &dyn PageFetcher = match &browser_fetcher {
+        Some(browser_fetcher) => browser_fetcher,
+        None => &http_fetcher,
+    };
+    let archive: Option<PageArchive> = PageArchive::from_env();
+
+    for url_id in url_ids {
+        revisit_listing(scrapper, storage, fetcher, archive.as_ref(), &url_id).await?;
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+
+    Ok(())
+}
+
+pub async fn revisit<S: Scrapper>(scrapper: &S, revisit_after_days: i64) {
+    let portal: &str = scrapper.portal();
+
+    let result = match open_storage() {
+        Ok(storage) => revisit_mechanism(scrapper, storage.as_ref(), revisit_after_days).await,
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok(_) => println!("{} revisit mechanism finished", portal),
+        Err(e) => eprintln!("Error: {:?}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scrappers::fake_fetcher::FakeFetcher;
+    use crate::storage::memory_storage::MemoryStorage;
+    use thirtyfour::error::WebDriverError;
+
+    #[tokio::test]
+    async fn only_delists_listings_whose_page_is_gone() {
+        let url = |url_id: &str| RemaxScrapper.listing_url(url_id);
+        let fetcher: FakeFetcher = FakeFetcher::new()
+            .fixture(&url("122361016-55"), "remax", "listing_1.html")
+            .response(
+                &url("121371134-12"),
+                200,
+                "https://www.remax.pt/pt/comprar",
+                "<html><head><title>Comprar</title></head></html>",
+            )
+            .error(&url("122901001-7"), || {
+                ScrapperError::WebDriver(WebDriverError::RequestFailed(
+                    "connection refused".to_string(),
+                ))
+            })
+            .error(&url("122900002-3"), || {
+                ScrapperError::WebDriver(WebDriverError::Timeout("page load".to_string()))
+            })
+            // Still loading when the wait timed out
+            .page(
+                &url("122900003-4"),
+                "<html><body><main></main></body></html>",
+            );
+        let storage: MemoryStorage = MemoryStorage::default();
+
+        for url_id in [
+            "122361016-55",
+            "121371134-12",
+            "122901001-7",
+            "122900002-3",
+            "122900003-4",
+            "122900004-5",
+        ] {
+            revisit_listing(&RemaxScrapper, &storage, &fetcher, None, url_id)
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(storage.observed(), vec!["122361016-55"]);
+        // Redirected elsewhere and not found
+        assert_eq!(storage.delisted(), vec!["121371134-12", "122900004-5"]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn fails_listing_pages_that_never_answer() {
+        let search: SearchSpec = SearchSpec::default();
+        let segment: String = RemaxScrapper.segments(&search).remove(0);
+        let url: String = RemaxScrapper.listing_page_url(&search, &segment, 1);
+        let fetcher: FakeFetcher = FakeFetcher::new()
+            .fixture(&url, "remax", "search.html")
+            .latency(&url, Duration::from_secs(3600));
+
+        let url_ids: Result<Option<Vec<String>>, ScrapperError> =
+            PortalScrapper::page_url_ids(&RemaxScrapper, &fetcher, &search, &segment, 1, &[]).await;
+
+        match url_ids {
+            Err(ScrapperError::PageNeverLoaded {
+                url: failed_url, ..
+            }) => {
+                assert_eq!(failed_url, url)
+            }
+            other => panic!("expected the page to never load, got {:?}", other),
+        }
+    }
+}
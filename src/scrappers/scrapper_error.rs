@@ -0,0 +1,59 @@
+use std::fmt;
+use thirtyfour::error::WebDriverError;
+
+#[derive(Debug)]
+pub enum ScrapperError {
+    /// The listing page no longer exists, it was sold or withdrawn
+    ListingGone(String),
+    /// Anything else that went wrong talking to the browser, worth retrying
+    WebDriver(WebDriverError),
+    /// The page could not be downloaded, worth retrying
+    Http(reqwest::Error),
+    /// The page did not meet the conditions of its wait before it timed out
+    PageNeverLoaded { url: String, waiting_for: String },
+    /// No element matches the selector of a field, the page may not have finished loading or the
+    /// portal changed its markup
+    MissingField {
+        field: &'static str,
+        css: &'static str,
+    },
+    /// The page cannot be extracted for any other reason
+    Extraction(String),
+}
+
+impl ScrapperError {
+    pub fn is_listing_gone(&self) -> bool {
+        matches!(self, ScrapperError::ListingGone(_))
+    }
+}
+
+impl fmt::Display for ScrapperError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScrapperError::ListingGone(reason) => write!(f, "listing gone: {}", reason),
+            ScrapperError::WebDriver(e) => write!(f, "{}", e),
+            ScrapperError::Http(e) => write!(f, "{}", e),
+            ScrapperError::PageNeverLoaded { url, waiting_for } => {
+                write!(f, "page {} never loaded, waiting for {}", url, waiting_for)
+            }
+            ScrapperError::MissingField { field, css } => {
+                write!(f, "no element matches `{}` for {}", css, field)
+            }
+            ScrapperError::Extraction(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl std::error::Error for ScrapperError {}
+
+impl From<WebDriverError> for ScrapperError {
+    fn from(e: WebDriverError) -> Self {
+        ScrapperError::WebDriver(e)
+    }
+}
+
+impl From<reqwest::Error> for ScrapperError {
+    fn from(e: reqwest::Error) -> Self {
+        ScrapperError::Http(e)
+    }
+}
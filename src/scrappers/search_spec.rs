@@ -0,0 +1,179 @@
+use crate::schemas::listing::{PropertyKind, Transaction, Typology};
+use crate::scrappers::scrapper_utils::PORTUGUESE_DISTRICTS;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::error::Error;
+
+/// What the scrappers search for, each portal translates it to its own search urls. Every filter
+/// left out is not applied.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct SearchSpec {
+    pub transaction: Transaction,
+    pub property_kinds: Vec<PropertyKind>,
+    /// In euros, the monthly rent when renting
+    pub min_price: Option<u64>,
+    pub max_price: Option<u64>,
+    /// In m²
+    pub min_area: Option<u32>,
+    pub max_area: Option<u32>,
+    pub typologies: Vec<Typology>,
+    /// Slugs of [`PORTUGUESE_DISTRICTS`], the whole country when empty
+    pub districts: Vec<String>,
+}
+
+impl Default for SearchSpec {
+    /// Homes for sale in the whole country
+    fn default() -> SearchSpec {
+        SearchSpec {
+            transaction: Transaction::Buy,
+            property_kinds: vec![PropertyKind::Apartment, PropertyKind::House],
+            min_price: None,
+            max_price: None,
+            min_area: None,
+            max_area: None,
+            typologies: Vec::new(),
+            districts: Vec::new(),
+        }
+    }
+}
+
+impl SearchSpec {
+    /// The Json spec at `SEARCH_SPEC_PATH`, homes for sale in the whole country when it is not set
+    pub fn from_env() -> Result<SearchSpec, Box<dyn Error + Send + Sync>> {
+        let search: SearchSpec = match env::var("SEARCH_SPEC_PATH") {
+            Ok(path) => serde_json::from_str(&std::fs::read_to_string(&path)?)
+                .map_err(|e| format!("{} is not a valid search spec: {}", path, e))?,
+            Err(_) => SearchSpec::default(),
+        };
+        search.validate()?;
+        Ok(search)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.property_kinds.is_empty() {
+            return Err("the search spec should have at least one property kind".to_string());
+        }
+        if let Some(district) = self
+            .districts
+            .iter()
+            .find(|district| !PORTUGUESE_DISTRICTS.contains(&district.as_str()))
+        {
+            return Err(format!(
+                "`{}` is not a district, use one of {}",
+                district,
+                PORTUGUESE_DISTRICTS.join(", ")
+            ));
+        }
+        if self
+            .min_price
+            .zip(self.max_price)
+            .is_some_and(|(min, max)| min > max)
+        {
+            return Err("the min price of the search spec is above its max price".to_string());
+        }
+        if self
+            .min_area
+            .zip(self.max_area)
+            .is_some_and(|(min, max)| min > max)
+        {
+            return Err("the min area of the search spec is above its max area".to_string());
+        }
+        Ok(())
+    }
+
+    /// The districts searched, every one of them when the spec has none
+    pub fn districts(&self) -> Vec<&str> {
+        match self.districts.is_empty() {
+            true => PORTUGUESE_DISTRICTS.to_vec(),
+            false => self
+                .districts
+                .iter()
+                .map(|district| district.as_str())
+                .collect(),
+        }
+    }
+
+    /// Whether both apartments and houses are searched, which some portals search together
+    pub fn has_homes(&self) -> bool {
+        self.property_kinds.contains(&PropertyKind::Apartment)
+            && self.property_kinds.contains(&PropertyKind::House)
+    }
+
+    /// The set bounds of the price and area ranges, named by the portal in the order min price,
+    /// max price, min area and max area
+    pub fn range_filters(&self, names: [&'static str; 4]) -> Vec<(&'static str, u64)> {
+        let bounds: [Option<u64>; 4] = [
+            self.min_price,
+            self.max_price,
+            self.min_area.map(u64::from),
+            self.max_area.map(u64::from),
+        ];
+
+        names
+            .into_iter()
+            .zip(bounds)
+            .filter_map(|(name, bound)| bound.map(|bound| (name, bound)))
+            .collect()
+    }
+
+    pub fn bedrooms(&self) -> Vec<u32> {
+        self.typologies
+            .iter()
+            .map(|typology| typology.no_bedrooms())
+            .collect()
+    }
+
+    /// Names the portal gives to the property kinds searched, the ones it has no name for are
+    /// left out with a message
+    pub fn portal_kinds(
+        &self,
+        portal: &str,
+        name: impl Fn(PropertyKind) -> Option<&'static str>,
+    ) -> Vec<&'static str> {
+        let mut names: Vec<&'static str> = Vec::new();
+        for property_kind in &self.property_kinds {
+            match name(*property_kind) {
+                Some(kind_name) if !names.contains(&kind_name) => names.push(kind_name),
+                Some(_) => {}
+                None => println!("{} has no search for {:?} listings", portal, property_kind),
+            }
+        }
+        names
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_partial_specs() {
+        let search: SearchSpec = serde_json::from_str(
+            r#"{"transaction": "rent", "typologies": ["T1", "T2"], "districts": ["lisboa"]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(search.transaction, Transaction::Rent);
+        assert_eq!(search.property_kinds, SearchSpec::default().property_kinds);
+        assert_eq!(search.typologies, vec![Typology::T1, Typology::T2]);
+        assert_eq!(search.districts(), vec!["lisboa"]);
+        assert_eq!(search.validate(), Ok(()));
+    }
+
+    #[test]
+    fn rejects_invalid_specs() {
+        let unknown_district: SearchSpec = SearchSpec {
+            districts: vec!["madrid".to_string()],
+            ..SearchSpec::default()
+        };
+        let empty_price_range: SearchSpec = SearchSpec {
+            min_price: Some(300_000),
+            max_price: Some(100_000),
+            ..SearchSpec::default()
+        };
+
+        assert!(unknown_district.validate().is_err());
+        assert!(empty_price_range.validate().is_err());
+    }
+}
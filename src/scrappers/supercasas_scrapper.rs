@@ -1,190 +1,351 @@
-use serde_json::json;
+use scraper::ElementRef;
 use std::time::Duration;
-use thirtyfour::{By, WebDriver, WebElement};
 
+use crate::schemas::listing::{PropertyKind, Typology};
 use crate::schemas::supercasas_listing_raw::SuperCasasListingRaw;
-use crate::scrappers::driver::initialize_driver;
-use crate::scrappers::scrapper_utils::PORTUGUESE_DISTRICTS;
-use crate::utils::file_utils::*;
-use thirtyfour::error::WebDriverError;
-use tokio::fs::File;
-use tokio::time::timeout;
-use tokio_retry::strategy::FixedInterval;
-use tokio_retry::Retry;
-
-async fn get_url_ids(
-    web_driver: &WebDriver,
-    page: u32,
-    district: &str,
-) -> Result<Vec<String>, WebDriverError> {
-    let _ = web_driver
-        .get(format!(
-            "https://supercasa.pt/comprar-casas/{}-distrito/pagina-{}?ordem=atualizado-desc",
-            district, page
-        ))
-        .await;
-
-    tokio::time::sleep(Duration::from_millis(2500)).await;
-
-    let mut ids: Vec<String> = Vec::new();
-
-    // The site takes some time to load this information
-    while ids.is_empty() {
-        let parent_div: Vec<WebElement> = web_driver
-            .find_all(By::ClassName("property-list-title"))
-            .await?;
-
-        // Find all divs within it and get IDs
+use crate::scrappers::html_page::{attr, find_in, text, FieldSelector, HtmlPage};
+use crate::scrappers::page_wait::PageWait;
+use crate::scrappers::scrapper::Scrapper;
+use crate::scrappers::scrapper_error::ScrapperError;
+use crate::scrappers::search_spec::SearchSpec;
 
-        for div in parent_div {
-            let child_divs: WebElement = div.find(By::Tag("a")).await?;
-            ids.push(child_divs.attr("href").await?.unwrap());
-        }
+pub struct SuperCasasScrapper;
+
+const URL_IDS_TITLE: FieldSelector = FieldSelector::new("url_ids", ".property-list-title");
+const URL_IDS_LINK: FieldSelector = FieldSelector::new("url_ids", "a");
+const DESCRIPTION: FieldSelector =
+    FieldSelector::new("description", ".detail-info-description-txt");
+const DETAILS_ITEM: FieldSelector =
+    FieldSelector::new("details_split_by_string", ".detail-info-features-list");
+const PRICE: FieldSelector = FieldSelector::new("price", ".property-price span");
+const LOCATION: FieldSelector = FieldSelector::new("location", ".property-location");
+const SEARCH_RESULTS: FieldSelector = FieldSelector::new("pagination", ".home-search-content");
+
+/// Supercasa name of a property kind, apartments and houses searched together are homes
+fn supercasas_kind(property_kind: PropertyKind, homes: bool) -> Option<&'static str> {
+    match property_kind {
+        PropertyKind::Apartment | PropertyKind::House if homes => Some("casas"),
+        PropertyKind::Apartment => Some("apartamentos"),
+        PropertyKind::House => Some("moradias"),
+        PropertyKind::Land => Some("terrenos"),
+        PropertyKind::Shop => Some("lojas"),
+        PropertyKind::Office => Some("escritorios"),
+        PropertyKind::Warehouse => Some("armazens"),
+        PropertyKind::Garage => Some("garagens"),
+        PropertyKind::Farm => Some("quintas"),
+        PropertyKind::Building => Some("predios"),
     }
+}
+
+fn supercasas_typology(typology: Typology) -> &'static str {
+    match typology {
+        Typology::T0 => "t0",
+        Typology::T1 => "t1",
+        Typology::T2 => "t2",
+        Typology::T3 => "t3",
+        Typology::T4 => "t4",
+        Typology::T5 => "t5",
+        Typology::T6Plus => "t6-ou-mais",
+    }
+}
 
-    println!("{:?}", ids);
+/// The filters go in the path, e.g. `com-preco-max-300000,t2/`
+fn supercasas_filters(search: &SearchSpec) -> String {
+    let mut filters: Vec<String> = search
+        .range_filters(["preco-min", "preco-max", "area-min", "area-max"])
+        .into_iter()
+        .map(|(key, value)| format!("{}-{}", key, value))
+        .collect();
+    filters.extend(
+        search
+            .typologies
+            .iter()
+            .map(|typology| supercasas_typology(*typology).to_string()),
+    );
 
-    Ok(ids)
+    match filters.is_empty() {
+        true => String::new(),
+        false => format!("com-{}/", filters.join(",")),
+    }
 }
 
-async fn get_listing(
-    web_driver: &WebDriver,
-    url_id: String,
-) -> Result<SuperCasasListingRaw, WebDriverError> {
-    let _ = web_driver
-        .get(format!("https://supercasa.pt{}", url_id))
-        .await;
-
-    tokio::time::sleep(Duration::from_millis(200)).await;
-
-    let description: Option<String> = match web_driver
-        .find(By::ClassName("detail-info-description-txt"))
-        .await
-    {
-        Ok(details_web_element) => Some(details_web_element.text().await?),
-        Err(_) => {
-            println!("Url {} did not have a description", url_id);
-            None
-        }
-    };
-    let details_vec = web_driver
-        .find_all(By::ClassName("detail-info-features-list"))
-        .await?;
+impl Scrapper for SuperCasasScrapper {
+    type ListingRaw = SuperCasasListingRaw;
 
-    let price: String = web_driver
-        .find(By::ClassName("property-price"))
-        .await?
-        .find(By::Tag("span"))
-        .await?
-        .text()
-        .await?;
+    fn portal(&self) -> &'static str {
+        "supercasas"
+    }
 
-    let mut details_split_by_string: Vec<String> = Vec::new();
+    // Searched per district, the portal only shows the first pages of a search
+    fn segments(&self, search: &SearchSpec) -> Vec<String> {
+        let homes: bool = search.has_homes();
 
-    for div in details_vec {
-        details_split_by_string.push(div.text().await?);
+        search
+            .portal_kinds(self.portal(), |property_kind| {
+                supercasas_kind(property_kind, homes)
+            })
+            .into_iter()
+            .flat_map(|kind| {
+                search.districts().into_iter().map(move |district| {
+                    format!(
+                        "{}-{}/{}-distrito",
+                        search.transaction.slug(),
+                        kind,
+                        district
+                    )
+                })
+            })
+            .collect()
     }
 
-    let supercasas_listing_raw = SuperCasasListingRaw {
-        price,
-        description,
-        details_split_by_string,
-        url_id,
-    };
+    fn listing_page_url(&self, search: &SearchSpec, segment: &str, page: u32) -> String {
+        format!(
+            "https://supercasa.pt/{}/{}pagina-{}?ordem=atualizado-desc",
+            segment,
+            supercasas_filters(search),
+            page
+        )
+    }
 
-    Ok(supercasas_listing_raw)
-}
+    fn newest_first(&self) -> bool {
+        true
+    }
+
+    fn listing_url(&self, url_id: &str) -> String {
+        format!("https://supercasa.pt{}", url_id)
+    }
+
+    fn selectors(&self) -> Vec<FieldSelector> {
+        vec![
+            URL_IDS_TITLE,
+            URL_IDS_LINK,
+            DESCRIPTION,
+            DETAILS_ITEM,
+            PRICE,
+            LOCATION,
+            SEARCH_RESULTS,
+        ]
+    }
+
+    // Past the last page there is only a message, so the results cannot be waited for
+    fn listing_page_wait(&self) -> PageWait {
+        PageWait::document_ready().network_idle(Duration::from_millis(500))
+    }
+
+    fn listing_wait(&self) -> PageWait {
+        PageWait::document_ready().element(PRICE)
+    }
 
-pub async fn supercasas_scrape_mechanism() -> Result<(), WebDriverError> {
-    let web_driver: WebDriver = initialize_driver().await?;
-    let supercasas_ids_read: File = get_file_read("supercasas_ids.txt").await?;
-    let mut supercasas_ids_write: File = get_file_write_append("supercasas_ids.txt").await?;
-    let mut supercasas_write: File = get_file_write_append("supercasas.json").await?;
-    let supercasas_ids: String = get_content_as_string(supercasas_ids_read).await?;
-
-    for district in PORTUGUESE_DISTRICTS {
-        for page in 1.. {
-            println!("\nScrapper mechanism page {}", page);
-
-            // If we cannot get the page loaded in 30 seconds we ignore it and move on
-            let url_ids_vec: Result<Vec<String>, WebDriverError> = timeout(
-                Duration::from_secs(30),
-                get_url_ids(&web_driver, page, district),
-            )
-            .await
-            .unwrap_or_else(|_| {
-                println!("Scrapper mechanism url_ids timed out after 30 seconds!");
-                Ok(Vec::new())
-            });
-
-            let current_page: String = web_driver.current_url().await?.to_string();
-            let elements_found: bool = match web_driver
-                .find(By::ClassName("home-search-content"))
-                .await
-            {
-                Ok(element) => match element.text().await {
-                    Ok(text) => !text.contains("Não encontrámos imóveis para o que procuras..."),
-                    Err(_) => true,
-                },
-                Err(_) => true,
-            };
-
-            if current_page.contains(district)
-                && current_page.contains(page.to_string().as_str())
-                && elements_found
-            {
-                match url_ids_vec {
-                    Ok(url_ids) => {
-                        for url_id in url_ids {
-                            if !supercasas_ids.contains(&url_id) {
-                                println!("Url id: {}", url_id);
-
-                                let supercasas_listing: SuperCasasListingRaw = Retry::spawn(
-                                    FixedInterval::from_millis(500).take(6),
-                                    || async { get_listing(&web_driver, url_id.clone()).await },
-                                )
-                                .await?;
-
-                                write_to_file(
-                                    &mut supercasas_write,
-                                    format!("{}\n", json!(supercasas_listing)),
-                                )
-                                .await?;
-
-                                write_to_file(
-                                    &mut supercasas_ids_write,
-                                    format!("{}\n", supercasas_listing.url_id),
-                                )
-                                .await?;
-
-                                tokio::time::sleep(Duration::from_millis(500)).await;
-                            } else {
-                                println!("Url id: {} is already scrapped", url_id);
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        println!("Error getting url_ids: {}", e);
-                        break;
-                    }
-                }
-            } else {
-                println!("No more pages after {} for district {}", page, district);
-                break;
+    fn listing_retries(&self) -> usize {
+        6
+    }
+
+    fn listing_gone_markers(&self) -> Vec<&'static str> {
+        vec!["Não encontrámos imóveis para o que procuras..."]
+    }
+
+    fn get_url_ids(&self, html_page: &HtmlPage) -> Result<Vec<String>, ScrapperError> {
+        let parent_div: Vec<ElementRef> = html_page.find_all(URL_IDS_TITLE)?;
+
+        let mut ids: Vec<String> = Vec::new();
+        for div in parent_div {
+            if let Some(href) = attr(find_in(div, URL_IDS_LINK)?, "href") {
+                ids.push(href);
             }
         }
+
+        Ok(ids)
+    }
+
+    fn get_listing(
+        &self,
+        html_page: &HtmlPage,
+        url_id: String,
+    ) -> Result<SuperCasasListingRaw, ScrapperError> {
+        let description: Option<String> = match html_page.find(DESCRIPTION) {
+            Ok(details_element) => Some(text(details_element)),
+            Err(_) => {
+                println!("Url {} did not have a description", url_id);
+                None
+            }
+        };
+        let details_vec: Vec<ElementRef> = html_page.find_all(DETAILS_ITEM)?;
+
+        let price: String = text(html_page.find(PRICE)?);
+
+        let details_split_by_string: Vec<String> = details_vec.into_iter().map(text).collect();
+
+        let location: Option<String> = html_page.find(LOCATION).ok().map(text);
+
+        let supercasas_listing_raw = SuperCasasListingRaw {
+            price,
+            description,
+            details_split_by_string,
+            url_id,
+            location,
+            transaction: None,
+        };
+
+        Ok(supercasas_listing_raw)
     }
 
-    Ok(())
+    fn is_last_page(
+        &self,
+        html_page: &HtmlPage,
+        segment: &str,
+        page: u32,
+        _url_ids: &[String],
+        _previous_url_ids: &[String],
+    ) -> Result<bool, ScrapperError> {
+        let current_page: &str = html_page.url();
+        let elements_found: bool = match html_page.find(SEARCH_RESULTS) {
+            Ok(element) => {
+                !text(element).contains("Não encontrámos imóveis para o que procuras...")
+            }
+            Err(_) => true,
+        };
+
+        Ok(!(current_page.contains(segment)
+            && current_page.contains(page.to_string().as_str())
+            && elements_found))
+    }
 }
 
-pub async fn run() {
-    match supercasas_scrape_mechanism().await {
-        Ok(_) => {
-            println!("SuperCasas scrapper mechanism finished")
-        }
-        Err(e) => {
-            eprintln!("Error: {:?}", e);
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schemas::listing::{Listing, Normalize};
+    use crate::scrappers::fixture_server::FixtureServer;
+    use crate::scrappers::html_page::FetchedPage;
+
+    #[tokio::test]
+    async fn extracts_url_ids_from_search_page() {
+        let server: FixtureServer = FixtureServer::start("supercasas").await;
+        let fetched: FetchedPage = server.fetch("search.html?segment=lisboa&page=1").await;
+        let html_page: HtmlPage = HtmlPage::parse(&fetched);
+
+        let url_ids: Vec<String> = SuperCasasScrapper.get_url_ids(&html_page).unwrap();
+
+        assert_eq!(
+            url_ids,
+            vec![
+                "/venda-apartamento-t1-lisboa-campo-de-ourique/i1764301",
+                "/venda-moradia-t3-oeiras/i1759123",
+            ]
+        );
+        assert!(!SuperCasasScrapper
+            .is_last_page(&html_page, "lisboa", 1, &url_ids, &[])
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn detects_past_last_page() {
+        let server: FixtureServer = FixtureServer::start("supercasas").await;
+        let empty: FetchedPage = server
+            .fetch("empty_search.html?segment=lisboa&page=9")
+            .await;
+        let redirected: FetchedPage = server.fetch("search.html").await;
+
+        assert!(SuperCasasScrapper
+            .is_last_page(&HtmlPage::parse(&empty), "lisboa", 9, &[], &[])
+            .unwrap());
+        assert!(SuperCasasScrapper
+            .is_last_page(&HtmlPage::parse(&redirected), "lisboa", 9, &[], &[])
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn extracts_listings() {
+        let server: FixtureServer = FixtureServer::start("supercasas").await;
+        let first: FetchedPage = server.fetch("listing_1.html").await;
+        let second: FetchedPage = server.fetch("listing_2.html").await;
+
+        assert_eq!(
+            SuperCasasScrapper
+                .get_listing(&HtmlPage::parse(&first), "/i1764301".to_string())
+                .unwrap(),
+            SuperCasasListingRaw {
+                price: "320.000 €".to_string(),
+                description: Some(
+                    "Apartamento T1 renovado, muito luminoso.\nPerto do Jardim da Estrela."
+                        .to_string()
+                ),
+                details_split_by_string: vec![
+                    "Tipologia: T1\nÁrea útil: 55 m²".to_string(),
+                    "Elevador\nVaranda".to_string(),
+                ],
+                url_id: "/i1764301".to_string(),
+                location: Some("Campo de Ourique, Lisboa".to_string()),
+                transaction: None,
+            }
+        );
+        assert_eq!(
+            SuperCasasScrapper
+                .get_listing(&HtmlPage::parse(&second), "/i1759123".to_string())
+                .unwrap(),
+            SuperCasasListingRaw {
+                price: "640.000 €".to_string(),
+                description: None,
+                details_split_by_string: vec!["Tipologia: T3\nÁrea útil: 180 m²".to_string()],
+                url_id: "/i1759123".to_string(),
+                location: None,
+                transaction: None,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn normalizes_listings() {
+        let server: FixtureServer = FixtureServer::start("supercasas").await;
+        let fetched: FetchedPage = server.fetch("listing_1.html").await;
+        let unfurnished_apartment: SuperCasasListingRaw = SuperCasasListingRaw {
+            price: "150.000 €".to_string(),
+            description: Some("Sem garagem. Apartamento não mobilado com varanda.".to_string()),
+            details_split_by_string: vec!["Tipologia: T2".to_string()],
+            url_id: "/i1760000".to_string(),
+            location: None,
+            transaction: None,
+        };
+
+        let listing: Listing = SuperCasasScrapper
+            .get_listing(&HtmlPage::parse(&fetched), "/i1764301".to_string())
+            .unwrap()
+            .normalize();
+        let unfurnished_listing: Listing = unfurnished_apartment.normalize();
+
+        assert_eq!(listing.price_cents, Some(32_000_000));
+        assert_eq!(listing.area_sqr_meters, Some(55.0));
+        assert_eq!(listing.no_bedrooms, Some(1));
+        assert_eq!(listing.features, vec!["elevator", "balcony", "garden"]);
+        assert_eq!(listing.freguesia, Some("Campo de Ourique".to_string()));
+        assert_eq!(listing.concelho, Some("Lisboa".to_string()));
+        assert_eq!(unfurnished_listing.features, vec!["balcony"]);
+        assert_eq!(unfurnished_listing.furnished, Some(false));
+    }
+
+    #[test]
+    fn builds_search_urls() {
+        let default: SearchSpec = SearchSpec::default();
+        let farms: SearchSpec = SearchSpec {
+            property_kinds: vec![PropertyKind::Farm],
+            min_price: Some(100_000),
+            max_price: Some(250_000),
+            districts: vec!["beja".to_string()],
+            ..SearchSpec::default()
+        };
+
+        assert_eq!(SuperCasasScrapper.segments(&default).len(), 18);
+        assert_eq!(
+            SuperCasasScrapper.listing_page_url(&default, "comprar-casas/aveiro-distrito", 2),
+            "https://supercasa.pt/comprar-casas/aveiro-distrito/pagina-2?ordem=atualizado-desc"
+        );
+        assert_eq!(
+            SuperCasasScrapper.segments(&farms),
+            vec!["comprar-quintas/beja-distrito"]
+        );
+        assert_eq!(
+            SuperCasasScrapper.listing_page_url(&farms, "comprar-quintas/beja-distrito", 1),
+            "https://supercasa.pt/comprar-quintas/beja-distrito/com-preco-min-100000,preco-max-250000/pagina-1?ordem=atualizado-desc"
+        );
     }
 }
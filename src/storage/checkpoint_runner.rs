@@ -0,0 +1,38 @@
+use crate::scrappers::scrapper::PortalScrapper;
+use crate::storage::checkpoint_store::{Checkpoint, CheckpointStore};
+
+/// Prints where the crawl of every portal resumes from
+pub async fn show(scrappers: &[&dyn PortalScrapper], checkpoints: &CheckpointStore) {
+    for scrapper in scrappers {
+        match checkpoints.get(scrapper.portal()).await {
+            Ok(Some(Checkpoint {
+                portal,
+                segment,
+                page,
+                last_seen_id,
+                run_id,
+                updated_at,
+            })) => println!(
+                "{} resumes from {} page {}, last stored {} by run {} at {}",
+                portal,
+                segment,
+                page,
+                last_seen_id.as_deref().unwrap_or("nothing"),
+                run_id,
+                updated_at
+            ),
+            Ok(None) => println!("{} starts from the first page", scrapper.portal()),
+            Err(e) => eprintln!("Error reading {} checkpoint: {:?}", scrapper.portal(), e),
+        }
+    }
+}
+
+/// Makes the next crawl of every portal start from the first page
+pub async fn reset(scrappers: &[&dyn PortalScrapper], checkpoints: &CheckpointStore) {
+    for scrapper in scrappers {
+        match checkpoints.clear(scrapper.portal()).await {
+            Ok(()) => println!("Reset {} checkpoint", scrapper.portal()),
+            Err(e) => eprintln!("Error resetting {} checkpoint: {:?}", scrapper.portal(), e),
+        }
+    }
+}
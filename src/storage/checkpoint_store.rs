@@ -0,0 +1,164 @@
+use crate::storage::listing_storage::StorageResult;
+use crate::utils::file_utils::{get_content_lines, get_file_read, write_atomically};
+use crate::utils::time_utils::unix_timestamp;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::path::PathBuf;
+
+/// Where the crawl of a portal stopped, the segment and page it resumes from
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Checkpoint {
+    pub portal: String,
+    pub segment: String,
+    pub page: u32,
+    /// The last listing stored before the checkpoint was written
+    pub last_seen_id: Option<String>,
+    pub run_id: i64,
+    pub updated_at: i64,
+}
+
+impl Checkpoint {
+    pub fn new(
+        portal: &str,
+        segment: &str,
+        page: u32,
+        last_seen_id: Option<String>,
+        run_id: i64,
+    ) -> Checkpoint {
+        Checkpoint {
+            portal: portal.to_string(),
+            segment: segment.to_string(),
+            page,
+            last_seen_id,
+            run_id,
+            updated_at: unix_timestamp(),
+        }
+    }
+}
+
+/// Checkpoints of the crawls, one `{CHECKPOINT_DIR}/{portal}.json` per portal so scrappers
+/// running at the same time never write the same file
+pub struct CheckpointStore {
+    directory: PathBuf,
+}
+
+impl CheckpointStore {
+    pub fn new(directory: &str) -> CheckpointStore {
+        CheckpointStore {
+            directory: PathBuf::from(directory),
+        }
+    }
+
+    /// The store at `CHECKPOINT_DIR` (defaults to `checkpoints`)
+    pub fn from_env() -> CheckpointStore {
+        CheckpointStore::new(&env::var("CHECKPOINT_DIR").unwrap_or("checkpoints".to_string()))
+    }
+
+    fn path(&self, portal: &str) -> PathBuf {
+        self.directory.join(format!("{}.json", portal))
+    }
+
+    /// The file the idealista scrapper kept its district and page in before the checkpoints
+    fn legacy_path(portal: &str) -> String {
+        format!("{}_cache.txt", portal)
+    }
+
+    /// The segment a district of the legacy file is now, it only crawled houses for sale
+    fn legacy_segment(district: &str) -> String {
+        format!("comprar-casas/{}-distrito", district)
+    }
+
+    async fn read_legacy(portal: &str) -> StorageResult<Option<Checkpoint>> {
+        let lines: Vec<String> = match get_file_read(&CheckpointStore::legacy_path(portal)).await {
+            Ok(file) => get_content_lines(file).await?,
+            Err(_) => return Ok(None),
+        };
+
+        match (lines.first(), lines.last()) {
+            (Some(district), Some(page)) => Ok(page.parse::<u32>().ok().map(|page| {
+                Checkpoint::new(
+                    portal,
+                    &CheckpointStore::legacy_segment(district),
+                    page,
+                    None,
+                    0,
+                )
+            })),
+            _ => Ok(None),
+        }
+    }
+
+    pub async fn get(&self, portal: &str) -> StorageResult<Option<Checkpoint>> {
+        match tokio::fs::read_to_string(self.path(portal)).await {
+            Ok(content) => Ok(Some(serde_json::from_str(&content)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                CheckpointStore::read_legacy(portal).await
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub async fn save(&self, checkpoint: &Checkpoint) -> StorageResult<()> {
+        tokio::fs::create_dir_all(&self.directory).await?;
+        write_atomically(
+            &self.path(&checkpoint.portal).to_string_lossy(),
+            serde_json::to_string_pretty(checkpoint)?,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Forgets where the portal stopped, its next crawl starts from the first page
+    pub async fn clear(&self, portal: &str) -> StorageResult<()> {
+        for path in [
+            self.path(portal),
+            PathBuf::from(CheckpointStore::legacy_path(portal)),
+        ] {
+            match tokio::fs::remove_file(&path).await {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scrappers::idealista_scrapper::IdealistaScrapper;
+    use crate::scrappers::scrapper::Scrapper;
+    use crate::scrappers::search_spec::SearchSpec;
+
+    #[tokio::test]
+    async fn saves_and_clears_checkpoints() {
+        let directory: PathBuf =
+            env::temp_dir().join(format!("res-checkpoints-{}", unix_timestamp()));
+        let store: CheckpointStore = CheckpointStore::new(&directory.to_string_lossy());
+        let checkpoint: Checkpoint =
+            Checkpoint::new("remax", "lisboa", 3, Some("122361016-55".to_string()), 7);
+
+        assert_eq!(store.get("remax").await.unwrap(), None);
+        store.save(&checkpoint).await.unwrap();
+        assert_eq!(store.get("remax").await.unwrap(), Some(checkpoint));
+        assert_eq!(store.get("era").await.unwrap(), None);
+
+        store.clear("remax").await.unwrap();
+        assert_eq!(store.get("remax").await.unwrap(), None);
+
+        let _ = tokio::fs::remove_dir_all(&directory).await;
+    }
+
+    #[test]
+    fn resumes_the_legacy_idealista_district() {
+        let search: SearchSpec = SearchSpec {
+            districts: vec!["lisboa".to_string()],
+            ..SearchSpec::default()
+        };
+
+        assert!(IdealistaScrapper
+            .segments(&search)
+            .contains(&CheckpointStore::legacy_segment("lisboa")));
+    }
+}
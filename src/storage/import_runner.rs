@@ -0,0 +1,78 @@
+use crate::schemas::listing::Listing;
+use crate::scrappers::scrapper::PortalScrapper;
+use crate::storage::listing_storage::{ListingStorage, StorageResult};
+use crate::storage::seen_ids::SeenIds;
+use crate::storage::sqlite_storage::SqliteStorage;
+use crate::utils::file_utils::{get_content_lines, get_file_read};
+use std::collections::HashSet;
+
+/// Imports the listings not in the database yet, so running it again adds nothing
+async fn import_portal(
+    storage: &SqliteStorage,
+    scrapper: &dyn PortalScrapper,
+) -> StorageResult<()> {
+    let portal: &str = scrapper.portal();
+    let mut new_listings: Vec<(String, Listing)> = Vec::new();
+    let mut new_url_ids: HashSet<String> = HashSet::new();
+
+    match get_file_read(&format!("{}.json", portal)).await {
+        Ok(listings_read) => {
+            for line in get_content_lines(listings_read).await? {
+                match scrapper.normalize_line(&line) {
+                    Ok(listing) => {
+                        let imported: bool = new_url_ids.contains(&listing.url_id)
+                            || storage.is_seen(portal, &listing.url_id).await?;
+                        if !imported {
+                            new_url_ids.insert(listing.url_id.clone());
+                            new_listings.push((line, listing));
+                        }
+                    }
+                    Err(e) => println!("Failed to import {} line: {}", portal, e),
+                }
+            }
+        }
+        Err(_) => println!("No {}.json file to import", portal),
+    }
+
+    if !new_listings.is_empty() {
+        let run_id: i64 = storage.start_run(portal).await?;
+        for (line, listing) in &new_listings {
+            storage.save_listing(run_id, line, listing).await?;
+        }
+        storage
+            .finish_run(run_id, portal, new_listings.len() as u32)
+            .await?;
+    }
+
+    // Ids can be in the ids file without a listing, they are still already scrapped
+    if let Ok(ids_read) = get_file_read(&SeenIds::path(portal)).await {
+        for url_id in get_content_lines(ids_read).await? {
+            if !url_id.is_empty() {
+                storage.mark_seen(portal, &url_id).await?;
+            }
+        }
+    }
+
+    println!("Imported {} new {} listings", new_listings.len(), portal);
+
+    Ok(())
+}
+
+/// One shot import of the Json and ids files of every portal into the SQLite database
+pub async fn run(scrappers: &[Box<dyn PortalScrapper>], database_path: &str) {
+    let storage: SqliteStorage = match SqliteStorage::open(database_path) {
+        Ok(storage) => storage,
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            return;
+        }
+    };
+
+    for scrapper in scrappers {
+        if let Err(e) = import_portal(&storage, scrapper.as_ref()).await {
+            eprintln!("Error importing {}: {:?}", scrapper.portal(), e);
+        }
+    }
+
+    println!("Import mechanism finished");
+}
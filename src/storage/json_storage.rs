@@ -0,0 +1,93 @@
+use crate::schemas::listing::Listing;
+use crate::storage::listing_storage::{ListingStorage, StorageResult};
+use crate::storage::seen_ids::SeenIds;
+use crate::utils::file_utils::*;
+use crate::utils::time_utils::unix_timestamp;
+use async_trait::async_trait;
+use serde_json::json;
+use std::collections::HashMap;
+use tokio::fs::File;
+use tokio::sync::Mutex;
+
+/// Append only Json files, `{portal}.json` with the listings and `{portal}_ids.txt` with the seen ids
+pub struct JsonStorage {
+    ids: Mutex<HashMap<String, SeenIds>>,
+}
+
+impl JsonStorage {
+    pub fn new() -> JsonStorage {
+        JsonStorage {
+            ids: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+#[async_trait]
+impl ListingStorage for JsonStorage {
+    async fn start_run(&self, _portal: &str) -> StorageResult<i64> {
+        Ok(unix_timestamp())
+    }
+
+    async fn finish_run(&self, run_id: i64, portal: &str, no_listings: u32) -> StorageResult<()> {
+        let mut runs_write: File = get_file_write_append("scrape_runs.json").await?;
+        let run = json!({
+            "run_id": run_id,
+            "portal": portal,
+            "started_at": run_id,
+            "finished_at": unix_timestamp(),
+            "no_listings": no_listings,
+        });
+
+        write_to_file(&mut runs_write, format!("{}\n", run)).await?;
+        Ok(())
+    }
+
+    async fn is_seen(&self, portal: &str, url_id: &str) -> StorageResult<bool> {
+        let mut ids = self.ids.lock().await;
+
+        if !ids.contains_key(portal) {
+            ids.insert(
+                portal.to_string(),
+                SeenIds::load(&SeenIds::path(portal)).await?,
+            );
+        }
+
+        Ok(ids[portal].contains(url_id))
+    }
+
+    async fn save_listing(
+        &self,
+        _run_id: i64,
+        listing_raw: &str,
+        listing: &Listing,
+    ) -> StorageResult<()> {
+        let mut listings_write: File =
+            get_file_write_append(&format!("{}.json", listing.portal)).await?;
+
+        write_to_file(&mut listings_write, format!("{}\n", listing_raw)).await?;
+        SeenIds::save(&SeenIds::path(&listing.portal), &listing.url_id).await?;
+
+        if let Some(ids) = self.ids.lock().await.get_mut(&listing.portal) {
+            ids.insert(&listing.url_id);
+        }
+
+        Ok(())
+    }
+
+    // The LLM runner already writes its responses to `OUTPUT_PATH`
+    async fn save_llm_result(
+        &self,
+        _url_id: &str,
+        _model: &str,
+        _response: &str,
+    ) -> StorageResult<()> {
+        Ok(())
+    }
+
+    async fn raw_listings(&self, portal: &str) -> StorageResult<Vec<String>> {
+        match get_file_read(&format!("{}.json", portal)).await {
+            Ok(listings_read) => Ok(get_content_lines(listings_read).await?),
+            Err(_) => Ok(Vec::new()),
+        }
+    }
+}
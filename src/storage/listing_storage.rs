@@ -0,0 +1,88 @@
+use crate::schemas::listing::Listing;
+use crate::schemas::listing_history::{ListingEvent, PriceDrop, TimeOnMarket};
+use crate::storage::json_storage::JsonStorage;
+use crate::storage::sqlite_storage::SqliteStorage;
+use async_trait::async_trait;
+use std::env;
+use std::error::Error;
+
+pub type StorageResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
+
+fn requires_sqlite(feature: &str) -> Box<dyn Error + Send + Sync> {
+    format!("{} requires `STORAGE` to be sqlite", feature).into()
+}
+
+/// Where the scrappers and the LLM keep what they produce
+#[async_trait]
+pub trait ListingStorage: Send + Sync {
+    /// Registers the start of a scrape run and returns its id
+    async fn start_run(&self, portal: &str) -> StorageResult<i64>;
+
+    async fn finish_run(&self, run_id: i64, portal: &str, no_listings: u32) -> StorageResult<()>;
+
+    /// Whether the listing was already scrapped in a previous run
+    async fn is_seen(&self, portal: &str, url_id: &str) -> StorageResult<bool>;
+
+    /// Stores the listing as scrapped (`listing_raw` is its Json) and marks its id as seen
+    async fn save_listing(
+        &self,
+        run_id: i64,
+        listing_raw: &str,
+        listing: &Listing,
+    ) -> StorageResult<()>;
+
+    async fn save_llm_result(&self, url_id: &str, model: &str, response: &str)
+        -> StorageResult<()>;
+
+    /// The Json of every listing of the portal stored, in the order they were scrapped
+    async fn raw_listings(&self, portal: &str) -> StorageResult<Vec<String>>;
+
+    /// Ids of the listings of the portal that were last observed before `observed_before`
+    async fn listings_to_revisit(
+        &self,
+        _portal: &str,
+        _observed_before: i64,
+    ) -> StorageResult<Vec<String>> {
+        Err(requires_sqlite("Revisiting listings"))
+    }
+
+    /// Stores a new observation of a known listing and returns the changes recorded as events
+    async fn save_observation(
+        &self,
+        _listing_raw: &str,
+        _listing: &Listing,
+    ) -> StorageResult<Vec<ListingEvent>> {
+        Err(requires_sqlite("Revisiting listings"))
+    }
+
+    /// Marks the listing as inactive from now on, it was sold or withdrawn
+    async fn save_delisting(&self, _portal: &str, _url_id: &str) -> StorageResult<ListingEvent> {
+        Err(requires_sqlite("Revisiting listings"))
+    }
+
+    /// Listings whose price dropped by more than `min_drop_percentage` since `since`
+    async fn price_drops(
+        &self,
+        _min_drop_percentage: f64,
+        _since: i64,
+    ) -> StorageResult<Vec<PriceDrop>> {
+        Err(requires_sqlite("Price drops"))
+    }
+
+    /// How long every listing was on the market, from when it was first seen until it was delisted
+    async fn time_on_market(&self) -> StorageResult<Vec<TimeOnMarket>> {
+        Err(requires_sqlite("Time on market"))
+    }
+}
+
+/// Opens the storage selected by the `STORAGE` env variable, `sqlite` (default) or `json`
+pub fn open_storage() -> StorageResult<Box<dyn ListingStorage>> {
+    match env::var("STORAGE").unwrap_or("sqlite".to_string()).as_str() {
+        "json" => Ok(Box::new(JsonStorage::new())),
+        "sqlite" => {
+            let database_path: String = env::var("DATABASE_PATH").unwrap_or("res.db".to_string());
+            Ok(Box::new(SqliteStorage::open(&database_path)?))
+        }
+        storage => Err(format!("Invalid storage {}, use `sqlite` or `json`", storage).into()),
+    }
+}
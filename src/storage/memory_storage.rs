@@ -0,0 +1,137 @@
+use crate::schemas::listing::Listing;
+use crate::schemas::listing_history::ListingEvent;
+use crate::schemas::listing_history::ListingEventKind;
+use crate::storage::listing_storage::{ListingStorage, StorageResult};
+use async_trait::async_trait;
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+#[derive(Default)]
+struct MemoryState {
+    next_run_id: i64,
+    /// Url ids in the order they were saved
+    saved: Vec<String>,
+    raw_listings: Vec<(String, String)>,
+    seen: HashSet<(String, String)>,
+    observed: Vec<String>,
+    delisted: Vec<String>,
+}
+
+/// Keeps everything in memory, for the tests of what the scrappers store
+#[derive(Default)]
+pub struct MemoryStorage {
+    state: Mutex<MemoryState>,
+}
+
+impl MemoryStorage {
+    /// Url ids of the listings scrapped, in the order they were saved
+    pub fn saved(&self) -> Vec<String> {
+        self.state.lock().unwrap().saved.clone()
+    }
+
+    pub fn observed(&self) -> Vec<String> {
+        self.state.lock().unwrap().observed.clone()
+    }
+
+    pub fn delisted(&self) -> Vec<String> {
+        self.state.lock().unwrap().delisted.clone()
+    }
+
+    pub fn mark_seen(&self, portal: &str, url_id: &str) {
+        self.state
+            .lock()
+            .unwrap()
+            .seen
+            .insert((portal.to_string(), url_id.to_string()));
+    }
+}
+
+#[async_trait]
+impl ListingStorage for MemoryStorage {
+    async fn start_run(&self, _portal: &str) -> StorageResult<i64> {
+        let mut state = self.state.lock().unwrap();
+        state.next_run_id += 1;
+        Ok(state.next_run_id)
+    }
+
+    async fn finish_run(
+        &self,
+        _run_id: i64,
+        _portal: &str,
+        _no_listings: u32,
+    ) -> StorageResult<()> {
+        Ok(())
+    }
+
+    async fn is_seen(&self, portal: &str, url_id: &str) -> StorageResult<bool> {
+        Ok(self
+            .state
+            .lock()
+            .unwrap()
+            .seen
+            .contains(&(portal.to_string(), url_id.to_string())))
+    }
+
+    async fn save_listing(
+        &self,
+        _run_id: i64,
+        listing_raw: &str,
+        listing: &Listing,
+    ) -> StorageResult<()> {
+        let mut state = self.state.lock().unwrap();
+        state.saved.push(listing.url_id.clone());
+        state
+            .raw_listings
+            .push((listing.portal.clone(), listing_raw.to_string()));
+        state
+            .seen
+            .insert((listing.portal.clone(), listing.url_id.clone()));
+        Ok(())
+    }
+
+    async fn save_llm_result(
+        &self,
+        _url_id: &str,
+        _model: &str,
+        _response: &str,
+    ) -> StorageResult<()> {
+        Ok(())
+    }
+
+    async fn raw_listings(&self, portal: &str) -> StorageResult<Vec<String>> {
+        Ok(self
+            .state
+            .lock()
+            .unwrap()
+            .raw_listings
+            .iter()
+            .filter(|(listing_portal, _)| listing_portal == portal)
+            .map(|(_, listing_raw)| listing_raw.clone())
+            .collect())
+    }
+
+    async fn save_observation(
+        &self,
+        _listing_raw: &str,
+        listing: &Listing,
+    ) -> StorageResult<Vec<ListingEvent>> {
+        self.state
+            .lock()
+            .unwrap()
+            .observed
+            .push(listing.url_id.clone());
+        Ok(Vec::new())
+    }
+
+    async fn save_delisting(&self, portal: &str, url_id: &str) -> StorageResult<ListingEvent> {
+        self.state.lock().unwrap().delisted.push(url_id.to_string());
+        Ok(ListingEvent {
+            portal: portal.to_string(),
+            url_id: url_id.to_string(),
+            occurred_at: 0,
+            kind: ListingEventKind::Delisted,
+            old_value: None,
+            new_value: None,
+        })
+    }
+}
@@ -0,0 +1,209 @@
+use crate::scrappers::html_page::FetchedPage;
+use crate::storage::listing_storage::StorageResult;
+use crate::utils::file_utils::write_atomically;
+use crate::utils::time_utils::unix_timestamp;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::env;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+// The metadata goes in a comment on the first line so an archived page is still a valid Html
+// fixture once decompressed
+const HEADER_START: &str = "<!-- res-archive ";
+const HEADER_END: &str = " -->";
+
+/// What is known of an archived page besides its source
+#[derive(Deserialize, Serialize)]
+struct ArchiveHeader {
+    portal: String,
+    url_id: String,
+    fetched_at: i64,
+    url: String,
+    status: Option<u16>,
+}
+
+pub struct ArchivedPage {
+    pub url_id: String,
+    pub page: FetchedPage,
+}
+
+/// Gzipped sources of the listing pages, `{ARCHIVE_DIR}/{portal}/{fetched_at}-{url_id hash}.html.gz`,
+/// kept to run the extraction again over past pages
+pub struct PageArchive {
+    directory: PathBuf,
+}
+
+fn url_id_hash(url_id: &str) -> String {
+    Sha256::digest(url_id.as_bytes())
+        .iter()
+        .take(6)
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+impl PageArchive {
+    pub fn new(directory: &str) -> PageArchive {
+        PageArchive {
+            directory: PathBuf::from(directory),
+        }
+    }
+
+    /// The archive at `ARCHIVE_DIR`, `None` when the pages are not archived
+    pub fn from_env() -> Option<PageArchive> {
+        env::var("ARCHIVE_DIR")
+            .ok()
+            .filter(|directory| !directory.is_empty())
+            .map(|directory| PageArchive::new(&directory))
+    }
+
+    pub async fn save(&self, portal: &str, url_id: &str, page: &FetchedPage) -> StorageResult<()> {
+        self.save_at(portal, url_id, page, unix_timestamp()).await
+    }
+
+    /// Archives the page as fetched at `fetched_at`
+    pub async fn save_at(
+        &self,
+        portal: &str,
+        url_id: &str,
+        page: &FetchedPage,
+        fetched_at: i64,
+    ) -> StorageResult<()> {
+        let header: ArchiveHeader = ArchiveHeader {
+            portal: portal.to_string(),
+            url_id: url_id.to_string(),
+            fetched_at,
+            url: page.url.clone(),
+            status: page.status,
+        };
+        let directory: PathBuf = self.directory.join(portal);
+        let path: PathBuf = directory.join(format!(
+            "{}-{}.html.gz",
+            header.fetched_at,
+            url_id_hash(url_id)
+        ));
+
+        let mut encoder: GzEncoder<Vec<u8>> = GzEncoder::new(Vec::new(), Compression::default());
+        encoder
+            .write_all(format!("{}{}{}\n", HEADER_START, json!(header), HEADER_END).as_bytes())?;
+        encoder.write_all(page.source.as_bytes())?;
+        let compressed: Vec<u8> = encoder.finish()?;
+
+        tokio::fs::create_dir_all(&directory).await?;
+        write_atomically(&path.to_string_lossy(), compressed).await?;
+        Ok(())
+    }
+
+    /// Archived pages of the portal, oldest first
+    pub async fn paths(&self, portal: &str) -> StorageResult<Vec<PathBuf>> {
+        let mut paths: Vec<PathBuf> = Vec::new();
+        let mut entries = match tokio::fs::read_dir(self.directory.join(portal)).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(paths),
+            Err(e) => return Err(e.into()),
+        };
+
+        while let Some(entry) = entries.next_entry().await? {
+            let path: PathBuf = entry.path();
+            if path.to_string_lossy().ends_with(".html.gz") {
+                paths.push(path);
+            }
+        }
+
+        // Named after the fetch timestamp, which has the same number of digits until 2286
+        paths.sort();
+        Ok(paths)
+    }
+
+    pub async fn load(&self, path: &Path) -> StorageResult<ArchivedPage> {
+        let compressed: Vec<u8> = tokio::fs::read(path).await?;
+        let mut content: String = String::new();
+        GzDecoder::new(compressed.as_slice()).read_to_string(&mut content)?;
+
+        let (first_line, source): (&str, &str) = content.split_once('\n').unwrap_or((&content, ""));
+        let header: ArchiveHeader = match first_line
+            .strip_prefix(HEADER_START)
+            .and_then(|header| header.strip_suffix(HEADER_END))
+        {
+            Some(header) => serde_json::from_str(header)?,
+            None => return Err(format!("{} is not an archived page", path.display()).into()),
+        };
+
+        Ok(ArchivedPage {
+            url_id: header.url_id,
+            page: FetchedPage {
+                url: header.url,
+                status: header.status,
+                source: source.to_string(),
+                never_loaded: None,
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn archive_dir(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("res-archive-{}-{}", name, unix_timestamp()))
+    }
+
+    fn page(source: &str) -> FetchedPage {
+        FetchedPage {
+            url: "https://www.remax.pt/pt/imoveis/122361016-55".to_string(),
+            status: Some(200),
+            source: source.to_string(),
+            never_loaded: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn loads_the_pages_it_archived_oldest_first() {
+        let directory: PathBuf = archive_dir("round-trip");
+        let archive: PageArchive = PageArchive::new(&directory.to_string_lossy());
+        let source: &str = "<html><body><h2 class=\"price\">385 000 €</h2></body></html>";
+
+        archive
+            .save_at("remax", "122361016-55", &page(source), 1_700_000_200)
+            .await
+            .unwrap();
+        archive
+            .save_at(
+                "remax",
+                "122361016-55",
+                &page("<html></html>"),
+                1_700_000_100,
+            )
+            .await
+            .unwrap();
+        let paths: Vec<PathBuf> = archive.paths("remax").await.unwrap();
+        let newest: ArchivedPage = archive.load(&paths[1]).await.unwrap();
+
+        assert_eq!(paths.len(), 2);
+        assert!(paths[0].ends_with(format!(
+            "remax/1700000100-{}.html.gz",
+            url_id_hash("122361016-55")
+        )));
+        assert_eq!(newest.url_id, "122361016-55");
+        assert_eq!(newest.page.url, page(source).url);
+        assert_eq!(newest.page.status, Some(200));
+        assert_eq!(newest.page.source, source);
+        assert!(archive.paths("era").await.unwrap().is_empty());
+
+        // Decompressed it is a fixture, its first line an Html comment
+        let mut content: String = String::new();
+        GzDecoder::new(std::fs::read(&paths[1]).unwrap().as_slice())
+            .read_to_string(&mut content)
+            .unwrap();
+        let (first_line, fixture): (&str, &str) = content.split_once('\n').unwrap();
+        assert!(first_line.starts_with("<!--") && first_line.ends_with("-->"));
+        assert_eq!(fixture, source);
+
+        let _ = tokio::fs::remove_dir_all(&directory).await;
+    }
+}
@@ -0,0 +1,195 @@
+use crate::schemas::listing::{Listing, Transaction};
+use crate::scrappers::scrapper::{mark_rental, PortalScrapper};
+use crate::scrappers::scrapper_error::ScrapperError;
+use crate::storage::listing_storage::{open_storage, ListingStorage, StorageResult};
+use crate::storage::page_archive::{ArchivedPage, PageArchive};
+use crate::utils::file_utils::write_atomically;
+use std::collections::{BTreeMap, HashSet};
+use std::path::PathBuf;
+
+/// Ids of the listings stored as rentals, the pages do not always say it so it comes from the search
+async fn rental_url_ids(
+    storage: &dyn ListingStorage,
+    scrapper: &dyn PortalScrapper,
+) -> StorageResult<HashSet<String>> {
+    let rental_url_ids: HashSet<String> = storage
+        .raw_listings(scrapper.portal())
+        .await?
+        .iter()
+        .filter_map(|listing_raw| scrapper.normalize_line(listing_raw).ok())
+        .filter(|listing| listing.transaction == Transaction::Rent)
+        .map(|listing| listing.url_id)
+        .collect();
+    Ok(rental_url_ids)
+}
+
+/// Writes the newest version of every archived listing of the portal to `output`
+async fn reprocess_portal(
+    archive: &PageArchive,
+    storage: &dyn ListingStorage,
+    scrapper: &dyn PortalScrapper,
+    output: &str,
+) -> StorageResult<()> {
+    let portal: &str = scrapper.portal();
+    let paths: Vec<PathBuf> = archive.paths(portal).await?;
+    if paths.is_empty() {
+        println!("No archived {} pages", portal);
+        return Ok(());
+    }
+
+    let rental_url_ids: HashSet<String> = rental_url_ids(storage, scrapper).await?;
+
+    // Oldest first so the newest page of every listing is the one kept
+    let mut listings: BTreeMap<String, String> = BTreeMap::new();
+    let mut failures: BTreeMap<String, u32> = BTreeMap::new();
+    let mut no_gone: u32 = 0;
+
+    for path in &paths {
+        let archived_page: ArchivedPage = match archive.load(path).await {
+            Ok(archived_page) => archived_page,
+            Err(e) => {
+                println!("Failed to read {}: {}", path.display(), e);
+                continue;
+            }
+        };
+
+        let extracted: Result<(String, Listing), ScrapperError> = scrapper
+            .extract_listing(&archived_page.page, &archived_page.url_id)
+            .map(|extracted| {
+                if rental_url_ids.contains(&archived_page.url_id) {
+                    mark_rental(extracted)
+                } else {
+                    extracted
+                }
+            });
+
+        match extracted {
+            Ok((listing_raw, _)) => {
+                listings.insert(archived_page.url_id, listing_raw);
+            }
+            Err(ScrapperError::ListingGone(_)) => {
+                listings.remove(&archived_page.url_id);
+                no_gone += 1;
+            }
+            Err(e) => {
+                *failures.entry(e.to_string()).or_default() += 1;
+            }
+        }
+    }
+
+    let content: String = listings
+        .values()
+        .map(|listing_raw| format!("{}\n", listing_raw))
+        .collect();
+    write_atomically(output, content).await?;
+
+    println!(
+        "Reprocessed {} {} pages into {} listings in {}, {} gone and {} failed",
+        paths.len(),
+        portal,
+        listings.len(),
+        output,
+        no_gone,
+        failures.values().sum::<u32>()
+    );
+    for (error, count) in failures {
+        println!("  {} pages: {}", count, error);
+    }
+
+    Ok(())
+}
+
+/// Runs the extraction of every portal again over its archived pages, without the network
+pub async fn run(scrappers: &[&dyn PortalScrapper], archive: &PageArchive) {
+    let storage: Box<dyn ListingStorage> = match open_storage() {
+        Ok(storage) => storage,
+        Err(e) => {
+            eprintln!("Error opening the storage: {:?}", e);
+            return;
+        }
+    };
+
+    for scrapper in scrappers {
+        let output: String = format!("{}_reprocessed.json", scrapper.portal());
+        if let Err(e) = reprocess_portal(archive, storage.as_ref(), *scrapper, &output).await {
+            eprintln!("Error reprocessing {}: {:?}", scrapper.portal(), e);
+        }
+    }
+
+    println!("Reprocess mechanism finished");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scrappers::html_page::FetchedPage;
+    use crate::scrappers::remax_scrapper::RemaxScrapper;
+    use crate::scrappers::scrapper::Scrapper;
+    use crate::storage::memory_storage::MemoryStorage;
+    use crate::utils::time_utils::unix_timestamp;
+    use std::env;
+
+    fn listing_page(url_id: &str, status: u16, source: &str) -> FetchedPage {
+        FetchedPage {
+            url: RemaxScrapper.listing_url(url_id),
+            status: Some(status),
+            source: source.to_string(),
+            never_loaded: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn keeps_the_newest_page_of_every_listing() {
+        let directory: PathBuf =
+            env::temp_dir().join(format!("res-reprocess-{}", unix_timestamp()));
+        let archive: PageArchive = PageArchive::new(&directory.join("archive").to_string_lossy());
+        let output: PathBuf = directory.join("remax_reprocessed.json");
+        let source: String = std::fs::read_to_string(
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/remax/listing_1.html"),
+        )
+        .unwrap();
+        let older_source: String = source.replace("385 000", "395 000");
+
+        // The price of the rental dropped, the listing sold was removed since
+        for (url_id, fetched_at, page) in [
+            ("rental", 100, listing_page("rental", 200, &older_source)),
+            ("rental", 200, listing_page("rental", 200, &source)),
+            ("sold", 100, listing_page("sold", 200, &source)),
+            ("sold", 200, listing_page("sold", 404, "")),
+        ] {
+            archive
+                .save_at("remax", url_id, &page, fetched_at)
+                .await
+                .unwrap();
+        }
+        let storage: MemoryStorage = MemoryStorage::default();
+        let (rental_raw, rental): (String, Listing) = mark_rental(
+            RemaxScrapper
+                .extract_listing(&listing_page("rental", 200, &older_source), "rental")
+                .unwrap(),
+        );
+        storage.save_listing(1, &rental_raw, &rental).await.unwrap();
+
+        reprocess_portal(
+            &archive,
+            &storage,
+            &RemaxScrapper,
+            &output.to_string_lossy(),
+        )
+        .await
+        .unwrap();
+
+        let lines: Vec<String> = std::fs::read_to_string(&output)
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect();
+        assert_eq!(lines.len(), 1);
+        let listing: Listing = RemaxScrapper.normalize_line(&lines[0]).unwrap();
+        assert_eq!(listing.url_id, "rental");
+        assert_eq!(listing.price_cents, Some(38_500_000));
+        assert_eq!(listing.transaction, Transaction::Rent);
+
+        let _ = tokio::fs::remove_dir_all(&directory).await;
+    }
+}
@@ -0,0 +1,87 @@
+use crate::utils::file_utils::{
+    get_content_lines, get_file_read, get_file_write_append, write_to_file,
+};
+use std::collections::HashSet;
+
+/// Exact match index of the url ids already scrapped for a portal
+#[derive(Default)]
+pub struct SeenIds {
+    ids: HashSet<String>,
+}
+
+impl SeenIds {
+    pub fn from_lines<I: IntoIterator<Item = String>>(lines: I) -> SeenIds {
+        let ids: HashSet<String> = lines
+            .into_iter()
+            .map(|line| line.trim().to_string())
+            .filter(|line| !line.is_empty())
+            .collect();
+
+        SeenIds { ids }
+    }
+
+    pub fn path(portal: &str) -> String {
+        format!("{}_ids.txt", portal)
+    }
+
+    /// Loads the ids file of a portal, a missing file means nothing was scrapped yet
+    pub async fn load(path: &str) -> Result<SeenIds, std::io::Error> {
+        match get_file_read(path).await {
+            Ok(file) => Ok(SeenIds::from_lines(get_content_lines(file).await?)),
+            Err(_) => Ok(SeenIds::default()),
+        }
+    }
+
+    /// Appends the id to the ids file, one per line
+    pub async fn save(path: &str, url_id: &str) -> Result<(), std::io::Error> {
+        let mut ids_write = get_file_write_append(path).await?;
+        write_to_file(&mut ids_write, format!("{}\n", url_id.trim())).await
+    }
+
+    pub fn contains(&self, url_id: &str) -> bool {
+        self.ids.contains(url_id.trim())
+    }
+
+    /// Returns whether the id was not seen before
+    pub fn insert(&mut self, url_id: &str) -> bool {
+        self.ids.insert(url_id.trim().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::time_utils::unix_timestamp;
+    use std::env;
+
+    #[test]
+    fn matches_ids_exactly() {
+        let mut seen_ids: SeenIds = SeenIds::from_lines(vec!["/imovel/1234".to_string()]);
+
+        assert!(seen_ids.contains("/imovel/1234"));
+        assert!(!seen_ids.contains("/imovel/123"));
+        assert!(seen_ids.insert("/imovel/123"));
+        assert!(!seen_ids.insert(" /imovel/123 "));
+    }
+
+    #[tokio::test]
+    async fn round_trips_through_the_ids_file() {
+        let path: String = env::temp_dir()
+            .join(format!("res-seen-ids-{}.txt", unix_timestamp()))
+            .to_string_lossy()
+            .to_string();
+
+        assert!(!SeenIds::load(&path).await.unwrap().contains("/imovel/123"));
+        for url_id in ["/imovel/123", "/imovel/1234", "122361016-55"] {
+            SeenIds::save(&path, url_id).await.unwrap();
+        }
+        let seen_ids: SeenIds = SeenIds::load(&path).await.unwrap();
+
+        assert!(seen_ids.contains("/imovel/123"));
+        assert!(seen_ids.contains("/imovel/1234"));
+        assert!(seen_ids.contains("122361016-55"));
+        assert!(!seen_ids.contains("/imovel/12"));
+
+        let _ = tokio::fs::remove_file(&path).await;
+    }
+}
@@ -0,0 +1,686 @@
+use crate::schemas::listing::Listing;
+use crate::schemas::listing_history::{ListingEvent, ListingEventKind, PriceDrop, TimeOnMarket};
+use crate::storage::listing_storage::{ListingStorage, StorageResult};
+use crate::utils::time_utils::unix_timestamp;
+use crate::utils::time_utils::SECONDS_PER_DAY;
+use async_trait::async_trait;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS scrape_runs (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        portal TEXT NOT NULL,
+        started_at INTEGER NOT NULL,
+        finished_at INTEGER,
+        no_listings INTEGER NOT NULL DEFAULT 0
+    );
+
+    CREATE TABLE IF NOT EXISTS seen_ids (
+        portal TEXT NOT NULL,
+        url_id TEXT NOT NULL,
+        first_seen_at INTEGER NOT NULL,
+        PRIMARY KEY (portal, url_id)
+    );
+
+    CREATE TABLE IF NOT EXISTS listings (
+        portal TEXT NOT NULL,
+        url_id TEXT NOT NULL,
+        run_id INTEGER REFERENCES scrape_runs(id),
+        scraped_at INTEGER NOT NULL,
+        url TEXT NOT NULL,
+        price_cents INTEGER,
+        area_sqr_meters REAL,
+        typology TEXT,
+        no_bedrooms INTEGER,
+        no_bathrooms INTEGER,
+        district TEXT,
+        concelho TEXT,
+        freguesia TEXT,
+        property_kind TEXT,
+        features TEXT NOT NULL,
+        raw TEXT NOT NULL,
+        delisted_at INTEGER,
+        PRIMARY KEY (portal, url_id)
+    );
+
+    CREATE INDEX IF NOT EXISTS listings_district ON listings (district, typology);
+
+    CREATE TABLE IF NOT EXISTS observations (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        portal TEXT NOT NULL,
+        url_id TEXT NOT NULL,
+        observed_at INTEGER NOT NULL,
+        price_cents INTEGER,
+        description TEXT
+    );
+
+    CREATE INDEX IF NOT EXISTS observations_listing ON observations (portal, url_id, observed_at);
+
+    CREATE TABLE IF NOT EXISTS listing_events (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        portal TEXT NOT NULL,
+        url_id TEXT NOT NULL,
+        occurred_at INTEGER NOT NULL,
+        kind TEXT NOT NULL,
+        old_value TEXT,
+        new_value TEXT
+    );
+
+    CREATE INDEX IF NOT EXISTS listing_events_kind ON listing_events (kind, occurred_at);
+
+    CREATE TABLE IF NOT EXISTS llm_results (
+        url_id TEXT NOT NULL,
+        model TEXT NOT NULL,
+        created_at INTEGER NOT NULL,
+        response TEXT NOT NULL,
+        PRIMARY KEY (url_id, model)
+    );
+";
+
+/// Serializes a unit enum the same way it is in the listings Json, e.g. `"T6+"` as `T6+`
+fn enum_text<T: serde::Serialize>(value: &Option<T>) -> Option<String> {
+    value
+        .as_ref()
+        .and_then(|value| json!(value).as_str().map(|text| text.to_string()))
+}
+
+fn upsert_listing(
+    connection: &Connection,
+    run_id: Option<i64>,
+    listing_raw: &str,
+    listing: &Listing,
+    now: i64,
+) -> rusqlite::Result<()> {
+    connection.execute(
+        "INSERT INTO listings (portal, url_id, run_id, scraped_at, url, price_cents,
+                area_sqr_meters, typology, no_bedrooms, no_bathrooms, district, concelho,
+                freguesia, property_kind, features, raw)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)
+             ON CONFLICT (portal, url_id) DO UPDATE SET
+                run_id = COALESCE(excluded.run_id, listings.run_id),
+                scraped_at = excluded.scraped_at, url = excluded.url,
+                price_cents = excluded.price_cents, area_sqr_meters = excluded.area_sqr_meters,
+                typology = excluded.typology, no_bedrooms = excluded.no_bedrooms,
+                no_bathrooms = excluded.no_bathrooms, district = excluded.district,
+                concelho = excluded.concelho, freguesia = excluded.freguesia,
+                property_kind = excluded.property_kind, features = excluded.features,
+                raw = excluded.raw, delisted_at = NULL",
+        params![
+            listing.portal,
+            listing.url_id,
+            run_id,
+            now,
+            listing.url,
+            listing.price_cents.map(|price| price as i64),
+            listing.area_sqr_meters,
+            enum_text(&listing.typology),
+            listing.no_bedrooms,
+            listing.no_bathrooms,
+            listing.district,
+            listing.concelho,
+            listing.freguesia,
+            enum_text(&listing.property_kind),
+            json!(listing.features).to_string(),
+            listing_raw,
+        ],
+    )?;
+    Ok(())
+}
+
+fn insert_observation(
+    connection: &Connection,
+    listing: &Listing,
+    now: i64,
+) -> rusqlite::Result<()> {
+    connection.execute(
+        "INSERT INTO observations (portal, url_id, observed_at, price_cents, description)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            listing.portal,
+            listing.url_id,
+            now,
+            listing.price_cents.map(|price| price as i64),
+            listing.description,
+        ],
+    )?;
+    Ok(())
+}
+
+fn insert_event(connection: &Connection, event: &ListingEvent) -> rusqlite::Result<()> {
+    connection.execute(
+        "INSERT INTO listing_events (portal, url_id, occurred_at, kind, old_value, new_value)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            event.portal,
+            event.url_id,
+            event.occurred_at,
+            event.kind.as_str(),
+            event.old_value,
+            event.new_value,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Adds the columns introduced after a database was created
+fn migrate(connection: &Connection) -> rusqlite::Result<()> {
+    let has_delisted_at: bool = connection
+        .prepare("SELECT 1 FROM pragma_table_info('listings') WHERE name = 'delisted_at'")?
+        .exists([])?;
+
+    if !has_delisted_at {
+        connection.execute("ALTER TABLE listings ADD COLUMN delisted_at INTEGER", [])?;
+    }
+
+    Ok(())
+}
+
+/// Embedded SQLite database, dedup is an indexed lookup on `seen_ids`. The queries run on the
+/// blocking threads of tokio so a slow disk never stalls the scrappers.
+pub struct SqliteStorage {
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl SqliteStorage {
+    pub fn open(path: &str) -> StorageResult<SqliteStorage> {
+        let connection: Connection = Connection::open(path)?;
+        connection.execute_batch(SCHEMA)?;
+        migrate(&connection)?;
+
+        Ok(SqliteStorage {
+            connection: Arc::new(Mutex::new(connection)),
+        })
+    }
+
+    /// Runs `query` with the connection on a blocking thread
+    async fn with_connection<T, F>(&self, query: F) -> StorageResult<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> StorageResult<T> + Send + 'static,
+    {
+        let connection: Arc<Mutex<Connection>> = Arc::clone(&self.connection);
+
+        tokio::task::spawn_blocking(move || {
+            let mut connection = connection
+                .lock()
+                .map_err(|_| "the SQLite connection lock is poisoned")?;
+            query(&mut connection)
+        })
+        .await?
+    }
+
+    pub async fn mark_seen(&self, portal: &str, url_id: &str) -> StorageResult<()> {
+        let (portal, url_id): (String, String) = (portal.to_string(), url_id.to_string());
+
+        self.with_connection(move |connection| {
+            connection.execute(
+                "INSERT OR IGNORE INTO seen_ids (portal, url_id, first_seen_at)
+                 VALUES (?1, ?2, ?3)",
+                params![portal, url_id, unix_timestamp()],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl ListingStorage for SqliteStorage {
+    async fn start_run(&self, portal: &str) -> StorageResult<i64> {
+        let portal: String = portal.to_string();
+
+        self.with_connection(move |connection| {
+            connection.execute(
+                "INSERT INTO scrape_runs (portal, started_at) VALUES (?1, ?2)",
+                params![portal, unix_timestamp()],
+            )?;
+            Ok(connection.last_insert_rowid())
+        })
+        .await
+    }
+
+    async fn finish_run(&self, run_id: i64, _portal: &str, no_listings: u32) -> StorageResult<()> {
+        self.with_connection(move |connection| {
+            connection.execute(
+                "UPDATE scrape_runs SET finished_at = ?1, no_listings = ?2 WHERE id = ?3",
+                params![unix_timestamp(), no_listings, run_id],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn is_seen(&self, portal: &str, url_id: &str) -> StorageResult<bool> {
+        let (portal, url_id): (String, String) = (portal.to_string(), url_id.to_string());
+
+        self.with_connection(move |connection| {
+            let seen: Option<i64> = connection
+                .query_row(
+                    "SELECT 1 FROM seen_ids WHERE portal = ?1 AND url_id = ?2",
+                    params![portal, url_id],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            Ok(seen.is_some())
+        })
+        .await
+    }
+
+    async fn save_listing(
+        &self,
+        run_id: i64,
+        listing_raw: &str,
+        listing: &Listing,
+    ) -> StorageResult<()> {
+        let (listing_raw, listing): (String, Listing) = (listing_raw.to_string(), listing.clone());
+
+        self.with_connection(move |connection| {
+            let transaction = connection.transaction()?;
+            let now: i64 = unix_timestamp();
+
+            upsert_listing(&transaction, Some(run_id), &listing_raw, &listing, now)?;
+            insert_observation(&transaction, &listing, now)?;
+            transaction.execute(
+                "INSERT OR IGNORE INTO seen_ids (portal, url_id, first_seen_at)
+                 VALUES (?1, ?2, ?3)",
+                params![listing.portal, listing.url_id, now],
+            )?;
+
+            transaction.commit()?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn save_llm_result(
+        &self,
+        url_id: &str,
+        model: &str,
+        response: &str,
+    ) -> StorageResult<()> {
+        let (url_id, model, response): (String, String, String) =
+            (url_id.to_string(), model.to_string(), response.to_string());
+
+        self.with_connection(move |connection| {
+            connection.execute(
+                "INSERT OR REPLACE INTO llm_results (url_id, model, created_at, response)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![url_id, model, unix_timestamp(), response],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn raw_listings(&self, portal: &str) -> StorageResult<Vec<String>> {
+        let portal: String = portal.to_string();
+
+        self.with_connection(move |connection| {
+            let mut statement = connection
+                .prepare("SELECT raw FROM listings WHERE portal = ?1 ORDER BY scraped_at")?;
+            let raw_listings = statement
+                .query_map(params![portal], |row| row.get(0))?
+                .collect::<rusqlite::Result<Vec<String>>>()?;
+            Ok(raw_listings)
+        })
+        .await
+    }
+
+    async fn listings_to_revisit(
+        &self,
+        portal: &str,
+        observed_before: i64,
+    ) -> StorageResult<Vec<String>> {
+        let portal: String = portal.to_string();
+
+        self.with_connection(move |connection| {
+            let mut statement = connection.prepare(
+                "SELECT l.url_id FROM listings l
+                 WHERE l.portal = ?1
+                   AND COALESCE(
+                       (SELECT MAX(o.observed_at) FROM observations o
+                        WHERE o.portal = l.portal AND o.url_id = l.url_id),
+                       l.scraped_at) < ?2
+                   AND l.delisted_at IS NULL
+                 ORDER BY l.scraped_at",
+            )?;
+            let url_ids = statement
+                .query_map(params![portal, observed_before], |row| row.get(0))?
+                .collect::<rusqlite::Result<Vec<String>>>()?;
+            Ok(url_ids)
+        })
+        .await
+    }
+
+    async fn save_observation(
+        &self,
+        listing_raw: &str,
+        listing: &Listing,
+    ) -> StorageResult<Vec<ListingEvent>> {
+        let (listing_raw, listing): (String, Listing) = (listing_raw.to_string(), listing.clone());
+
+        self.with_connection(move |connection| {
+            let transaction = connection.transaction()?;
+            let now: i64 = unix_timestamp();
+
+            let last_observation: Option<(Option<i64>, Option<String>)> = transaction
+                .query_row(
+                    "SELECT price_cents, description FROM observations
+                     WHERE portal = ?1 AND url_id = ?2
+                     ORDER BY observed_at DESC, id DESC LIMIT 1",
+                    params![listing.portal, listing.url_id],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .optional()?;
+
+            let mut events: Vec<ListingEvent> = Vec::new();
+
+            if let Some((price_cents, description)) = last_observation {
+                let new_price_cents: Option<i64> = listing.price_cents.map(|price| price as i64);
+                let event =
+                    |kind: ListingEventKind, old_value: Option<String>, new_value| ListingEvent {
+                        portal: listing.portal.clone(),
+                        url_id: listing.url_id.clone(),
+                        occurred_at: now,
+                        kind,
+                        old_value,
+                        new_value,
+                    };
+
+                if price_cents != new_price_cents {
+                    events.push(event(
+                        ListingEventKind::PriceChange,
+                        price_cents.map(|price| price.to_string()),
+                        new_price_cents.map(|price| price.to_string()),
+                    ));
+                }
+                if description != listing.description {
+                    events.push(event(
+                        ListingEventKind::DescriptionChange,
+                        description,
+                        listing.description.clone(),
+                    ));
+                }
+            }
+
+            for event in &events {
+                insert_event(&transaction, event)?;
+            }
+            upsert_listing(&transaction, None, &listing_raw, &listing, now)?;
+            insert_observation(&transaction, &listing, now)?;
+
+            transaction.commit()?;
+            Ok(events)
+        })
+        .await
+    }
+
+    async fn save_delisting(&self, portal: &str, url_id: &str) -> StorageResult<ListingEvent> {
+        let event: ListingEvent = ListingEvent {
+            portal: portal.to_string(),
+            url_id: url_id.to_string(),
+            occurred_at: unix_timestamp(),
+            kind: ListingEventKind::Delisted,
+            old_value: None,
+            new_value: None,
+        };
+
+        self.with_connection(move |connection| {
+            let transaction = connection.transaction()?;
+
+            insert_event(&transaction, &event)?;
+            transaction.execute(
+                "UPDATE listings SET delisted_at = ?1 WHERE portal = ?2 AND url_id = ?3",
+                params![event.occurred_at, event.portal, event.url_id],
+            )?;
+
+            transaction.commit()?;
+            Ok(event)
+        })
+        .await
+    }
+
+    async fn price_drops(
+        &self,
+        min_drop_percentage: f64,
+        since: i64,
+    ) -> StorageResult<Vec<PriceDrop>> {
+        self.with_connection(move |connection| {
+            // Compares the price before the first change in the window with the price after the
+            // last one
+            let mut statement = connection.prepare(
+                "WITH changes AS (
+                    SELECT portal, url_id, occurred_at,
+                           CAST(old_value AS INTEGER) AS old_price,
+                           CAST(new_value AS INTEGER) AS new_price,
+                           ROW_NUMBER() OVER (
+                               PARTITION BY portal, url_id ORDER BY occurred_at, id) AS first_rank,
+                           ROW_NUMBER() OVER (
+                               PARTITION BY portal, url_id ORDER BY occurred_at DESC, id DESC) AS last_rank
+                    FROM listing_events
+                    WHERE kind = 'price_change' AND occurred_at >= ?1
+                )
+                SELECT first.portal, first.url_id, l.url, first.old_price, last.new_price,
+                       (first.old_price - last.new_price) * 100.0 / first.old_price AS drop_percentage,
+                       last.occurred_at
+                FROM changes first
+                JOIN changes last
+                  ON last.portal = first.portal AND last.url_id = first.url_id AND last.last_rank = 1
+                JOIN listings l ON l.portal = first.portal AND l.url_id = first.url_id
+                WHERE first.first_rank = 1
+                  AND first.old_price > 0 AND last.new_price IS NOT NULL
+                  AND (first.old_price - last.new_price) * 100.0 / first.old_price > ?2
+                ORDER BY drop_percentage DESC",
+            )?;
+            let price_drops = statement
+                .query_map(params![since, min_drop_percentage], |row| {
+                    Ok(PriceDrop {
+                        portal: row.get(0)?,
+                        url_id: row.get(1)?,
+                        url: row.get(2)?,
+                        old_price_cents: row.get::<_, i64>(3)? as u64,
+                        new_price_cents: row.get::<_, i64>(4)? as u64,
+                        drop_percentage: row.get(5)?,
+                        changed_at: row.get(6)?,
+                    })
+                })?
+                .collect::<rusqlite::Result<Vec<PriceDrop>>>()?;
+            Ok(price_drops)
+        })
+        .await
+    }
+
+    async fn time_on_market(&self) -> StorageResult<Vec<TimeOnMarket>> {
+        self.with_connection(move |connection| {
+            let now: i64 = unix_timestamp();
+            let mut statement = connection.prepare(
+                "SELECT l.portal, l.url_id, l.district,
+                        COALESCE(s.first_seen_at, l.scraped_at) AS listed_at, l.delisted_at
+                 FROM listings l
+                 LEFT JOIN seen_ids s ON s.portal = l.portal AND s.url_id = l.url_id",
+            )?;
+            let time_on_market = statement
+                .query_map([], |row| {
+                    let listed_at: i64 = row.get(3)?;
+                    let delisted_at: Option<i64> = row.get(4)?;
+
+                    Ok(TimeOnMarket {
+                        portal: row.get(0)?,
+                        url_id: row.get(1)?,
+                        district: row.get(2)?,
+                        listed_at,
+                        delisted_at,
+                        days_on_market: (delisted_at.unwrap_or(now) - listed_at) as f64
+                            / SECONDS_PER_DAY as f64,
+                    })
+                })?
+                .collect::<rusqlite::Result<Vec<TimeOnMarket>>>()?;
+            Ok(time_on_market)
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn listing(url_id: &str, price_cents: u64, description: &str) -> Listing {
+        Listing {
+            portal: "remax".to_string(),
+            url_id: url_id.to_string(),
+            url: format!("https://www.remax.pt/pt/imoveis/{}", url_id),
+            price_cents: Some(price_cents),
+            description: Some(description.to_string()),
+            ..Listing::default()
+        }
+    }
+
+    async fn execute(storage: &SqliteStorage, sql: &'static str) {
+        storage
+            .with_connection(move |connection| {
+                connection.execute_batch(sql)?;
+                Ok(())
+            })
+            .await
+            .unwrap();
+    }
+
+    async fn price_change(storage: &SqliteStorage, url_id: &str, at: i64, old: u64, new: u64) {
+        let event: ListingEvent = ListingEvent {
+            portal: "remax".to_string(),
+            url_id: url_id.to_string(),
+            occurred_at: at,
+            kind: ListingEventKind::PriceChange,
+            old_value: Some(old.to_string()),
+            new_value: Some(new.to_string()),
+        };
+        storage
+            .with_connection(move |connection| Ok(insert_event(connection, &event)?))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn records_what_changed_between_observations() {
+        let storage: SqliteStorage = SqliteStorage::open(":memory:").unwrap();
+        let run_id: i64 = storage.start_run("remax").await.unwrap();
+        let listing_v1: Listing = listing("1", 30_000_000, "T2 com varanda");
+        storage
+            .save_listing(run_id, "{}", &listing_v1)
+            .await
+            .unwrap();
+
+        let unchanged: Vec<ListingEvent> =
+            storage.save_observation("{}", &listing_v1).await.unwrap();
+        let events: Vec<ListingEvent> = storage
+            .save_observation(
+                "{}",
+                &listing("1", 28_500_000, "T2 com varanda, remodelado"),
+            )
+            .await
+            .unwrap();
+        let first_seen: Vec<ListingEvent> = storage
+            .save_observation("{}", &listing("2", 10_000_000, "T0"))
+            .await
+            .unwrap();
+
+        assert!(unchanged.is_empty());
+        assert!(first_seen.is_empty());
+        let changes: Vec<(ListingEventKind, Option<&str>, Option<&str>)> = events
+            .iter()
+            .map(|event| {
+                (
+                    event.kind,
+                    event.old_value.as_deref(),
+                    event.new_value.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                (
+                    ListingEventKind::PriceChange,
+                    Some("30000000"),
+                    Some("28500000")
+                ),
+                (
+                    ListingEventKind::DescriptionChange,
+                    Some("T2 com varanda"),
+                    Some("T2 com varanda, remodelado")
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn finds_the_price_drops_since_a_date() {
+        let storage: SqliteStorage = SqliteStorage::open(":memory:").unwrap();
+        let run_id: i64 = storage.start_run("remax").await.unwrap();
+        for url_id in ["dropped", "barely", "before", "raised"] {
+            storage
+                .save_listing(run_id, "{}", &listing(url_id, 100_000, "T1"))
+                .await
+                .unwrap();
+        }
+
+        // Two drops in the window add up, from the first old price to the last new one
+        price_change(&storage, "dropped", 100, 100_000, 95_000).await;
+        price_change(&storage, "dropped", 200, 95_000, 80_000).await;
+        price_change(&storage, "barely", 150, 100_000, 97_000).await;
+        // Only the change after `since` counts
+        price_change(&storage, "before", 50, 100_000, 50_000).await;
+        price_change(&storage, "before", 150, 50_000, 49_000).await;
+        price_change(&storage, "raised", 150, 100_000, 120_000).await;
+
+        let price_drops: Vec<PriceDrop> = storage.price_drops(5.0, 100).await.unwrap();
+
+        assert_eq!(price_drops.len(), 1);
+        let price_drop: &PriceDrop = &price_drops[0];
+        assert_eq!(price_drop.url_id, "dropped");
+        assert_eq!(price_drop.url, "https://www.remax.pt/pt/imoveis/dropped");
+        assert_eq!(
+            (price_drop.old_price_cents, price_drop.new_price_cents),
+            (100_000, 80_000)
+        );
+        assert_eq!(price_drop.drop_percentage, 20.0);
+        assert_eq!(price_drop.changed_at, 200);
+        assert_eq!(storage.price_drops(1.0, 100).await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn revisits_the_listings_not_observed_since_a_date() {
+        let storage: SqliteStorage = SqliteStorage::open(":memory:").unwrap();
+        let run_id: i64 = storage.start_run("remax").await.unwrap();
+        for url_id in ["old", "recent", "older", "delisted"] {
+            storage
+                .save_listing(run_id, "{}", &listing(url_id, 100_000, "T1"))
+                .await
+                .unwrap();
+        }
+        storage.save_delisting("remax", "delisted").await.unwrap();
+        execute(
+            &storage,
+            "UPDATE listings SET scraped_at = 300 WHERE url_id = 'old';
+             UPDATE listings SET scraped_at = 100 WHERE url_id = 'recent';
+             UPDATE listings SET scraped_at = 200 WHERE url_id = 'older';
+             UPDATE observations SET observed_at = 50;
+             UPDATE observations SET observed_at = 1000 WHERE url_id = 'recent';
+             DELETE FROM observations WHERE url_id = 'older';",
+        )
+        .await;
+
+        let url_ids: Vec<String> = storage.listings_to_revisit("remax", 500).await.unwrap();
+
+        // Without observations the scrape time counts, ordered by when they were scrapped
+        assert_eq!(url_ids, vec!["older", "old"]);
+        assert!(storage
+            .listings_to_revisit("era", 500)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
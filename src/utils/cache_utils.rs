@@ -1,35 +1,385 @@
-use crate::utils::file_utils::{get_content_lines, write_to_file};
-use moka::future::Cache;
+use crate::utils::file_utils::{
+    get_content_lines, get_file_read, get_file_write_append, write_atomically, write_to_file,
+};
+use crate::utils::time_utils::unix_timestamp;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
 use std::error::Error;
 use tokio::fs::File;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
 
-pub async fn spawn_cache(opt_file: Option<File>) -> Result<Cache<String, String>, Box<dyn Error>> {
-    match opt_file {
-        None => Ok(Cache::new(1000000)),
-        Some(file) => {
-            let mut cache: Cache<String, String> = Cache::new(1000000);
-            let content_vec: Vec<String> = get_content_lines(file).await?;
-
-            for line in content_vec {
-                let split_line = line.split("|:|").collect::<Vec<&str>>();
-                // println!("Split line {} , {}", split_line[0], split_line[1]);
-                cache
-                    .insert(split_line[0].to_string(), split_line[1].to_string())
-                    .await;
+const CACHE_FORMAT: &str = "res-cache";
+pub const CACHE_FORMAT_VERSION: u32 = 1;
+// Separator of the format used before the versioned one, `key|:|value`
+const LEGACY_SEPARATOR: &str = "|:|";
+
+/// First line of the file
+#[derive(Deserialize, Serialize)]
+struct CacheHeader {
+    format: String,
+    version: u32,
+}
+
+/// Every other line, an insert or when `value` is missing a removal
+#[derive(Deserialize, Serialize)]
+struct CacheRecord {
+    key: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    value: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expires_at: Option<i64>,
+}
+
+struct CachedValue {
+    value: String,
+    expires_at: Option<i64>,
+}
+
+impl CachedValue {
+    fn is_expired(&self, now: i64) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+struct CacheState {
+    entries: BTreeMap<String, CachedValue>,
+    writer: File,
+}
+
+/// Key value cache kept in a Json lines file. Every insert is appended as soon as it happens so
+/// nothing is lost when a run crashes, and the file is rewritten atomically without the removed
+/// and expired entries when opening and compacting it.
+pub struct PersistentCache {
+    path: String,
+    ttl_seconds: Option<i64>,
+    state: Mutex<CacheState>,
+}
+
+type CacheResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
+
+fn read_entries(lines: Vec<String>) -> CacheResult<BTreeMap<String, CachedValue>> {
+    let mut entries: BTreeMap<String, CachedValue> = BTreeMap::new();
+    let mut lines = lines.into_iter().filter(|line| !line.trim().is_empty());
+
+    let first_line: String = match lines.next() {
+        Some(first_line) => first_line,
+        None => return Ok(entries),
+    };
+
+    match serde_json::from_str::<CacheHeader>(&first_line) {
+        Ok(header) if header.format == CACHE_FORMAT => {
+            if header.version > CACHE_FORMAT_VERSION {
+                return Err(format!(
+                    "cache version {} is newer than the supported {}",
+                    header.version, CACHE_FORMAT_VERSION
+                )
+                .into());
+            }
+        }
+        _ => {
+            println!("Migrating the cache from the `{}` format", LEGACY_SEPARATOR);
+            for line in std::iter::once(first_line).chain(lines) {
+                if let Some((key, value)) = line.split_once(LEGACY_SEPARATOR) {
+                    let cached_value: CachedValue = CachedValue {
+                        value: value.to_string(),
+                        expires_at: None,
+                    };
+                    entries.insert(key.to_string(), cached_value);
+                }
+            }
+            return Ok(entries);
+        }
+    }
+
+    let mut no_invalid_lines: usize = 0;
+    for line in lines {
+        // The last line can be cut in half when a run is killed while writing it
+        let record: CacheRecord = match serde_json::from_str(&line) {
+            Ok(record) => record,
+            Err(_) => {
+                no_invalid_lines += 1;
+                continue;
+            }
+        };
+
+        match record.value {
+            Some(value) => {
+                let cached_value: CachedValue = CachedValue {
+                    value,
+                    expires_at: record.expires_at,
+                };
+                entries.insert(record.key, cached_value);
+            }
+            None => {
+                entries.remove(&record.key);
+            }
+        }
+    }
+
+    if no_invalid_lines > 0 {
+        println!("Skipped {} invalid cache lines", no_invalid_lines);
+    }
+
+    Ok(entries)
+}
+
+fn to_line<T: Serialize>(value: &T) -> String {
+    format!("{}\n", json!(value))
+}
+
+impl PersistentCache {
+    /// Loads the cache at `path`, entries inserted from now on expire after `ttl_seconds`
+    pub async fn open(path: &str, ttl_seconds: Option<i64>) -> CacheResult<PersistentCache> {
+        let lines: Vec<String> = match get_file_read(path).await {
+            Ok(file) => get_content_lines(file).await?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut entries: BTreeMap<String, CachedValue> = read_entries(lines)?;
+        let now: i64 = unix_timestamp();
+        entries.retain(|_, cached_value| !cached_value.is_expired(now));
+
+        write_atomically(path, PersistentCache::content(&entries)).await?;
+        println!("Loaded {} cache entries from {}", entries.len(), path);
+
+        Ok(PersistentCache {
+            path: path.to_string(),
+            ttl_seconds,
+            state: Mutex::new(CacheState {
+                entries,
+                writer: get_file_write_append(path).await?,
+            }),
+        })
+    }
+
+    fn content(entries: &BTreeMap<String, CachedValue>) -> String {
+        let header: CacheHeader = CacheHeader {
+            format: CACHE_FORMAT.to_string(),
+            version: CACHE_FORMAT_VERSION,
+        };
+        let mut content: String = to_line(&header);
+
+        for (key, cached_value) in entries {
+            content.push_str(&to_line(&CacheRecord {
+                key: key.clone(),
+                value: Some(cached_value.value.clone()),
+                expires_at: cached_value.expires_at,
+            }));
+        }
+
+        content
+    }
+
+    async fn append(writer: &mut File, record: &CacheRecord) -> CacheResult<()> {
+        write_to_file(writer, to_line(record)).await?;
+        writer.flush().await?;
+        Ok(())
+    }
+
+    pub async fn get(&self, key: &str) -> Option<String> {
+        let state = self.state.lock().await;
+
+        state
+            .entries
+            .get(key)
+            .filter(|cached_value| !cached_value.is_expired(unix_timestamp()))
+            .map(|cached_value| cached_value.value.clone())
+    }
+
+    pub async fn insert(&self, key: String, value: String) -> CacheResult<()> {
+        let mut state = self.state.lock().await;
+        let record: CacheRecord = CacheRecord {
+            key,
+            value: Some(value),
+            expires_at: self
+                .ttl_seconds
+                .map(|ttl_seconds| unix_timestamp() + ttl_seconds),
+        };
+
+        PersistentCache::append(&mut state.writer, &record).await?;
+        let cached_value: CachedValue = CachedValue {
+            value: record.value.unwrap_or_default(),
+            expires_at: record.expires_at,
+        };
+        state.entries.insert(record.key, cached_value);
+        Ok(())
+    }
+
+    pub async fn remove(&self, key: &str) -> CacheResult<Option<String>> {
+        let mut state = self.state.lock().await;
+
+        match state.entries.remove(key) {
+            Some(cached_value) => {
+                let record: CacheRecord = CacheRecord {
+                    key: key.to_string(),
+                    value: None,
+                    expires_at: None,
+                };
+                PersistentCache::append(&mut state.writer, &record).await?;
+                Ok(Some(cached_value.value))
             }
-            Ok(cache)
+            None => Ok(None),
         }
     }
+
+    /// Rewrites the file with only the live entries
+    pub async fn compact(&self) -> CacheResult<()> {
+        let mut state = self.state.lock().await;
+        let now: i64 = unix_timestamp();
+        state
+            .entries
+            .retain(|_, cached_value| !cached_value.is_expired(now));
+
+        println!("Compacting {} cache entries", state.entries.len());
+        write_atomically(&self.path, PersistentCache::content(&state.entries)).await?;
+        // The old file was replaced, appending has to continue on the new one
+        state.writer = get_file_write_append(&self.path).await?;
+        Ok(())
+    }
 }
 
-pub async fn export(
-    cache: &Cache<String, String>,
-    file_writer: &mut File,
-) -> Result<(), Box<dyn Error>> {
-    cache.run_pending_tasks().await;
-    println!("Exporting {} elements of the cache", cache.entry_count());
-    for (key, value) in cache.iter() {
-        write_to_file(file_writer, format!("{}|:|{}\n", key, value)).await?
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn cache_path(name: &str) -> String {
+        env::temp_dir()
+            .join(format!("res-cache-{}-{}.jsonl", name, unix_timestamp()))
+            .to_string_lossy()
+            .to_string()
+    }
+
+    async fn file_lines(path: &str) -> Vec<String> {
+        get_content_lines(get_file_read(path).await.unwrap())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn round_trips_values_with_newlines_and_the_legacy_separator() {
+        let path: String = cache_path("round-trip");
+        let value: String = "{\"summary\": \"T2\nremodelado\"}|:|fim".to_string();
+
+        let cache: PersistentCache = PersistentCache::open(&path, None).await.unwrap();
+        cache
+            .insert("a|:|b".to_string(), value.clone())
+            .await
+            .unwrap();
+        drop(cache);
+        let cache: PersistentCache = PersistentCache::open(&path, None).await.unwrap();
+
+        assert_eq!(cache.get("a|:|b").await, Some(value));
+        assert_eq!(cache.get("a").await, None);
+
+        let _ = tokio::fs::remove_file(&path).await;
+    }
+
+    #[tokio::test]
+    async fn migrates_the_legacy_format() {
+        let path: String = cache_path("legacy");
+        write_atomically(
+            &path,
+            "remax-1|:|{\"score\": 7}\nremax-2|:|a|:|b\nsem separador\n",
+        )
+        .await
+        .unwrap();
+
+        let cache: PersistentCache = PersistentCache::open(&path, None).await.unwrap();
+
+        assert_eq!(
+            cache.get("remax-1").await,
+            Some("{\"score\": 7}".to_string())
+        );
+        assert_eq!(cache.get("remax-2").await, Some("a|:|b".to_string()));
+        assert_eq!(file_lines(&path).await.len(), 3);
+        assert!(file_lines(&path).await[0].contains(CACHE_FORMAT));
+
+        let _ = tokio::fs::remove_file(&path).await;
+    }
+
+    #[tokio::test]
+    async fn skips_a_truncated_last_line() {
+        let path: String = cache_path("truncated");
+        let header: String = format!(
+            "{{\"format\": \"{}\", \"version\": {}}}",
+            CACHE_FORMAT, CACHE_FORMAT_VERSION
+        );
+        write_atomically(
+            &path,
+            format!(
+                "{}\n{{\"key\": \"a\", \"value\": \"1\"}}\n{{\"key\": \"b\", \"val",
+                header
+            ),
+        )
+        .await
+        .unwrap();
+
+        let cache: PersistentCache = PersistentCache::open(&path, None).await.unwrap();
+
+        assert_eq!(cache.get("a").await, Some("1".to_string()));
+        assert_eq!(cache.get("b").await, None);
+
+        let _ = tokio::fs::remove_file(&path).await;
+    }
+
+    #[tokio::test]
+    async fn expires_entries_after_the_ttl() {
+        let path: String = cache_path("ttl");
+
+        let expired: PersistentCache = PersistentCache::open(&path, Some(-1)).await.unwrap();
+        expired
+            .insert("a".to_string(), "1".to_string())
+            .await
+            .unwrap();
+        assert_eq!(expired.get("a").await, None);
+        drop(expired);
+
+        let cache: PersistentCache = PersistentCache::open(&path, Some(3_600)).await.unwrap();
+        assert_eq!(file_lines(&path).await.len(), 1);
+        cache
+            .insert("b".to_string(), "2".to_string())
+            .await
+            .unwrap();
+        assert_eq!(cache.get("b").await, Some("2".to_string()));
+
+        let _ = tokio::fs::remove_file(&path).await;
+    }
+
+    #[tokio::test]
+    async fn compacts_to_the_latest_values() {
+        let path: String = cache_path("compact");
+        let cache: PersistentCache = PersistentCache::open(&path, None).await.unwrap();
+
+        for value in ["1", "2", "3"] {
+            cache
+                .insert("a".to_string(), value.to_string())
+                .await
+                .unwrap();
+        }
+        cache
+            .insert("b".to_string(), "1".to_string())
+            .await
+            .unwrap();
+        assert_eq!(cache.remove("b").await.unwrap(), Some("1".to_string()));
+        assert_eq!(file_lines(&path).await.len(), 6);
+
+        cache.compact().await.unwrap();
+        assert_eq!(file_lines(&path).await.len(), 2);
+        cache
+            .insert("c".to_string(), "1".to_string())
+            .await
+            .unwrap();
+        drop(cache);
+
+        let cache: PersistentCache = PersistentCache::open(&path, None).await.unwrap();
+        assert_eq!(cache.get("a").await, Some("3".to_string()));
+        assert_eq!(cache.get("b").await, None);
+        assert_eq!(cache.get("c").await, Some("1".to_string()));
+
+        let _ = tokio::fs::remove_file(&path).await;
     }
-    Ok(())
 }
@@ -0,0 +1,33 @@
+use std::env;
+use std::error::Error;
+use std::str::FromStr;
+
+/// The number in the environment variable `name`, `default` when it is not set
+pub fn env_number<T: FromStr>(name: &str, default: T) -> Result<T, Box<dyn Error + Send + Sync>> {
+    match env::var(name) {
+        Ok(value) => Ok(value
+            .parse()
+            .map_err(|_| format!("`{}` should be a number", name))?),
+        Err(_) => Ok(default),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_numbers_from_the_environment() {
+        env::set_var("RES_TEST_ENV_NUMBER", "0.5");
+        env::set_var("RES_TEST_ENV_NOT_A_NUMBER", "half");
+
+        assert_eq!(env_number("RES_TEST_ENV_NUMBER", 1.0).unwrap(), 0.5);
+        assert_eq!(env_number("RES_TEST_ENV_UNSET", 3).unwrap(), 3);
+        assert_eq!(
+            env_number("RES_TEST_ENV_NOT_A_NUMBER", 1.0)
+                .unwrap_err()
+                .to_string(),
+            "`RES_TEST_ENV_NOT_A_NUMBER` should be a number"
+        );
+    }
+}
@@ -1,5 +1,5 @@
 use tokio::fs::{File, OpenOptions};
-use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 
 pub async fn get_file_read(path: &str) -> Result<File, std::io::Error> {
     let file_read: File = OpenOptions::new().read(true).open(path).await?;
@@ -28,13 +28,6 @@ pub async fn get_file_write_truncate(path: &str) -> Result<File, std::io::Error>
     Ok(file_write)
 }
 
-pub async fn get_content_as_string(mut file: File) -> Result<String, std::io::Error> {
-    let mut content = String::new();
-    file.read_to_string(&mut content).await?;
-
-    Ok(content)
-}
-
 pub async fn get_content_lines(file: File) -> Result<Vec<String>, std::io::Error> {
     let reader = BufReader::new(file);
 
@@ -52,3 +45,13 @@ pub async fn write_to_file(file: &mut File, content: String) -> Result<(), std::
     file.write_all(content.as_bytes()).await?;
     Ok(())
 }
+
+/// Writes to a temporary file and renames it over `path`, so a crash never leaves it half written
+pub async fn write_atomically(path: &str, content: impl AsRef<[u8]>) -> Result<(), std::io::Error> {
+    let temporary_path: String = format!("{}.tmp", path);
+    let mut file: File = get_file_write_truncate(&temporary_path).await?;
+
+    file.write_all(content.as_ref()).await?;
+    file.sync_all().await?;
+    tokio::fs::rename(&temporary_path, path).await
+}
@@ -0,0 +1,353 @@
+use crate::schemas::listing::{Listing, PropertyKind, Transaction, Typology};
+use crate::scrappers::scrapper_utils::PORTUGUESE_DISTRICTS;
+use crate::utils::parsing_utils::{parse_area, parse_price, ParseError};
+use regex::Regex;
+use std::sync::LazyLock;
+
+// Matched against the listing text lowercased and without accents
+const PROPERTY_KINDS: [(&str, PropertyKind); 12] = [
+    ("apartamento", PropertyKind::Apartment),
+    ("duplex", PropertyKind::Apartment),
+    ("moradia", PropertyKind::House),
+    ("vivenda", PropertyKind::House),
+    ("terreno", PropertyKind::Land),
+    ("loja", PropertyKind::Shop),
+    ("escritorio", PropertyKind::Office),
+    ("armazem", PropertyKind::Warehouse),
+    ("lugar de garagem", PropertyKind::Garage),
+    ("quinta", PropertyKind::Farm),
+    ("herdade", PropertyKind::Farm),
+    ("predio", PropertyKind::Building),
+];
+
+const FEATURES: [(&str, &str); 13] = [
+    ("garagem", "garage"),
+    ("estacionamento", "garage"),
+    ("piscina", "pool"),
+    ("elevador", "elevator"),
+    ("varanda", "balcony"),
+    ("terraco", "terrace"),
+    ("jardim", "garden"),
+    ("ar condicionado", "air_conditioning"),
+    ("arrecadacao", "storage"),
+    ("lareira", "fireplace"),
+    ("vista mar", "sea_view"),
+    ("mobilado", "furnished"),
+    ("paineis solares", "solar_panels"),
+];
+
+// Matched against the price lowercased and without accents, rents are shown per period
+const RENT_PERIODS: [(&str, f64); 5] = [
+    ("/mes", 1.0),
+    ("por mes", 1.0),
+    ("mensa", 1.0),
+    ("/semana", 52.0 / 12.0),
+    ("/ano", 1.0 / 12.0),
+];
+
+static TYPOLOGY_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\bt(\d{1,2})\b").unwrap());
+
+// The number before the word, "3 quartos", or after it, "Quartos: 3"
+static BEDROOMS_REGEXES: LazyLock<[Regex; 2]> = LazyLock::new(|| {
+    [
+        Regex::new(r"(\d+) *quartos?\b").unwrap(),
+        Regex::new(r"quartos?\D{0,3}(\d+)").unwrap(),
+    ]
+});
+
+static BATHROOMS_REGEXES: LazyLock<[Regex; 2]> = LazyLock::new(|| {
+    [
+        Regex::new(r"(\d+) *(?:casas? de banho|wc)\b").unwrap(),
+        Regex::new(r"(?:casas? de banho|wc)\D{0,3}(\d+)").unwrap(),
+    ]
+});
+
+/// Every district as a whole word, longest names first so Bragança is not Braga and neither
+/// "farol" nor "guarda-roupa" are districts
+static DISTRICT_REGEXES: LazyLock<Vec<(&str, Regex)>> = LazyLock::new(|| {
+    let mut districts: Vec<&str> = PORTUGUESE_DISTRICTS.to_vec();
+    districts.sort_by_key(|district| std::cmp::Reverse(district.len()));
+
+    districts
+        .into_iter()
+        .map(|district| {
+            let name: String = district.replace('-', "[ -]");
+            (
+                district,
+                Regex::new(&format!(r"(?:^|[^\w-]){}(?:$|[^\w-])", name)).unwrap(),
+            )
+        })
+        .collect()
+});
+
+static CONCELHO_REGEX: LazyLock<Regex> = LazyLock::new(|| labelled_value_regex("concelho"));
+
+static FREGUESIA_REGEX: LazyLock<Regex> = LazyLock::new(|| labelled_value_regex("freguesia"));
+
+static MIN_CONTRACT_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?:contrato|duracao|prazo|estadia)\s+minim[oa][^\d\n]{0,20}(\d+)\s*(mes|ano)")
+        .unwrap()
+});
+
+/// Lowercases and strips the Portuguese accents so keywords can be matched regardless of spelling
+pub fn fold_text(text: &str) -> String {
+    text.to_lowercase()
+        .chars()
+        .map(|c| match c {
+            'á' | 'à' | 'â' | 'ã' => 'a',
+            'é' | 'ê' => 'e',
+            'í' => 'i',
+            'ó' | 'ô' | 'õ' => 'o',
+            'ú' => 'u',
+            'ç' => 'c',
+            _ => c,
+        })
+        .collect()
+}
+
+fn capture_u32(regex: &Regex, text: &str) -> Option<u32> {
+    regex
+        .captures(text)
+        .and_then(|captures| captures[1].parse::<u32>().ok())
+}
+
+fn extract_typology(texts: &[&str]) -> Option<Typology> {
+    texts
+        .iter()
+        .find_map(|text| capture_u32(&TYPOLOGY_REGEX, text))
+        .map(Typology::from_bedrooms)
+}
+
+fn extract_no_bedrooms(details: &str) -> Option<u32> {
+    BEDROOMS_REGEXES
+        .iter()
+        .find_map(|regex| capture_u32(regex, details))
+}
+
+fn extract_no_bathrooms(details: &str) -> Option<u32> {
+    BATHROOMS_REGEXES
+        .iter()
+        .find_map(|regex| capture_u32(regex, details))
+}
+
+fn extract_area(details: &str) -> Result<Option<f32>, ParseError> {
+    let area_lines: Vec<&str> = details
+        .lines()
+        .filter(|line| line.contains("m²") || line.contains("m2"))
+        .collect();
+
+    let usable_area = area_lines
+        .iter()
+        .filter(|line| line.contains("util"))
+        .find_map(|line| parse_area(line).ok());
+
+    match usable_area.or_else(|| area_lines.iter().find_map(|line| parse_area(line).ok())) {
+        Some(area) => Ok(Some(area)),
+        None => match area_lines.first() {
+            Some(line) => parse_area(line).map(Some),
+            None => Ok(None),
+        },
+    }
+}
+
+/// The first district named in the texts
+fn extract_district(texts: &[&str]) -> Option<String> {
+    texts.iter().find_map(|text| {
+        DISTRICT_REGEXES
+            .iter()
+            .find(|(_, regex)| regex.is_match(text))
+            .map(|(district, _)| district.to_string())
+    })
+}
+
+/// Captures the value after a label, as "Concelho: Lisboa", "Concelho Lisboa" or on the next line
+fn labelled_value_regex(label: &str) -> Regex {
+    Regex::new(&format!(r"(?i)\b{}\b[ \t]*:?\s*([^\n]+)", label)).unwrap()
+}
+
+fn extract_labelled_value(details: &str, label_regex: &Regex) -> Option<String> {
+    label_regex
+        .captures(details)
+        .map(|captures| captures[1].trim().to_string())
+}
+
+/// Freguesia and concelho of the address a portal shows, as "Arroios, Lisboa" or with the street
+/// and the district around them, "Rua Cândido dos Reis, Cacilhas, Almada, Setúbal"
+fn parse_location(location: &str) -> (Option<String>, Option<String>) {
+    let mut parts: Vec<&str> = location
+        .split(',')
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .collect();
+    if parts.len() > 2 && extract_district(&[&fold_text(parts[parts.len() - 1])]).is_some() {
+        parts.pop();
+    }
+
+    let concelho: Option<String> = parts.pop().map(str::to_string);
+    (parts.pop().map(str::to_string), concelho)
+}
+
+/// Whether the text mentions the keyword other than as "não X" or "sem X"
+fn mentions(text: &str, keyword: &str) -> bool {
+    text.match_indices(keyword)
+        .any(|(index, _)| !matches!(text[..index].split_whitespace().last(), Some("nao" | "sem")))
+}
+
+fn extract_property_kind(texts: &[&str]) -> Option<PropertyKind> {
+    texts.iter().find_map(|text| {
+        PROPERTY_KINDS
+            .iter()
+            .find(|(keyword, _)| text.contains(keyword))
+            .map(|(_, kind)| *kind)
+    })
+}
+
+/// How many times the price is paid in a month, `None` for a price that is not a rent
+fn rent_period(price: Option<&str>) -> Option<f64> {
+    let folded_price: String = fold_text(price.unwrap_or_default()).replace(' ', "");
+
+    RENT_PERIODS
+        .iter()
+        .find(|(period, _)| folded_price.contains(&period.replace(' ', "")))
+        .map(|(_, per_month)| *per_month)
+}
+
+fn extract_furnished(text: &str) -> Option<bool> {
+    let keywords: [&str; 3] = ["mobilado", "mobilada", "mobilia"];
+
+    if keywords.iter().any(|keyword| mentions(text, keyword)) {
+        Some(true)
+    } else if keywords.iter().any(|keyword| text.contains(keyword)) || text.contains("sem moveis") {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// Months of "contrato mínimo de 12 meses" or "duração mínima: 1 ano"
+fn extract_min_contract_months(text: &str) -> Option<u32> {
+    MIN_CONTRACT_REGEX.captures(text).and_then(|captures| {
+        let number: u32 = captures[1].parse::<u32>().ok()?;
+        match &captures[2] {
+            "ano" => Some(number * 12),
+            _ => Some(number),
+        }
+    })
+}
+
+fn extract_features(text: &str) -> Vec<String> {
+    let mut features: Vec<String> = Vec::new();
+
+    for (keyword, feature) in FEATURES {
+        if mentions(text, keyword) && !features.iter().any(|f| f == feature) {
+            features.push(feature.to_string());
+        }
+    }
+
+    features
+}
+
+/// Builds a [`Listing`] out of the loosely structured text every portal gives us
+pub fn normalize_listing(
+    portal: &str,
+    url_id: &str,
+    url: String,
+    price: Option<&str>,
+    details: &[String],
+    description: Option<&str>,
+    location: Option<&str>,
+) -> Listing {
+    let details_text: String = details.join("\n");
+    let folded_details: String = fold_text(&details_text);
+    let folded_url_id: String = fold_text(url_id).replace(['-', '/', '_'], " ");
+    let folded_description: String = fold_text(description.unwrap_or_default());
+
+    let mut parse_errors: Vec<String> = Vec::new();
+    let mut report = |error: ParseError| {
+        println!("Failed to parse {} listing {}: {}", portal, url_id, error);
+        parse_errors.push(error.to_string());
+    };
+
+    let rent_period: Option<f64> = rent_period(price);
+    let price_cents: Option<u64> = parse_price(price)
+        .unwrap_or_else(|e| {
+            report(e);
+            None
+        })
+        .map(|price_cents| match rent_period {
+            Some(per_month) => (price_cents as f64 * per_month).round() as u64,
+            None => price_cents,
+        });
+    let area_sqr_meters: Option<f32> = extract_area(&folded_details).unwrap_or_else(|e| {
+        report(e);
+        None
+    });
+
+    let typology: Option<Typology> =
+        extract_typology(&[&folded_details, &folded_url_id, &folded_description]);
+    let no_bedrooms: Option<u32> = extract_no_bedrooms(&folded_details).or(typology
+        .filter(|typology| *typology != Typology::T6Plus)
+        .map(|typology| typology.no_bedrooms()));
+    // The labels of the details are more precise than the address when the portal has both
+    let (freguesia, concelho): (Option<String>, Option<String>) =
+        location.map(parse_location).unwrap_or_default();
+
+    Listing {
+        portal: portal.to_string(),
+        url_id: url_id.to_string(),
+        url,
+        transaction: match rent_period {
+            Some(_) => Transaction::Rent,
+            None => Transaction::Buy,
+        },
+        price_cents,
+        area_sqr_meters,
+        typology: typology.or(no_bedrooms.map(Typology::from_bedrooms)),
+        no_bedrooms,
+        no_bathrooms: extract_no_bathrooms(&folded_details),
+        district: extract_district(&[&folded_url_id, &folded_details, &folded_description]),
+        concelho: extract_labelled_value(&details_text, &CONCELHO_REGEX).or(concelho),
+        freguesia: extract_labelled_value(&details_text, &FREGUESIA_REGEX).or(freguesia),
+        property_kind: extract_property_kind(&[
+            &folded_url_id,
+            &folded_details,
+            &folded_description,
+        ]),
+        features: extract_features(&format!("{}\n{}", folded_details, folded_description)),
+        furnished: extract_furnished(&format!("{}\n{}", folded_details, folded_description)),
+        min_contract_months: extract_min_contract_months(&format!(
+            "{}\n{}",
+            folded_details, folded_description
+        )),
+        description: description.map(|description| description.to_string()),
+        parse_errors,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_the_freguesia_and_concelho_of_addresses() {
+        let some = |freguesia: Option<&str>, concelho: &str| {
+            (freguesia.map(str::to_string), Some(concelho.to_string()))
+        };
+
+        assert_eq!(
+            parse_location("Arroios, Lisboa"),
+            some(Some("Arroios"), "Lisboa")
+        );
+        assert_eq!(
+            parse_location("Rua Cândido dos Reis, Cacilhas, Almada, Setúbal"),
+            some(Some("Cacilhas"), "Almada")
+        );
+        assert_eq!(
+            parse_location("Lordelo do Ouro e Massarelos, Porto, Porto"),
+            some(Some("Lordelo do Ouro e Massarelos"), "Porto")
+        );
+        assert_eq!(parse_location(" Cascais "), some(None, "Cascais"));
+        assert_eq!(parse_location(""), (None, None));
+    }
+}
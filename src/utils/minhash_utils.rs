@@ -0,0 +1,123 @@
+use crate::utils::listing_utils::fold_text;
+use std::collections::HashSet;
+
+pub const NO_HASHES: usize = 64;
+pub const NO_BANDS: usize = 16;
+
+/// FNV-1a, unlike the std hasher it gives the same value across runs and Rust versions
+pub fn stable_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+// splitmix64 finalizer, turns one shingle hash into `NO_HASHES` independent ones
+fn mix(value: u64, seed: u64) -> u64 {
+    let mut z: u64 = value ^ seed.wrapping_mul(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+/// Hashes of every `size` consecutive words of the text, ignoring case, accents and punctuation
+pub fn shingles(text: &str, size: usize) -> HashSet<u64> {
+    let folded: String = fold_text(text);
+    let words: Vec<&str> = folded
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect();
+
+    words
+        .windows(size.min(words.len()).max(1))
+        .map(|window| stable_hash(window.join(" ").as_bytes()))
+        .collect()
+}
+
+pub fn minhash_signature(shingles: &HashSet<u64>) -> Vec<u64> {
+    (0..NO_HASHES as u64)
+        .map(|seed| {
+            shingles
+                .iter()
+                .map(|shingle| mix(*shingle, seed))
+                .min()
+                .unwrap_or(u64::MAX)
+        })
+        .collect()
+}
+
+/// Fraction of equal minhashes, an estimate of the Jaccard similarity of the shingles
+pub fn estimated_similarity(signature: &[u64], other_signature: &[u64]) -> f64 {
+    let equal: usize = signature
+        .iter()
+        .zip(other_signature)
+        .filter(|(hash, other_hash)| hash == other_hash && **hash != u64::MAX)
+        .count();
+
+    equal as f64 / NO_HASHES as f64
+}
+
+/// Locality sensitive hashing keys, similar signatures very likely share at least one of them
+pub fn band_keys(signature: &[u64]) -> Vec<u64> {
+    signature
+        .chunks(NO_HASHES / NO_BANDS)
+        .enumerate()
+        .filter(|(_, band)| band.iter().all(|hash| *hash != u64::MAX))
+        .map(|(index, band)| {
+            let mut bytes: Vec<u8> = (index as u64).to_le_bytes().to_vec();
+            for hash in band {
+                bytes.extend_from_slice(&hash.to_le_bytes());
+            }
+            stable_hash(&bytes)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DESCRIPTION: &str = "Apartamento T2 totalmente remodelado junto ao metro de Arroios com \
+        cozinha equipada e varanda com vista desafogada";
+
+    #[test]
+    fn shingles_ignore_case_accents_and_punctuation() {
+        assert_eq!(
+            shingles("Prédio com ELEVADOR, garagem.", 2),
+            shingles("predio com elevador garagem", 2)
+        );
+        assert_eq!(shingles("T2", 3).len(), 1);
+        assert!(shingles("", 3).is_empty());
+    }
+
+    #[test]
+    fn estimates_the_similarity_of_the_shingles() {
+        let signature: Vec<u64> = minhash_signature(&shingles(DESCRIPTION, 3));
+        let edited: Vec<u64> = minhash_signature(&shingles(
+            &DESCRIPTION.replace("desafogada", "para o rio"),
+            3,
+        ));
+        let unrelated: Vec<u64> =
+            minhash_signature(&shingles("Moradia isolada com piscina e jardim", 3));
+        let empty: Vec<u64> = minhash_signature(&HashSet::new());
+
+        assert_eq!(estimated_similarity(&signature, &signature), 1.0);
+        assert!(estimated_similarity(&signature, &edited) > 0.6);
+        assert!(estimated_similarity(&signature, &unrelated) < 0.1);
+        assert_eq!(estimated_similarity(&empty, &empty), 0.0);
+    }
+
+    #[test]
+    fn similar_signatures_share_a_band() {
+        let band_keys_of = |text: &str| -> HashSet<u64> {
+            band_keys(&minhash_signature(&shingles(text, 3)))
+                .into_iter()
+                .collect()
+        };
+        let keys: HashSet<u64> = band_keys_of(DESCRIPTION);
+
+        assert_eq!(keys.len(), NO_BANDS);
+        assert!(!keys.is_disjoint(&band_keys_of(&DESCRIPTION.replace("Arroios", "Alvalade"))));
+        assert!(keys.is_disjoint(&band_keys_of("Moradia isolada com piscina e jardim")));
+        assert!(band_keys(&minhash_signature(&HashSet::new())).is_empty());
+    }
+}
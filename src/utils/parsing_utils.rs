@@ -0,0 +1,194 @@
+use regex::Regex;
+use std::fmt;
+use std::sync::LazyLock;
+
+/// Describes a value the portals gave us that could not be turned into a typed value
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub field: &'static str,
+    pub input: String,
+    pub reason: &'static str,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {:?}: {}", self.field, self.input, self.reason)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+// Portuguese numbers group thousands with a space or a dot and use a comma for decimals. The
+// groups start at a word boundary so the 2 of "t2 120 m²" is not read as 2 120.
+const NUMBER_REGEX: &str = r"\b\d{1,3}(?:[ .]\d{3})+(?:,\d+)?|\d+(?:[.,]\d+)?";
+
+static NUMBER: LazyLock<Regex> = LazyLock::new(|| Regex::new(NUMBER_REGEX).unwrap());
+
+static AREA: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(&format!(r"({})\s*m(?:²|2)", NUMBER_REGEX)).unwrap());
+
+/// Replaces the html entities and the non breaking spaces the portals use between digits
+fn clean_input(raw: &str) -> String {
+    raw.replace("&nbsp;", " ")
+        .replace(['\u{a0}', '\u{202f}', '\u{2009}'], " ")
+        .trim()
+        .to_string()
+}
+
+/// A dot followed by groups of 3 digits separates thousands, "1.250", otherwise decimals, "2.5"
+fn parse_portuguese_number(number: &str) -> Option<f64> {
+    let dot_groups_thousands: bool = number
+        .split('.')
+        .skip(1)
+        .all(|group| group.len() == 3 && group.chars().all(|c| c.is_ascii_digit()));
+    let number: String = if number.contains(',') || dot_groups_thousands {
+        number.replace([' ', '.'], "").replace(',', ".")
+    } else {
+        number.replace(' ', "")
+    };
+
+    number.parse::<f64>().ok()
+}
+
+/// The first number of the text written the Portuguese way, "350.000" or "€ 1.250.000"
+pub fn parse_number(raw: &str) -> Option<f64> {
+    let input: String = clean_input(raw);
+    let number_match = NUMBER.find(&input)?;
+    let number: f64 = parse_portuguese_number(number_match.as_str())?;
+
+    if input[..number_match.start()].trim_end().ends_with('-') {
+        Some(-number)
+    } else {
+        Some(number)
+    }
+}
+
+/// Parses prices such as "350 000 €", "€ 1.250.000" or "1.250,50 €" into cents.
+/// Prices on request ("Preço sob consulta") or missing are `Ok(None)`.
+pub fn parse_price(raw: Option<&str>) -> Result<Option<u64>, ParseError> {
+    let input: String = match raw {
+        None => return Ok(None),
+        Some(raw) => clean_input(raw),
+    };
+    let error = |reason: &'static str| ParseError {
+        field: "price",
+        input: input.clone(),
+        reason,
+    };
+
+    if input.is_empty() || input.to_lowercase().contains("consulta") {
+        return Ok(None);
+    }
+
+    let number: &str = NUMBER
+        .find(&input)
+        .ok_or_else(|| error("no amount found"))?
+        .as_str();
+
+    let euros: f64 = parse_portuguese_number(number).ok_or_else(|| error("invalid amount"))?;
+
+    if euros <= 0.0 {
+        return Err(error("amount is not positive"));
+    }
+
+    Ok(Some((euros * 100.0).round() as u64))
+}
+
+/// Parses areas such as "120 m²", "1.200 m2" or "Área útil: 85,5 m²" into square meters
+pub fn parse_area(raw: &str) -> Result<f32, ParseError> {
+    let input: String = clean_input(raw);
+    let error = |reason: &'static str| ParseError {
+        field: "area",
+        input: input.clone(),
+        reason,
+    };
+
+    let captures = AREA
+        .captures(&input)
+        .ok_or_else(|| error("no area in m² found"))?;
+
+    let sqr_meters: f64 =
+        parse_portuguese_number(&captures[1]).ok_or_else(|| error("invalid area"))?;
+
+    if sqr_meters <= 0.0 {
+        return Err(error("area is not positive"));
+    }
+
+    Ok(sqr_meters as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_scraped_prices() {
+        let cases: [(Option<&str>, Option<u64>); 9] = [
+            (Some("350 000 €"), Some(35_000_000)),
+            (Some("€ 1.250.000"), Some(125_000_000)),
+            (Some("1.250,50 €"), Some(125_050)),
+            (Some("690.000&nbsp;€"), Some(69_000_000)),
+            (Some("1\u{a0}100 €/mês"), Some(110_000)),
+            (Some("Preço sob consulta"), None),
+            (Some("  "), None),
+            (Some("950"), Some(95_000)),
+            (None, None),
+        ];
+
+        for (raw, expected) in cases {
+            assert_eq!(parse_price(raw), Ok(expected), "price {:?}", raw);
+        }
+    }
+
+    #[test]
+    fn rejects_unparsable_prices() {
+        for raw in ["Contacte-nos", "€", "0 €", "--"] {
+            let error: ParseError = parse_price(Some(raw)).unwrap_err();
+            assert_eq!(error.field, "price", "price {:?}", raw);
+        }
+    }
+
+    #[test]
+    fn parses_scraped_areas() {
+        let cases: [(&str, f32); 7] = [
+            ("120 m²", 120.0),
+            ("t2 120 m²", 120.0),
+            ("moradia t3 200 m2", 200.0),
+            ("85,5 m²", 85.5),
+            ("1.200 m2", 1200.0),
+            ("Área útil: 82 m²", 82.0),
+            ("450\u{a0}m² área bruta", 450.0),
+        ];
+
+        for (raw, expected) in cases {
+            assert_eq!(parse_area(raw), Ok(expected), "area {:?}", raw);
+        }
+    }
+
+    #[test]
+    fn parses_numbers_the_portuguese_way() {
+        let cases: [(&str, Option<f64>); 9] = [
+            ("350.000", Some(350_000.0)),
+            ("€ 1.250.000", Some(1_250_000.0)),
+            ("1.250,50", Some(1_250.5)),
+            ("2.5", Some(2.5)),
+            ("3", Some(3.0)),
+            ("-12", Some(-12.0)),
+            ("cerca de 85 m²", Some(85.0)),
+            ("T2 120 m²", Some(2.0)),
+            ("n/a", None),
+        ];
+
+        for (raw, expected) in cases {
+            assert_eq!(parse_number(raw), expected, "number {:?}", raw);
+        }
+    }
+
+    #[test]
+    fn rejects_unparsable_areas() {
+        for raw in ["Área útil", "120 hectares", "0 m²", ""] {
+            let error: ParseError = parse_area(raw).unwrap_err();
+            assert_eq!(error.field, "area", "area {:?}", raw);
+        }
+    }
+}